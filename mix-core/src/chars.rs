@@ -0,0 +1,31 @@
+/// MIX character set, the index is the character code
+pub const SYMBOLS: [char; 56] = [
+    ' ', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{0394}', 'J', 'K', 'L', 'M', 'N', 'O',
+    'P', 'Q', 'R', '\u{2211}', '\u{03A0}', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '0', '1', '2',
+    '3', '4', '5', '6', '7', '8', '9', '.', ',', '(', ')', '+', '-', '*', '/', '=', '$', '<', '>',
+    '@', ';', ':', '\'',
+];
+
+pub fn char_code(c: char) -> Option<u8> {
+    SYMBOLS.iter().position(|s| *s == c).map(|code| code as u8)
+}
+
+pub fn code_char(code: u8) -> Option<char> {
+    SYMBOLS.get(code as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes() {
+        assert_eq!(Some(0), char_code(' '));
+        assert_eq!(Some(10), char_code('\u{0394}'));
+        assert_eq!(Some(30), char_code('0'));
+        assert_eq!(None, char_code('a'));
+
+        assert_eq!(Some('9'), code_char(39));
+        assert_eq!(None, code_char(56));
+    }
+}
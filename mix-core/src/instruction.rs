@@ -0,0 +1,567 @@
+use crate::word::Word;
+use crate::Bytes;
+
+use std::fmt;
+
+/*
+ * Every MIX operation is a variant, the word is +/- AA I F C
+ *
+ * operations with a free F (field spec, unit or count) -> Lda { addr, minus_zero, index, field }
+ * operations distinguished by F                        -> Jmp { addr, minus_zero, index }
+ *
+ * `minus_zero` is the address -0, e.g. ENTA -0 sets rA to -0. It's ignored when
+ * `addr` isn't 0, the sign of the others is the sign of `addr`
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodeError {
+    UnknownOperation { c: u8, f: u8 },
+    IndexOutOfRange(u8),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnknownOperation { c, f: field } => {
+                write!(f, "unknown operation C={c} F={field}")
+            }
+            DecodeError::IndexOutOfRange(i) => write!(f, "index register {i} is out of range 0-6"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncodeError {
+    AddressOutOfRange(i32),
+    IndexOutOfRange(u8),
+    FieldOutOfRange(u8),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::AddressOutOfRange(a) => {
+                write!(
+                    f,
+                    "address {a} doesn't fit in 2 bytes, it should be -4095..4095"
+                )
+            }
+            EncodeError::IndexOutOfRange(i) => write!(f, "index register {i} is out of range 0-6"),
+            EncodeError::FieldOutOfRange(field) => write!(f, "field {field} is out of range 0-63"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+macro_rules! instructions {
+    (
+        field: [ $( $fv:ident $fname:literal $fc:literal $ff:literal, )* ]
+        fixed: [ $( $v:ident $name:literal $c:literal $f:literal, )* ]
+    ) => {
+        #[derive(Debug, Copy, Clone, PartialEq)]
+        pub enum Instruction {
+            $( $fv { addr: i32, minus_zero: bool, index: u8, field: u8 }, )*
+            $( $v { addr: i32, minus_zero: bool, index: u8 }, )*
+        }
+
+        impl Instruction {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $( Instruction::$fv { .. } => $fname, )*
+                    $( Instruction::$v { .. } => $name, )*
+                }
+            }
+
+            /// (address, -0, I, F, C)
+            fn parts(&self) -> (i32, bool, u8, u8, u8) {
+                match *self {
+                    $( Instruction::$fv { addr, minus_zero, index, field } => {
+                        (addr, minus_zero, index, field, $fc)
+                    } )*
+                    $( Instruction::$v { addr, minus_zero, index } => {
+                        (addr, minus_zero, index, $f, $c)
+                    } )*
+                }
+            }
+
+            fn from_parts(
+                addr: i32,
+                minus_zero: bool,
+                index: u8,
+                field: u8,
+                c: u8,
+            ) -> Option<Instruction> {
+                match (c, field) {
+                    $( ($fc, _) => Some(Instruction::$fv { addr, minus_zero, index, field }), )*
+                    $( ($c, $f) => Some(Instruction::$v { addr, minus_zero, index }), )*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+instructions! {
+    field: [
+        Nop "NOP" 0 0,
+        Add "ADD" 1 5,
+        Sub "SUB" 2 5,
+        Mul "MUL" 3 5,
+        Div "DIV" 4 5,
+        Move "MOVE" 7 0,
+        Lda "LDA" 8 5,
+        Ld1 "LD1" 9 5,
+        Ld2 "LD2" 10 5,
+        Ld3 "LD3" 11 5,
+        Ld4 "LD4" 12 5,
+        Ld5 "LD5" 13 5,
+        Ld6 "LD6" 14 5,
+        Ldx "LDX" 15 5,
+        Ldan "LDAN" 16 5,
+        Ld1n "LD1N" 17 5,
+        Ld2n "LD2N" 18 5,
+        Ld3n "LD3N" 19 5,
+        Ld4n "LD4N" 20 5,
+        Ld5n "LD5N" 21 5,
+        Ld6n "LD6N" 22 5,
+        Ldxn "LDXN" 23 5,
+        Sta "STA" 24 5,
+        St1 "ST1" 25 5,
+        St2 "ST2" 26 5,
+        St3 "ST3" 27 5,
+        St4 "ST4" 28 5,
+        St5 "ST5" 29 5,
+        St6 "ST6" 30 5,
+        Stx "STX" 31 5,
+        Stj "STJ" 32 2,
+        Stz "STZ" 33 5,
+        Jbus "JBUS" 34 0,
+        Ioc "IOC" 35 0,
+        In "IN" 36 0,
+        Out "OUT" 37 0,
+        Jred "JRED" 38 0,
+        Cmpa "CMPA" 56 5,
+        Cmp1 "CMP1" 57 5,
+        Cmp2 "CMP2" 58 5,
+        Cmp3 "CMP3" 59 5,
+        Cmp4 "CMP4" 60 5,
+        Cmp5 "CMP5" 61 5,
+        Cmp6 "CMP6" 62 5,
+        Cmpx "CMPX" 63 5,
+    ]
+    fixed: [
+        Num "NUM" 5 0,
+        Char "CHAR" 5 1,
+        Hlt "HLT" 5 2,
+        Sla "SLA" 6 0,
+        Sra "SRA" 6 1,
+        Slax "SLAX" 6 2,
+        Srax "SRAX" 6 3,
+        Slc "SLC" 6 4,
+        Src "SRC" 6 5,
+        Jmp "JMP" 39 0,
+        Jsj "JSJ" 39 1,
+        Jov "JOV" 39 2,
+        Jnov "JNOV" 39 3,
+        Jl "JL" 39 4,
+        Je "JE" 39 5,
+        Jg "JG" 39 6,
+        Jge "JGE" 39 7,
+        Jne "JNE" 39 8,
+        Jle "JLE" 39 9,
+        Jan "JAN" 40 0,
+        Jaz "JAZ" 40 1,
+        Jap "JAP" 40 2,
+        Jann "JANN" 40 3,
+        Janz "JANZ" 40 4,
+        Janp "JANP" 40 5,
+        J1n "J1N" 41 0,
+        J2n "J2N" 42 0,
+        J3n "J3N" 43 0,
+        J4n "J4N" 44 0,
+        J5n "J5N" 45 0,
+        J6n "J6N" 46 0,
+        J1z "J1Z" 41 1,
+        J2z "J2Z" 42 1,
+        J3z "J3Z" 43 1,
+        J4z "J4Z" 44 1,
+        J5z "J5Z" 45 1,
+        J6z "J6Z" 46 1,
+        J1p "J1P" 41 2,
+        J2p "J2P" 42 2,
+        J3p "J3P" 43 2,
+        J4p "J4P" 44 2,
+        J5p "J5P" 45 2,
+        J6p "J6P" 46 2,
+        J1nn "J1NN" 41 3,
+        J2nn "J2NN" 42 3,
+        J3nn "J3NN" 43 3,
+        J4nn "J4NN" 44 3,
+        J5nn "J5NN" 45 3,
+        J6nn "J6NN" 46 3,
+        J1nz "J1NZ" 41 4,
+        J2nz "J2NZ" 42 4,
+        J3nz "J3NZ" 43 4,
+        J4nz "J4NZ" 44 4,
+        J5nz "J5NZ" 45 4,
+        J6nz "J6NZ" 46 4,
+        J1np "J1NP" 41 5,
+        J2np "J2NP" 42 5,
+        J3np "J3NP" 43 5,
+        J4np "J4NP" 44 5,
+        J5np "J5NP" 45 5,
+        J6np "J6NP" 46 5,
+        Jxn "JXN" 47 0,
+        Jxz "JXZ" 47 1,
+        Jxp "JXP" 47 2,
+        Jxnn "JXNN" 47 3,
+        Jxnz "JXNZ" 47 4,
+        Jxnp "JXNP" 47 5,
+        Inca "INCA" 48 0,
+        Deca "DECA" 48 1,
+        Enta "ENTA" 48 2,
+        Enna "ENNA" 48 3,
+        Inc1 "INC1" 49 0,
+        Inc2 "INC2" 50 0,
+        Inc3 "INC3" 51 0,
+        Inc4 "INC4" 52 0,
+        Inc5 "INC5" 53 0,
+        Inc6 "INC6" 54 0,
+        Dec1 "DEC1" 49 1,
+        Dec2 "DEC2" 50 1,
+        Dec3 "DEC3" 51 1,
+        Dec4 "DEC4" 52 1,
+        Dec5 "DEC5" 53 1,
+        Dec6 "DEC6" 54 1,
+        Ent1 "ENT1" 49 2,
+        Ent2 "ENT2" 50 2,
+        Ent3 "ENT3" 51 2,
+        Ent4 "ENT4" 52 2,
+        Ent5 "ENT5" 53 2,
+        Ent6 "ENT6" 54 2,
+        Enn1 "ENN1" 49 3,
+        Enn2 "ENN2" 50 3,
+        Enn3 "ENN3" 51 3,
+        Enn4 "ENN4" 52 3,
+        Enn5 "ENN5" 53 3,
+        Enn6 "ENN6" 54 3,
+        Incx "INCX" 55 0,
+        Decx "DECX" 55 1,
+        Entx "ENTX" 55 2,
+        Ennx "ENNX" 55 3,
+    ]
+}
+
+impl Instruction {
+    /// Word +/- AA I F C, an error if the address doesn't fit in 2 bytes,
+    /// the index isn't 0-6 or the field doesn't fit in a byte
+    pub fn encode(&self) -> Result<Word, EncodeError> {
+        let (addr, minus_zero, index, field, c) = self.parts();
+        if addr.unsigned_abs() > 0o7777 {
+            return Err(EncodeError::AddressOutOfRange(addr));
+        }
+        if index > 6 {
+            return Err(EncodeError::IndexOutOfRange(index));
+        }
+        if field > 0o77 {
+            return Err(EncodeError::FieldOutOfRange(field));
+        }
+        let sign = if addr < 0 || (addr == 0 && minus_zero) {
+            -1
+        } else {
+            0
+        };
+        let aa = addr.unsigned_abs();
+
+        Ok(Word::new_by_bytes(
+            sign,
+            &[(aa >> 6) as u8, (aa & 0o77) as u8, index, field, c],
+        ))
+    }
+
+    /// The sign of the address is kept, -0 sets `minus_zero`
+    pub fn decode(word: Word) -> Result<Instruction, DecodeError> {
+        let aa = ((word.get_byte(1) as i32) << 6) | word.get_byte(2) as i32;
+        let addr = if word.get_sign() < 0 { -aa } else { aa };
+        let minus_zero = word.get_sign() < 0 && aa == 0;
+        let index = word.get_byte(3);
+        let field = word.get_byte(4);
+        let c = word.get_byte(5);
+
+        if index > 6 {
+            return Err(DecodeError::IndexOutOfRange(index));
+        }
+        Instruction::from_parts(addr, minus_zero, index, field, c)
+            .ok_or(DecodeError::UnknownOperation { c, f: field })
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (addr, minus_zero, index, field, _) = self.parts();
+        match addr == 0 && minus_zero {
+            true => write!(f, "{} -0", self.name())?,
+            false => write!(f, "{} {}", self.name(), addr)?,
+        }
+        if index != 0 {
+            write!(f, ",{}", index)?;
+        }
+        match self {
+            // F is a count or an io unit
+            Instruction::Nop { .. }
+            | Instruction::Move { .. }
+            | Instruction::Jbus { .. }
+            | Instruction::Ioc { .. }
+            | Instruction::In { .. }
+            | Instruction::Out { .. }
+            | Instruction::Jred { .. } => write!(f, "({})", field),
+            _ if crate::opcodes::by_name(self.name()).is_none_or(|op| op.f == field) => Ok(()),
+            _ => write!(f, "({}:{})", field / 8, field % 8),
+        }
+    }
+}
+
+/// Start address and the words with their locations
+pub type Program = (u32, Vec<(u32, Word)>);
+
+/// Lays out words from an origin, like ORIG and the location counter of MIXAL
+pub struct ProgramBuilder {
+    location: u32,
+    start: u32,
+    words: Vec<(u32, Word)>,
+    error: Option<(u32, EncodeError)>, // the first instruction that can't be encoded
+}
+
+impl ProgramBuilder {
+    pub fn new(origin: u32) -> ProgramBuilder {
+        ProgramBuilder {
+            location: origin,
+            start: origin,
+            words: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Location of the next word
+    pub fn location(&self) -> u32 {
+        self.location
+    }
+
+    pub fn orig(mut self, location: u32) -> ProgramBuilder {
+        self.location = location;
+        self
+    }
+
+    pub fn start(mut self, start: u32) -> ProgramBuilder {
+        self.start = start;
+        self
+    }
+
+    /// An instruction that can't be encoded is reported by `build`
+    pub fn instruction(mut self, instruction: Instruction) -> ProgramBuilder {
+        match instruction.encode() {
+            Ok(word) => self.word(word),
+            Err(err) => {
+                self.error.get_or_insert((self.location, err));
+                self.word(Word::new(0))
+            }
+        }
+    }
+
+    pub fn con(self, value: i32) -> ProgramBuilder {
+        self.word(Word::new_from_signed(value))
+    }
+
+    pub fn word(mut self, word: Word) -> ProgramBuilder {
+        self.words.push((self.location, word));
+        self.location += 1;
+        self
+    }
+
+    /// The program, or the location and the error of the first instruction that can't be encoded
+    pub fn build(self) -> Result<Program, (u32, EncodeError)> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok((self.start, self.words)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opcodes::OPCODES;
+
+    #[test]
+    fn encode() {
+        let lda = Instruction::Lda {
+            addr: -2000,
+            minus_zero: false,
+            index: 3,
+            field: 13,
+        };
+        assert_eq!(
+            Ok(Word::new_by_bytes(-1, &[31, 16, 3, 13, 8])),
+            lda.encode()
+        );
+        assert_eq!("LDA -2000,3(1:5)", lda.to_string());
+
+        let jmp = Instruction::Jsj {
+            addr: 3000,
+            minus_zero: false,
+            index: 0,
+        };
+        assert_eq!(Ok(Word::new_by_bytes(0, &[46, 56, 0, 1, 39])), jmp.encode());
+        assert_eq!("JSJ 3000", jmp.to_string());
+
+        let out = Instruction::Out {
+            addr: 1995,
+            minus_zero: false,
+            index: 0,
+            field: 18,
+        };
+        assert_eq!("OUT 1995(18)", out.to_string());
+    }
+
+    #[test]
+    fn decode_all_operations() {
+        for op in OPCODES {
+            let word = Word::new_by_bytes(-1, &[1, 2, 4, op.f, op.c]);
+            let instruction = Instruction::decode(word).expect(op.name);
+
+            assert_eq!(op.name, instruction.name());
+            assert_eq!(Ok(word), instruction.encode());
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // every operation at the limits of the address, the index and the field
+        for op in OPCODES {
+            // (0, true) is the address -0
+            let addresses = [-4095, -4094, -1, 0, 1, 4094, 4095]
+                .map(|addr| (addr, false))
+                .into_iter()
+                .chain([(0, true)]);
+            for (addr, minus_zero) in addresses {
+                for index in 0..=6 {
+                    let fields = match Instruction::from_parts(addr, false, index, 63, op.c) {
+                        Some(_) => vec![0, 1, op.f, 62, 63],
+                        None => vec![op.f],
+                    };
+                    for field in fields {
+                        let instruction =
+                            Instruction::from_parts(addr, minus_zero, index, field, op.c)
+                                .expect(op.name);
+                        let word = instruction.encode().expect(op.name);
+                        assert_eq!(Ok(instruction), Instruction::decode(word));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn minus_zero() {
+        // ENTA -0 and ENNA -0
+        for (word, text) in [
+            (Word::new_by_bytes(-1, &[0, 0, 0, 2, 48]), "ENTA -0"),
+            (Word::new_by_bytes(-1, &[0, 0, 0, 3, 48]), "ENNA -0"),
+        ] {
+            let instruction = Instruction::decode(word).expect(text);
+            assert_eq!(text, instruction.to_string());
+            assert_eq!(Ok(word), instruction.encode());
+        }
+
+        let enta = |minus_zero| Instruction::Enta {
+            addr: 0,
+            minus_zero,
+            index: 0,
+        };
+        assert_eq!(
+            Ok(enta(true)),
+            Instruction::decode(Word::new_by_bytes(-1, &[0, 0, 0, 2, 48]))
+        );
+        assert_eq!(
+            Ok(enta(false)),
+            Instruction::decode(Word::new_by_bytes(0, &[0, 0, 0, 2, 48]))
+        );
+        assert_eq!("ENTA 0", enta(false).to_string());
+    }
+
+    #[test]
+    fn encode_errors() {
+        let lda = |addr, index, field| {
+            Instruction::Lda {
+                addr,
+                minus_zero: false,
+                index,
+                field,
+            }
+            .encode()
+        };
+        assert_eq!(Err(EncodeError::AddressOutOfRange(4096)), lda(4096, 0, 5));
+        assert_eq!(Err(EncodeError::AddressOutOfRange(-4096)), lda(-4096, 0, 5));
+        assert_eq!(Err(EncodeError::IndexOutOfRange(7)), lda(0, 7, 5));
+        assert_eq!(Err(EncodeError::FieldOutOfRange(64)), lda(0, 0, 64));
+
+        let result = ProgramBuilder::new(3000)
+            .instruction(Instruction::Hlt {
+                addr: 0,
+                minus_zero: false,
+                index: 0,
+            })
+            .instruction(Instruction::Jmp {
+                addr: 4096,
+                minus_zero: false,
+                index: 0,
+            })
+            .build();
+        assert_eq!(Err((3001, EncodeError::AddressOutOfRange(4096))), result);
+    }
+
+    #[test]
+    fn decode_errors() {
+        assert_eq!(
+            Err(DecodeError::UnknownOperation { c: 5, f: 9 }),
+            Instruction::decode(Word::new_by_bytes(0, &[0, 0, 0, 9, 5]))
+        );
+        assert_eq!(
+            Err(DecodeError::IndexOutOfRange(7)),
+            Instruction::decode(Word::new_by_bytes(0, &[0, 0, 7, 5, 8]))
+        );
+    }
+
+    #[test]
+    fn build_program() {
+        let (start, words) = ProgramBuilder::new(3000)
+            .instruction(Instruction::Ent1 {
+                addr: 5,
+                minus_zero: false,
+                index: 0,
+            })
+            .instruction(Instruction::Hlt {
+                addr: 0,
+                minus_zero: false,
+                index: 0,
+            })
+            .orig(100)
+            .con(-7)
+            .start(3001)
+            .build()
+            .expect("valid program");
+
+        assert_eq!(3001, start);
+        assert_eq!(
+            vec![
+                (3000, Word::new_by_bytes(0, &[0, 5, 0, 2, 49])),
+                (3001, Word::new_by_bytes(0, &[0, 0, 0, 2, 5])),
+                (100, Word::new_from_signed(-7)),
+            ],
+            words
+        );
+    }
+}
@@ -0,0 +1,34 @@
+/*
+ * MIX object file written by mixal and loaded by mix, all numbers are big-endian
+ *
+ * header   -> magic "MIXO" || version u16 || start u16 || segments u16
+ * segment  -> address u16 || count u16 || count * word u32
+ * section  -> id u8 || length u32 || payload   (repeated until the end of file)
+ *
+ * word is stored as 30 bits of bytes 1-5 and the sign in bit 31
+ *
+ * section 1, symbols     -> (kind u8 || value i32 || name length u8 || name utf-8)*
+ *                           kind: 0 EQU, 1 label, 2 local, 3 literal, 4 extern
+ * section 2, lines       -> (address u16 || source line u32)*
+ *
+ * relocatable module, its addresses are offsets from the base chosen by the linker,
+ * the emulator refuses to load it
+ *
+ * section 3, module      -> size u16 || has start u8
+ * section 4, relocations -> (address u16 || field u8)*
+ *                           field: 0 address of an instruction, 1 whole word
+ * section 5, entries     -> (relative u8 || value i32 || name length u8 || name utf-8)*
+ * section 6, externals   -> (address u16 || field u8 || name length u8 || name utf-8)*
+ *
+ * unknown sections are skipped by readers
+ */
+
+pub const MAGIC: &[u8; 4] = b"MIXO";
+pub const VERSION: u16 = 1;
+
+pub const SECTION_SYMBOLS: u8 = 1;
+pub const SECTION_LINES: u8 = 2;
+pub const SECTION_MODULE: u8 = 3;
+pub const SECTION_RELOCATIONS: u8 = 4;
+pub const SECTION_ENTRIES: u8 = 5;
+pub const SECTION_EXTERNALS: u8 = 6;
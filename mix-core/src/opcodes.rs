@@ -0,0 +1,194 @@
+/// MIX operation: mnemonic, operation code C and the default (or fixed) field F
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OpCode {
+    pub name: &'static str,
+    pub c: u8,
+    pub f: u8,
+}
+
+impl OpCode {
+    const fn new(name: &'static str, c: u8, f: u8) -> OpCode {
+        OpCode { name, c, f }
+    }
+}
+
+pub const OPCODES: [OpCode; 144] = [
+    OpCode::new("NOP", 0, 0),
+    OpCode::new("ADD", 1, 5),
+    OpCode::new("SUB", 2, 5),
+    OpCode::new("MUL", 3, 5),
+    OpCode::new("DIV", 4, 5),
+    OpCode::new("NUM", 5, 0),
+    OpCode::new("CHAR", 5, 1),
+    OpCode::new("HLT", 5, 2),
+    OpCode::new("SLA", 6, 0),
+    OpCode::new("SRA", 6, 1),
+    OpCode::new("SLAX", 6, 2),
+    OpCode::new("SRAX", 6, 3),
+    OpCode::new("SLC", 6, 4),
+    OpCode::new("SRC", 6, 5),
+    OpCode::new("MOVE", 7, 0),
+    OpCode::new("LDA", 8, 5),
+    OpCode::new("LD1", 9, 5),
+    OpCode::new("LD2", 10, 5),
+    OpCode::new("LD3", 11, 5),
+    OpCode::new("LD4", 12, 5),
+    OpCode::new("LD5", 13, 5),
+    OpCode::new("LD6", 14, 5),
+    OpCode::new("LDX", 15, 5),
+    OpCode::new("LDAN", 16, 5),
+    OpCode::new("LD1N", 17, 5),
+    OpCode::new("LD2N", 18, 5),
+    OpCode::new("LD3N", 19, 5),
+    OpCode::new("LD4N", 20, 5),
+    OpCode::new("LD5N", 21, 5),
+    OpCode::new("LD6N", 22, 5),
+    OpCode::new("LDXN", 23, 5),
+    OpCode::new("STA", 24, 5),
+    OpCode::new("ST1", 25, 5),
+    OpCode::new("ST2", 26, 5),
+    OpCode::new("ST3", 27, 5),
+    OpCode::new("ST4", 28, 5),
+    OpCode::new("ST5", 29, 5),
+    OpCode::new("ST6", 30, 5),
+    OpCode::new("STX", 31, 5),
+    OpCode::new("STJ", 32, 2),
+    OpCode::new("STZ", 33, 5),
+    OpCode::new("JBUS", 34, 0),
+    OpCode::new("IOC", 35, 0),
+    OpCode::new("IN", 36, 0),
+    OpCode::new("OUT", 37, 0),
+    OpCode::new("JRED", 38, 0),
+    OpCode::new("JMP", 39, 0),
+    OpCode::new("JSJ", 39, 1),
+    OpCode::new("JOV", 39, 2),
+    OpCode::new("JNOV", 39, 3),
+    OpCode::new("JL", 39, 4),
+    OpCode::new("JE", 39, 5),
+    OpCode::new("JG", 39, 6),
+    OpCode::new("JGE", 39, 7),
+    OpCode::new("JNE", 39, 8),
+    OpCode::new("JLE", 39, 9),
+    OpCode::new("JAN", 40, 0),
+    OpCode::new("JAZ", 40, 1),
+    OpCode::new("JAP", 40, 2),
+    OpCode::new("JANN", 40, 3),
+    OpCode::new("JANZ", 40, 4),
+    OpCode::new("JANP", 40, 5),
+    OpCode::new("J1N", 41, 0),
+    OpCode::new("J2N", 42, 0),
+    OpCode::new("J3N", 43, 0),
+    OpCode::new("J4N", 44, 0),
+    OpCode::new("J5N", 45, 0),
+    OpCode::new("J6N", 46, 0),
+    OpCode::new("J1Z", 41, 1),
+    OpCode::new("J2Z", 42, 1),
+    OpCode::new("J3Z", 43, 1),
+    OpCode::new("J4Z", 44, 1),
+    OpCode::new("J5Z", 45, 1),
+    OpCode::new("J6Z", 46, 1),
+    OpCode::new("J1P", 41, 2),
+    OpCode::new("J2P", 42, 2),
+    OpCode::new("J3P", 43, 2),
+    OpCode::new("J4P", 44, 2),
+    OpCode::new("J5P", 45, 2),
+    OpCode::new("J6P", 46, 2),
+    OpCode::new("J1NN", 41, 3),
+    OpCode::new("J2NN", 42, 3),
+    OpCode::new("J3NN", 43, 3),
+    OpCode::new("J4NN", 44, 3),
+    OpCode::new("J5NN", 45, 3),
+    OpCode::new("J6NN", 46, 3),
+    OpCode::new("J1NZ", 41, 4),
+    OpCode::new("J2NZ", 42, 4),
+    OpCode::new("J3NZ", 43, 4),
+    OpCode::new("J4NZ", 44, 4),
+    OpCode::new("J5NZ", 45, 4),
+    OpCode::new("J6NZ", 46, 4),
+    OpCode::new("J1NP", 41, 5),
+    OpCode::new("J2NP", 42, 5),
+    OpCode::new("J3NP", 43, 5),
+    OpCode::new("J4NP", 44, 5),
+    OpCode::new("J5NP", 45, 5),
+    OpCode::new("J6NP", 46, 5),
+    OpCode::new("JXN", 47, 0),
+    OpCode::new("JXZ", 47, 1),
+    OpCode::new("JXP", 47, 2),
+    OpCode::new("JXNN", 47, 3),
+    OpCode::new("JXNZ", 47, 4),
+    OpCode::new("JXNP", 47, 5),
+    OpCode::new("INCA", 48, 0),
+    OpCode::new("DECA", 48, 1),
+    OpCode::new("ENTA", 48, 2),
+    OpCode::new("ENNA", 48, 3),
+    OpCode::new("INC1", 49, 0),
+    OpCode::new("INC2", 50, 0),
+    OpCode::new("INC3", 51, 0),
+    OpCode::new("INC4", 52, 0),
+    OpCode::new("INC5", 53, 0),
+    OpCode::new("INC6", 54, 0),
+    OpCode::new("DEC1", 49, 1),
+    OpCode::new("DEC2", 50, 1),
+    OpCode::new("DEC3", 51, 1),
+    OpCode::new("DEC4", 52, 1),
+    OpCode::new("DEC5", 53, 1),
+    OpCode::new("DEC6", 54, 1),
+    OpCode::new("ENT1", 49, 2),
+    OpCode::new("ENT2", 50, 2),
+    OpCode::new("ENT3", 51, 2),
+    OpCode::new("ENT4", 52, 2),
+    OpCode::new("ENT5", 53, 2),
+    OpCode::new("ENT6", 54, 2),
+    OpCode::new("ENN1", 49, 3),
+    OpCode::new("ENN2", 50, 3),
+    OpCode::new("ENN3", 51, 3),
+    OpCode::new("ENN4", 52, 3),
+    OpCode::new("ENN5", 53, 3),
+    OpCode::new("ENN6", 54, 3),
+    OpCode::new("INCX", 55, 0),
+    OpCode::new("DECX", 55, 1),
+    OpCode::new("ENTX", 55, 2),
+    OpCode::new("ENNX", 55, 3),
+    OpCode::new("CMPA", 56, 5),
+    OpCode::new("CMP1", 57, 5),
+    OpCode::new("CMP2", 58, 5),
+    OpCode::new("CMP3", 59, 5),
+    OpCode::new("CMP4", 60, 5),
+    OpCode::new("CMP5", 61, 5),
+    OpCode::new("CMP6", 62, 5),
+    OpCode::new("CMPX", 63, 5),
+];
+
+/// Operation by its mnemonic, e.g. `LDA`
+pub fn by_name(name: &str) -> Option<&'static OpCode> {
+    OPCODES.iter().find(|op| op.name == name)
+}
+
+/// Mnemonic of the instruction, for operations distinguished by F (e.g. `JMP`/`JSJ`) the F must match,
+/// for the others (e.g. `LDA`) any F is a field spec
+pub fn by_code(c: u8, f: u8) -> Option<&'static OpCode> {
+    let ops: Vec<&OpCode> = OPCODES.iter().filter(|op| op.c == c).collect();
+    match ops.len() {
+        0 => None,
+        1 => Some(ops[0]),
+        _ => ops.into_iter().find(|op| op.f == f),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_opcodes() {
+        assert_eq!(Some(&OpCode::new("LDA", 8, 5)), by_name("LDA"));
+        assert_eq!(None, by_name("LDB"));
+
+        assert_eq!("LDA", by_code(8, 3).expect("LDA").name);
+        assert_eq!("JSJ", by_code(39, 1).expect("JSJ").name);
+        assert_eq!("HLT", by_code(5, 2).expect("HLT").name);
+        assert_eq!(None, by_code(5, 9));
+        assert_eq!("JBUS", by_code(34, 18).expect("JBUS").name);
+        assert_eq!(None, by_code(64, 0));
+    }
+}
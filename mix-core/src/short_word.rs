@@ -1,10 +1,10 @@
-use crate::memory::word::Word;
-use crate::memory::word::BYTES;
-use crate::memory::word::BYTE_4;
-use crate::memory::word::BYTE_5;
-use crate::memory::word::SIGN;
-use crate::memory::Bytes;
-use crate::memory::word_access::WordAccess;
+use crate::word::Word;
+use crate::word::BYTES;
+use crate::word::BYTE_4;
+use crate::word::BYTE_5;
+use crate::word::SIGN;
+use crate::Bytes;
+use crate::word_access::WordAccess;
 
 pub const MAX_2_BYTES: i32 = 4095; 
 
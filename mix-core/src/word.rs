@@ -1,6 +1,6 @@
-use crate::memory::word_access::WordAccess;
-use crate::memory::Bytes;
-use crate::memory::Instruction;
+use crate::word_access::WordAccess;
+use crate::Bytes;
+use crate::Instruction;
 
 pub const ABS: u32 = 0b00_111111_111111_111111_111111_111111;
 
@@ -0,0 +1,197 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const USAGE: &str = "usage: mix [options] <program.mix|program.mixo|program.deck>
+
+options:
+  --start ADDR         start from ADDR instead of the program start address
+  --max-steps N        stop after N executed instructions
+  --max-time U         stop after U units of MIX time
+  --timeout SECONDS    stop after SECONDS of wall-clock time
+  --device UNIT=PATH   attach a file to the io unit, can be repeated
+  --output-dir DIR     directory for the files of unmapped output units
+  --dump               print registers and non-zero memory when the run ends
+  -q, --quiet          print nothing but the program output
+  -v, --verbose        trace every executed instruction to stderr
+  -h, --help           print this help
+
+exit code: 0 the program stopped, 1 it can't be loaded, 2 a limit is reached,
+           3 an instruction can't be executed";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub program: String,
+    pub start: Option<u32>,
+    pub max_steps: Option<u64>,
+    pub max_time: Option<u64>,
+    pub timeout: Option<Duration>,
+    pub devices: Vec<(u8, String)>,
+    pub output_dir: Option<String>,
+    pub dump: bool,
+    pub verbosity: Verbosity,
+    pub help: bool,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            program: String::new(),
+            start: None,
+            max_steps: None,
+            max_time: None,
+            timeout: None,
+            devices: Vec::new(),
+            output_dir: None,
+            dump: false,
+            verbosity: Verbosity::Normal,
+            help: false,
+        }
+    }
+}
+
+/// Parses the command line arguments without the program name
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::new();
+    let mut program: Option<String> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or(format!("{name} requires a value"))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            "--dump" => options.dump = true,
+            "--start" => {
+                let addr = parse_number::<u32>("--start", &value("--start")?)?;
+                if addr > 3_999 {
+                    return Err(format!("--start address {addr} is out of range 0-3999"));
+                }
+                options.start = Some(addr);
+            }
+            "--max-steps" => {
+                options.max_steps = Some(parse_number("--max-steps", &value("--max-steps")?)?)
+            }
+            "--max-time" => {
+                options.max_time = Some(parse_number("--max-time", &value("--max-time")?)?)
+            }
+            "--timeout" => {
+                let seconds = parse_number::<f64>("--timeout", &value("--timeout")?)?;
+                options.timeout = Some(
+                    Duration::try_from_secs_f64(seconds)
+                        .map_err(|_| format!("--timeout: '{seconds}' is not a valid duration"))?,
+                );
+            }
+            "--output-dir" => options.output_dir = Some(value("--output-dir")?),
+            "--device" => {
+                let mapping = value("--device")?;
+                let (unit, path) = mapping
+                    .split_once('=')
+                    .ok_or(format!("--device expects UNIT=PATH, found '{mapping}'"))?;
+                let unit = parse_number::<u8>("--device", unit)?;
+                if unit > 20 {
+                    return Err(format!("io unit {unit} is out of range 0-20"));
+                }
+                options.devices.push((unit, path.to_string()));
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => match program {
+                Some(_) => return Err(format!("unexpected argument {arg}")),
+                None => program = Some(arg.to_string()),
+            },
+        }
+    }
+
+    match program {
+        Some(program) => options.program = program,
+        None if options.help => {}
+        None => return Err("program path is missing".to_string()),
+    }
+
+    Ok(options)
+}
+
+fn parse_number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    T::from_str(value).map_err(|_| format!("{option}: '{value}' is not a valid number"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = parse_args(&args(
+            "--start 3000 --max-steps 100 --max-time 5000 --timeout 1.5 --device 18=out.txt --device 16=in.deck \
+             --output-dir out -q --dump primes.mix",
+        ))
+        .expect("valid options");
+
+        assert_eq!("primes.mix", options.program);
+        assert_eq!(Some(3000), options.start);
+        assert_eq!(Some(100), options.max_steps);
+        assert_eq!(Some(5000), options.max_time);
+        assert_eq!(Some(Duration::from_millis(1500)), options.timeout);
+        assert_eq!(
+            vec![(18, "out.txt".to_string()), (16, "in.deck".to_string())],
+            options.devices
+        );
+        assert_eq!(Some("out".to_string()), options.output_dir);
+        assert_eq!(Verbosity::Quiet, options.verbosity);
+        assert!(options.dump);
+
+        let options = parse_args(&args("-v p.mix")).expect("valid options");
+        assert_eq!(Verbosity::Verbose, options.verbosity);
+        assert_eq!(None, options.start);
+        assert!(!options.dump);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases = [
+            ("", "program path is missing"),
+            ("p.mix q.mix", "unexpected argument q.mix"),
+            ("--fast p.mix", "unknown option --fast"),
+            ("p.mix --start", "--start requires a value"),
+            (
+                "--start 4000 p.mix",
+                "--start address 4000 is out of range 0-3999",
+            ),
+            (
+                "--max-steps x p.mix",
+                "--max-steps: 'x' is not a valid number",
+            ),
+            (
+                "--timeout -1 p.mix",
+                "--timeout: '-1' is not a valid duration",
+            ),
+            (
+                "--device 18 p.mix",
+                "--device expects UNIT=PATH, found '18'",
+            ),
+            ("--device 21=x p.mix", "io unit 21 is out of range 0-20"),
+        ];
+
+        for (line, err) in cases {
+            assert_eq!(Err(err.to_string()), parse_args(&args(line)), "{line}");
+        }
+
+        assert!(parse_args(&args("--help")).expect("help").help);
+    }
+}
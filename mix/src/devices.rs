@@ -0,0 +1,195 @@
+use crate::memory::word::Word;
+use crate::memory::Bytes;
+use crate::mix::load_error::*;
+use crate::operations::io::IO_FILE_PREFIX;
+use mix_core::chars::char_code;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+pub const CARD_READER_UNIT: u8 = 16;
+pub const CARD_COLUMNS: usize = 80;
+pub const CARD_BLOCK: usize = 16;
+pub const PRINTER_UNIT: u8 = 18;
+pub const PRINTER_BLOCK: usize = 24;
+
+/// Card reader: unit 16, every card is 80 columns = 16 words of 5 characters
+pub struct CardReader {
+    cards: Vec<String>,
+    next_card: usize,
+}
+
+impl CardReader {
+    pub fn new(cards: Vec<String>) -> CardReader {
+        CardReader {
+            cards,
+            next_card: 0,
+        }
+    }
+
+    /// Reads a deck, one card per line
+    pub fn from_file(path: &str) -> Result<CardReader, LoadError> {
+        let deck = fs::read_to_string(path)
+            .map_err(|e| LoadError::new(path, 0, LoadProblem::CantRead(e.to_string())))?;
+
+        CardReader::from_str(path, &deck)
+    }
+
+    /// Same as `from_file`, `name` is used for the error messages only.
+    /// Every card must fit 80 columns and have only MIX characters
+    pub fn from_str(name: &str, deck: &str) -> Result<CardReader, LoadError> {
+        let mut cards = Vec::new();
+        for (i, line) in deck.lines().enumerate() {
+            let card = line.trim_end_matches('\r');
+            let err = |problem| LoadError::new(name, i + 1, problem);
+
+            if let Some(c) = card.chars().find(|c| char_code(*c).is_none()) {
+                return Err(err(LoadProblem::UnknownCardSymbol(c)));
+            }
+            let columns = card.chars().count();
+            if columns > CARD_COLUMNS {
+                return Err(err(LoadProblem::CardTooLong(columns)));
+            }
+            cards.push(card.to_string());
+        }
+
+        Ok(CardReader::new(cards))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.next_card >= self.cards.len()
+    }
+
+    /// Next card as 16 words, an error if the reader is empty or the card isn't valid
+    pub fn read(&mut self) -> Result<Vec<Word>, String> {
+        let card = match self.cards.get(self.next_card) {
+            None => return Err(format!("card reader is empty, no card {}", self.next_card + 1)),
+            Some(card) => card,
+        };
+        self.next_card += 1;
+
+        let mut columns = card
+            .chars()
+            .map(|c| char_code(c).ok_or(format!("unsupported card symbol '{c}'")))
+            .collect::<Result<Vec<u8>, String>>()?;
+        if columns.len() > CARD_COLUMNS {
+            return Err(format!(
+                "card {} is longer than {CARD_COLUMNS} columns",
+                self.next_card
+            ));
+        }
+        columns.resize(CARD_COLUMNS, 0);
+
+        Ok(columns
+            .chunks(5)
+            .map(|bytes| Word::new_by_bytes(0, bytes))
+            .collect())
+    }
+}
+
+pub struct Devices {
+    card_reader: CardReader,
+    output_dir: PathBuf,
+    unit_paths: HashMap<u8, PathBuf>,
+}
+
+impl Default for Devices {
+    fn default() -> Devices {
+        Devices::new()
+    }
+}
+
+impl Devices {
+    pub fn new() -> Devices {
+        Devices {
+            card_reader: CardReader::new(Vec::new()),
+            output_dir: PathBuf::new(),
+            unit_paths: HashMap::new(),
+        }
+    }
+
+    /// Directory for the files of output units without an explicit mapping
+    pub fn set_output_dir(&mut self, dir: &str) {
+        self.output_dir = PathBuf::from(dir);
+    }
+
+    /// Attaches a file to the unit, the card reader reads its deck from the file right away
+    pub fn map_unit(&mut self, unit: u8, path: &str) -> Result<(), LoadError> {
+        if unit == CARD_READER_UNIT {
+            self.set_card_reader(CardReader::from_file(path)?);
+        }
+        self.unit_paths.insert(unit, PathBuf::from(path));
+        Ok(())
+    }
+
+    pub fn unit_path(&self, unit: u8) -> PathBuf {
+        match self.unit_paths.get(&unit) {
+            Some(path) => path.clone(),
+            None => self
+                .output_dir
+                .join(IO_FILE_PREFIX.to_string() + &unit.to_string()),
+        }
+    }
+
+    pub fn set_card_reader(&mut self, card_reader: CardReader) {
+        self.card_reader = card_reader;
+    }
+
+    pub fn card_reader(&mut self) -> &mut CardReader {
+        &mut self.card_reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_card() {
+        let mut reader = CardReader::new(vec![" O O6 Z O6".to_string()]);
+
+        let words = reader.read().expect("card");
+        assert_eq!(CARD_BLOCK, words.len());
+        assert_eq!(Word::new_by_bytes(0, &[0, 16, 0, 16, 36]), words[0]);
+        assert_eq!(Word::new_by_bytes(0, &[0, 29, 0, 16, 36]), words[1]);
+        assert_eq!(Word::new(0), words[15]);
+        assert!(reader.is_empty());
+        assert_eq!(
+            Err("card reader is empty, no card 2".to_string()),
+            reader.read()
+        );
+    }
+
+    #[test]
+    fn deck_errors() {
+        let card = "X".repeat(CARD_COLUMNS + 1);
+        let cases = [
+            ("TRANS0\n", None),
+            ("A\nA#\n", Some((2, LoadProblem::UnknownCardSymbol('#')))),
+            (&card[..], Some((1, LoadProblem::CardTooLong(81)))),
+        ];
+
+        for (deck, problem) in cases {
+            let result = CardReader::from_str("p.deck", deck).err();
+            let expected = problem.map(|(line, problem)| LoadError::new("p.deck", line, problem));
+            assert_eq!(expected, result, "{deck}");
+        }
+
+        let mut devices = Devices::new();
+        let err = devices.map_unit(16, "no/such.deck").expect_err("missing deck");
+        assert_eq!("no/such.deck", err.path);
+        assert!(matches!(err.problem, LoadProblem::CantRead(_)));
+    }
+
+    #[test]
+    fn unit_paths() {
+        let mut devices = Devices::new();
+        assert_eq!(PathBuf::from("io_unit_18"), devices.unit_path(18));
+
+        devices.set_output_dir("out");
+        devices.map_unit(17, "punch.txt").expect("output unit");
+        assert_eq!(PathBuf::from("out/io_unit_18"), devices.unit_path(18));
+        assert_eq!(PathBuf::from("punch.txt"), devices.unit_path(17));
+    }
+}
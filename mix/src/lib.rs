@@ -0,0 +1,33 @@
+//! MIX computer from "The Art of Computer Programming" by Donald Knuth
+//!
+//! ```
+//! use mix::{RunOutcome, MIX};
+//!
+//! let mut mix = MIX::new();
+//! // ENTA 5 ; HLT
+//! mix.load_str("program", "3000, 5, 0, 2, 48\n3001, 0, 0, 2, 5\n3000\n")
+//!     .expect("valid program");
+//!
+//! assert_eq!(RunOutcome::Halted, mix.execute());
+//! assert_eq!(5, mix.registers().get_a().get_signed_value());
+//! ```
+
+pub mod devices;
+pub mod memory;
+pub mod mix;
+pub mod operations;
+pub mod processor;
+pub mod registers;
+
+pub use crate::devices::CardReader;
+pub use crate::devices::Devices;
+pub use crate::memory::short_word::ShortWord;
+pub use crate::memory::word::Word;
+pub use crate::memory::Memory;
+pub use crate::mix::load_error::LoadError;
+pub use crate::mix::load_error::LoadProblem;
+pub use crate::mix::MIX;
+pub use crate::processor::Limits;
+pub use crate::processor::Processor;
+pub use crate::processor::RunOutcome;
+pub use crate::registers::Registers;
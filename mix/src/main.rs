@@ -1,19 +1,101 @@
 use std::env;
-use crate::mix::MIX;
+use std::process;
+use crate::cli::Verbosity;
+use mix::Limits;
+use mix::RunOutcome;
+use mix::MIX;
 
-pub mod memory;
-pub mod mix;
-pub mod operations;
-pub mod processor;
-pub mod registers;
+mod cli;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let program_path = &args[1];
+    let options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("mix: {err}\n\n{}", cli::USAGE);
+            process::exit(1);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let program_path = &options.program;
+    let is_deck = program_path.ends_with(".deck");
+    if is_deck && options.start.is_some() {
+        eprintln!("mix: --start can't be used with a card deck, it always starts from 0000");
+        process::exit(1);
+    }
 
     let mut mix = MIX::new();
 
-    mix.load(program_path);
-    mix.execute();
+    if let Some(dir) = &options.output_dir {
+        mix.devices_mut().set_output_dir(dir);
+    }
+    for (unit, path) in &options.devices {
+        if let Err(err) = mix.devices_mut().map_unit(*unit, path) {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+    }
+    mix.set_limits(Limits {
+        max_steps: options.max_steps,
+        max_time: options.max_time,
+        timeout: options.timeout,
+    });
+    mix.set_trace(options.verbosity == Verbosity::Verbose);
+
+    let outcome = if is_deck {
+        if let Err(err) = mix.load_deck(program_path) {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+        mix.go()
+    } else {
+        let loaded = if program_path.ends_with(".mixo") {
+            mix.load_object(program_path)
+        } else {
+            mix.load(program_path)
+        };
+        if let Err(err) = loaded {
+            eprintln!("{err}");
+            process::exit(1);
+        }
+        if let Some(start) = options.start {
+            mix.set_start(start);
+        }
+        mix.execute()
+    };
+
+    if options.verbosity != Verbosity::Quiet {
+        let proc = mix.processor();
+        let how = match &outcome {
+            RunOutcome::Halted => "halted",
+            RunOutcome::Exited => "left the memory",
+            RunOutcome::StepLimit => "reached the step limit",
+            RunOutcome::TimeLimit => "reached the time limit",
+            RunOutcome::Timeout => "timed out",
+            RunOutcome::Fault { .. } => "stopped by a fault",
+        };
+        eprintln!(
+            "{how} at {:04} after {} steps, {}u",
+            proc.get_addr(),
+            proc.get_steps(),
+            proc.get_time()
+        );
+    }
+    if let RunOutcome::Fault { addr, message } = &outcome {
+        eprintln!("mix: fault at {addr:04}: {message}");
+    }
+    if options.dump {
+        print!("{}", mix.dump());
+    }
+
+    match outcome {
+        RunOutcome::Halted | RunOutcome::Exited => {}
+        RunOutcome::StepLimit | RunOutcome::TimeLimit | RunOutcome::Timeout => process::exit(2),
+        RunOutcome::Fault { .. } => process::exit(3),
+    }
 }
@@ -1,21 +1,39 @@
+use crate::devices::CardReader;
+use crate::devices::Devices;
 use crate::memory::short_word::ShortWord;
+use crate::memory::short_word::MAX_2_BYTES;
 use crate::memory::word::Word;
+use crate::memory::word::MAX_5_BYTES;
+use crate::memory::word_access::WordAccess;
 use crate::memory::Bytes;
 use crate::memory::Instruction;
 use crate::memory::Memory;
+use crate::mix::load_error::*;
+use crate::mix::object::*;
+use crate::processor::Limits;
 use crate::processor::Processor;
+use crate::processor::RunOutcome;
 use crate::registers::Registers;
 
-use std::fs::File;
-use std::io;
-use std::io::prelude::*;
-use std::io::BufReader;
+use std::fs;
 use std::str::FromStr;
 
+pub mod load_error;
+pub mod object;
+
 pub struct MIX {
     reg: Registers,
     mem: Memory,
     proc: Processor,
+    devices: Devices,
+    symbols: Vec<Symbol>,
+    source_lines: Vec<(u32, u32)>,
+}
+
+impl Default for MIX {
+    fn default() -> MIX {
+        MIX::new()
+    }
 }
 
 impl MIX {
@@ -24,88 +42,272 @@ impl MIX {
             reg: Registers::new(),
             mem: Memory::new(),
             proc: Processor::new(),
+            devices: Devices::new(),
+            symbols: Vec::new(),
+            source_lines: Vec::new(),
         }
     }
 
-    pub fn execute(&mut self) {
-      self.proc.execute(&mut self.mem, &mut self.reg);
-    }
-
-    pub fn load(&mut self, path: &str) {
-        let lines: Vec<String> = self.read_programm(path);
-
-        for line in lines {
-            let bytes: Vec<&str> = line.split(",").map(|s| s.trim()).collect();
-            let len = bytes.len();
-            // println!("{:#?}", bytes);
-            // let addr = bytes.get(0);
-
-            let addr = u32::from_str(bytes.get(0).expect("cant get addr")).expect("cant parse u32");
-
-            if len == 1 {
-                self.proc.set_addr(addr);
-            } else if len == 2 {
-                let value =
-                    i32::from_str(bytes.get(1).expect("cant get 2")).expect("cant parse i32");
-
-                self.mem
-                    .set_word(addr as usize, Word::new_from_signed(value));
-            } else if len == 5 {
-                let AA = self.parse_i32(&bytes, 1);
-                let byte_3 = self.parse_u8(&bytes, 2);
-                let byte_4 = self.parse_u8(&bytes, 3);
-                let byte_5 = self.parse_u8(&bytes, 4);
-
-                self.mem
-                    .set_instr_as_bytes(addr as usize, AA, byte_3, byte_4, byte_5);
-            } else if len == 7 {
-                let sign = self.parse_i8(&bytes, 1);
-                let byte_1 = self.parse_u8(&bytes, 2);
-                let byte_2 = self.parse_u8(&bytes, 3);
-                let byte_3 = self.parse_u8(&bytes, 4);
-                let byte_4 = self.parse_u8(&bytes, 5);
-                let byte_5 = self.parse_u8(&bytes, 6);
-
-                self.mem
-                    .set_bytes(addr as usize, sign, byte_1, byte_2, byte_3, byte_4, byte_5);
-            } else {
-                panic!("unsupported program format {len}");
+    /// Runs until the program stops or a limit is reached
+    pub fn execute(&mut self) -> RunOutcome {
+        self.proc.execute(&mut self.mem, &mut self.reg, &mut self.devices)
+    }
+
+    /// Executes one instruction, returns the outcome if the program is stopped
+    pub fn step(&mut self) -> Option<RunOutcome> {
+        self.proc.step(&mut self.mem, &mut self.reg, &mut self.devices)
+    }
+
+    /// Overrides the start address of the loaded program
+    pub fn set_start(&mut self, addr: u32) {
+        self.proc.set_addr(addr);
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.proc.set_limits(limits);
+    }
+
+    /// Prints every executed instruction to stderr
+    pub fn set_trace(&mut self, trace: bool) {
+        self.proc.set_trace(trace);
+    }
+
+    /// Location counter, executed steps and spent time
+    pub fn processor(&self) -> &Processor {
+        &self.proc
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.reg
+    }
+
+    pub fn registers_mut(&mut self) -> &mut Registers {
+        &mut self.reg
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.mem
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory {
+        &mut self.mem
+    }
+
+    pub fn devices(&self) -> &Devices {
+        &self.devices
+    }
+
+    pub fn devices_mut(&mut self) -> &mut Devices {
+        &mut self.devices
+    }
+
+    /// Registers, the location counter and all non-zero memory cells, one per line
+    pub fn dump(&self) -> String {
+        let mut result = format!(
+            "{:?}\nloc:{} steps:{} time:{}u\n",
+            self.reg,
+            self.proc.get_addr(),
+            self.proc.get_steps(),
+            self.proc.get_time()
+        );
+        for i in 0..4_000 {
+            let word = self.mem.get(i);
+            if word.get() != 0 {
+                result += &format!("{:04}: {}\n", i, word.get_signed_value());
             }
         }
+        result
+    }
+
+    /// Puts a card deck (one card per line) into the card reader,
+    /// GO needs at least the first card
+    pub fn load_deck(&mut self, path: &str) -> Result<(), LoadError> {
+        let card_reader = CardReader::from_file(path)?;
+        if card_reader.is_empty() {
+            return Err(LoadError::new(path, 0, LoadProblem::EmptyDeck));
+        }
+
+        self.devices.set_card_reader(card_reader);
+        Ok(())
+    }
+
+    /// GO button: reads the first card into 0000-0015, sets rJ to 0 and jumps to 0000
+    pub fn go(&mut self) -> RunOutcome {
+        let card = match self.devices.card_reader().read() {
+            Ok(card) => card,
+            Err(message) => return RunOutcome::Fault { addr: 0, message },
+        };
+        for (i, w) in card.into_iter().enumerate() {
+            self.mem.set_word(i, w);
+        }
+        self.reg.set_j(ShortWord::new(0));
+        self.proc.set_addr(0);
+
+        self.execute()
     }
 
-    fn parse_u8(&self, bytes: &Vec<&str>, byte: usize) -> u8 {
-        u8::from_str(bytes.get(byte).expect("cant get byte")).expect("cant parse u8")
+    /// Loads a program in the comma-separated `.mix` format
+    /// nothing is written to memory unless the whole file is valid
+    pub fn load(&mut self, path: &str) -> Result<(), LoadError> {
+        let program = fs::read_to_string(path)
+            .map_err(|e| LoadError::new(path, 0, LoadProblem::CantRead(e.to_string())))?;
+
+        self.load_str(path, &program)
     }
 
-    fn parse_i8(&self, bytes: &Vec<&str>, byte: usize) -> i8 {
-        i8::from_str(bytes.get(byte).expect("cant get byte")).expect("cant parse i8")
+    /// Loads a binary object file written by `mixal --object`
+    pub fn load_object(&mut self, path: &str) -> Result<(), LoadError> {
+        let bytes = fs::read(path)
+            .map_err(|e| LoadError::new(path, 0, LoadProblem::CantRead(e.to_string())))?;
+
+        self.load_object_bytes(path, &bytes)
     }
 
-    fn parse_i32(&self, bytes: &Vec<&str>, byte: usize) -> i32 {
-        i32::from_str(bytes.get(byte).expect("cant get byte")).expect("cant parse i32")
+    /// Same as `load_object`, `name` is used for the error messages only
+    pub fn load_object_bytes(&mut self, name: &str, bytes: &[u8]) -> Result<(), LoadError> {
+        let object = parse_object(bytes).map_err(|problem| LoadError::new(name, 0, problem))?;
+
+        for (addr, words) in object.segments {
+            for (i, word) in words.into_iter().enumerate() {
+                self.mem.set_word(addr as usize + i, word);
+            }
+        }
+        self.proc.set_addr(object.start);
+        self.symbols = object.symbols;
+        self.source_lines = object.lines;
+
+        Ok(())
     }
 
-    fn read_programm(&self, path: &str) -> Vec<String> {
-        let mut file = File::open(path.to_string()).expect(&("file not found ".to_owned() + path));
-        let mut reader = BufReader::new(file);
-        let mut result = Vec::new();
+    /// Puts the words into memory and sets the start address
+    pub fn load_image(&mut self, start: u32, words: &[(u32, Word)]) -> Result<(), LoadError> {
+        let name = "image";
+        if start > 3_999 {
+            return Err(LoadError::new(name, 0, LoadProblem::AddressOutOfRange(start as i64)));
+        }
+        if let Some((addr, _)) = words.iter().find(|(addr, _)| *addr > 3_999) {
+            return Err(LoadError::new(name, 0, LoadProblem::AddressOutOfRange(*addr as i64)));
+        }
 
-        let lines = reader.lines();
-        for line in lines {
-            let line = line.expect("some err in lines");
+        for (addr, word) in words {
+            self.mem.set_word(*addr as usize, *word);
+        }
+        self.proc.set_addr(start);
 
+        Ok(())
+    }
+
+    /// Symbols of the loaded object file
+    pub fn symbols(&self) -> &[Symbol] {
+        &self.symbols
+    }
+
+    /// Source line of the instruction at `addr`, if the object file has line information
+    pub fn source_line(&self, addr: u32) -> Option<u32> {
+        self.source_lines
+            .iter()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, line)| *line)
+    }
+
+    /// Same as `load`, `name` is used for the error messages only
+    pub fn load_str(&mut self, name: &str, program: &str) -> Result<(), LoadError> {
+        let mut start: Option<(u32, usize)> = None;
+        let mut words: Vec<(usize, Word)> = Vec::new();
+
+        for (i, line) in program.lines().enumerate() {
+            let line_num = i + 1;
             let line = line.trim();
-            if line.len() <= 0 {
+            if line.is_empty() {
                 continue;
             }
-            result.push(line.to_string());
 
-            println!("{line}");
+            let err = |problem| LoadError::new(name, line_num, problem);
+
+            let fields: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            let addr = parse_field(&fields, 0, 0, 3_999, LoadProblem::AddressOutOfRange)
+                .map_err(err)?;
+
+            match fields.len() {
+                1 => match start {
+                    Some((_, first_line)) => {
+                        return Err(err(LoadProblem::DuplicateStartAddress(first_line)))
+                    }
+                    None => start = Some((addr as u32, line_num)),
+                },
+                2 => {
+                    let value = parse_field(
+                        &fields,
+                        1,
+                        -MAX_5_BYTES as i64,
+                        MAX_5_BYTES as i64,
+                        LoadProblem::ValueOutOfRange,
+                    )
+                    .map_err(err)?;
+
+                    words.push((addr as usize, Word::new_from_signed(value as i32)));
+                }
+                5 => {
+                    let aa = parse_field(
+                        &fields,
+                        1,
+                        -MAX_2_BYTES as i64,
+                        MAX_2_BYTES as i64,
+                        LoadProblem::ValueOutOfRange,
+                    )
+                    .map_err(err)?;
+                    let i = parse_byte(&fields, 2).map_err(err)?;
+                    let f = parse_byte(&fields, 3).map_err(err)?;
+                    let c = parse_byte(&fields, 4).map_err(err)?;
+
+                    let mut word =
+                        Word::new_instruction(aa as i32, i, WordAccess::new_by_spec(0), c);
+                    word.set_byte(4, f);
+                    words.push((addr as usize, word));
+                }
+                7 => {
+                    let sign = parse_field(&fields, 1, -1, 0, LoadProblem::SignOutOfRange)
+                        .map_err(err)?;
+                    let mut bytes = [0; 5];
+                    for (b, byte) in bytes.iter_mut().enumerate() {
+                        *byte = parse_byte(&fields, b + 2).map_err(err)?;
+                    }
+
+                    words.push((addr as usize, Word::new_by_bytes(sign as i8, &bytes)));
+                }
+                len => return Err(err(LoadProblem::FieldCount(len))),
+            }
         }
 
-        result
+        let (start, _) = start.ok_or(LoadError::new(name, 0, LoadProblem::MissingStartAddress))?;
+
+        for (addr, word) in words {
+            self.mem.set_word(addr, word);
+        }
+        self.proc.set_addr(start);
+
+        Ok(())
+    }
+}
+
+fn parse_field(
+    fields: &[&str],
+    i: usize,
+    min: i64,
+    max: i64,
+    out_of_range: fn(i64) -> LoadProblem,
+) -> Result<i64, LoadProblem> {
+    let field = fields[i];
+    let value = i64::from_str(field).map_err(|_| LoadProblem::NotANumber(field.to_string()))?;
+
+    if value < min || value > max {
+        return Err(out_of_range(value));
     }
+    Ok(value)
+}
+
+fn parse_byte(fields: &[&str], i: usize) -> Result<u8, LoadProblem> {
+    parse_field(fields, i, 0, 63, LoadProblem::ByteOutOfRange).map(|b| b as u8)
 }
 
 #[cfg(test)]
@@ -116,8 +318,164 @@ mod tests {
     fn load_program() {
         let mut mix = MIX::new();
 
-        mix.load("../programs/print_500_primes.mix");
+        mix.load("../programs/print_500_primes.mix")
+            .expect("program should load");
+
+        assert_eq!(RunOutcome::Halted, mix.execute());
+    }
+
+    #[test]
+    fn dump() {
+        let mut mix = MIX::new();
 
+        mix.load_str("p.mix", "10, -5\n3000, 0, 0, 2, 5\n3000\n")
+            .expect("program should load");
         mix.execute();
+
+        let dump = mix.dump();
+        assert!(dump.starts_with("ra:0 rx:0"), "{dump}");
+        assert!(dump.contains("loc:3001 steps:1 time:10u\n"), "{dump}");
+        assert!(dump.ends_with("0010: -5\n3000: 133\n"), "{dump}");
+    }
+
+    #[test]
+    fn load_object_program() {
+        let mut mix = MIX::new();
+
+        mix.load_object("../programs/print_500_primes.mixo")
+            .expect("object should load");
+
+        assert_eq!(3000, mix.proc.get_addr());
+        assert_eq!(Some(9), mix.source_line(3000));
+        assert!(mix
+            .symbols()
+            .iter()
+            .any(|s| s.name == "BUF0" && s.kind == SymbolKind::Equ && s.value == 2000));
+
+        mix.execute();
+        assert_eq!(3571, mix.mem.get(499).get_signed_value());
+    }
+
+    #[test]
+    fn load_str() {
+        let mut mix = MIX::new();
+
+        mix.load_str("p.mix", "10, -5\n\n11, 2000, 1, 5, 8\n12, -1, 1, 2, 3, 4, 5\n11\n")
+            .expect("program should load");
+
+        assert_eq!(-5, mix.mem.get(10).get_signed_value());
+        assert_eq!(Word::new_by_bytes(0, &[31, 16, 1, 5, 8]), mix.mem.get(11));
+        assert_eq!(Word::new_by_bytes(-1, &[1, 2, 3, 4, 5]), mix.mem.get(12));
+        assert_eq!(11, mix.proc.get_addr());
+    }
+
+    #[test]
+    fn load_errors() {
+        let cases = [
+            ("10, 1, 2\n10", 1, LoadProblem::FieldCount(3)),
+            ("10, x\n10", 1, LoadProblem::NotANumber("x".to_string())),
+            ("10\n4000, 1", 2, LoadProblem::AddressOutOfRange(4000)),
+            ("10, 0, 64, 5, 8\n10", 1, LoadProblem::ByteOutOfRange(64)),
+            ("10, 4096, 0, 5, 8\n10", 1, LoadProblem::ValueOutOfRange(4096)),
+            ("10, 1, 1, 2, 3, 4, 5\n10", 1, LoadProblem::SignOutOfRange(1)),
+            ("10\n\n11", 3, LoadProblem::DuplicateStartAddress(1)),
+            ("10, 1", 0, LoadProblem::MissingStartAddress),
+        ];
+
+        for (program, line, problem) in cases {
+            let mut mix = MIX::new();
+            let err = mix.load_str("p.mix", program).expect_err(program);
+            assert_eq!(LoadError::new("p.mix", line, problem), err, "{program}");
+        }
+
+        let mut mix = MIX::new();
+        let err = mix.load_str("p.mix", "10, 1\n10, 0, 64, 5, 8\n10").expect_err("bad byte");
+        assert_eq!("p.mix:2: byte 64 is out of range 0-63", err.to_string());
+        assert_eq!(0, mix.mem.get(10).get_signed_value());
+    }
+
+    #[test]
+    fn load_image_and_step() {
+        let mut mix = MIX::new();
+
+        // ENTA 5 ; INCA 2 ; HLT
+        let mut program = Vec::new();
+        for (i, (addr, f, c)) in [(5, 2, 48), (2, 0, 48), (0, 2, 5)].into_iter().enumerate() {
+            let mut word = Word::new_instruction(addr, 0, WordAccess::new_by_spec(0), c);
+            word.set_byte(4, f);
+            program.push((3000 + i as u32, word));
+        }
+        mix.load_image(3000, &program).expect("image should load");
+
+        assert_eq!(None, mix.step());
+        assert_eq!(5, mix.registers().get_a().get_signed_value());
+        assert_eq!(3001, mix.processor().get_addr());
+
+        mix.registers_mut().set_a(Word::new_from_signed(-10));
+        assert_eq!(None, mix.step());
+        assert_eq!(-8, mix.registers().get_a().get_signed_value());
+
+        assert_eq!(Some(RunOutcome::Halted), mix.step());
+        assert_eq!(3, mix.processor().get_steps());
+
+        mix.memory_mut().set_word(10, Word::new(7));
+        assert_eq!(7, mix.memory().get(10).get_signed_value());
+
+        let err = mix.load_image(3000, &[(4000, Word::new(1))]).expect_err("bad address");
+        assert_eq!(LoadProblem::AddressOutOfRange(4000), err.problem);
+    }
+
+    #[test]
+    fn load_object_bytes() {
+        let mut mix = MIX::new();
+
+        let bytes = fs::read("../programs/print_500_primes.mixo").expect("object file");
+        mix.load_object_bytes("primes", &bytes).expect("object should load");
+        mix.set_limits(Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        });
+        assert_eq!(RunOutcome::StepLimit, mix.execute());
+
+        let err = mix.load_object_bytes("primes", b"MI").expect_err("not an object");
+        assert_eq!("primes: not a MIX object file", err.to_string());
+    }
+
+    #[test]
+    fn load_deck_errors() {
+        let mut mix = MIX::new();
+        let err = mix.load_deck("no/such.deck").expect_err("missing deck");
+        assert!(matches!(err.problem, LoadProblem::CantRead(_)), "{err}");
+
+        let empty = std::env::temp_dir().join(format!("mix_empty_{}.deck", std::process::id()));
+        fs::write(&empty, "").expect("empty deck");
+        let path = empty.to_str().expect("path");
+        let err = mix.load_deck(path).expect_err("empty deck");
+        let _ = fs::remove_file(&empty);
+        assert_eq!(LoadError::new(path, 0, LoadProblem::EmptyDeck), err);
+    }
+
+    #[test]
+    fn go_program() {
+        let printer = std::env::temp_dir().join(format!("mix_go_{}", std::process::id()));
+        let _ = fs::remove_file(&printer);
+        let mut mix = MIX::new();
+        mix.devices_mut()
+            .map_unit(18, printer.to_str().expect("path"))
+            .expect("printer");
+
+        mix.load_deck("../programs/print_500_primes.deck")
+            .expect("deck should load");
+        assert_eq!(RunOutcome::Halted, mix.go());
+        // stopped by HLT
+        assert_eq!(3030, mix.proc.get_addr());
+
+        let output = fs::read_to_string(&printer).expect("printer output");
+        let _ = fs::remove_file(&printer);
+        let lines: Vec<&str> = output.lines().map(|l| l.trim_end()).collect();
+        assert_eq!("FIRST FIVE HUNDRED PRIMES", lines[1]);
+        assert_eq!("     0002 0233 0547 0877 1229 1597 1993 2371 2749 3187", lines[2]);
+        assert_eq!("     0003 0239 0557 0881 1231 1601 1997 2377 2753 3191", lines[3]);
+        assert_eq!("     0229 0541 0863 1223 1583 1987 2357 2741 3181 3571", lines[51]);
     }
 }
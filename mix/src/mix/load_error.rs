@@ -0,0 +1,89 @@
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoadProblem {
+    CantRead(String),
+    FieldCount(usize),
+    NotANumber(String),
+    AddressOutOfRange(i64),
+    ByteOutOfRange(i64),
+    SignOutOfRange(i64),
+    ValueOutOfRange(i64),
+    DuplicateStartAddress(usize),
+    MissingStartAddress,
+    NotAnObject,
+    UnsupportedVersion(u16),
+    Truncated(usize),
+    NotLinked,
+    EmptyDeck,
+    CardTooLong(usize),
+    UnknownCardSymbol(char),
+}
+
+impl fmt::Display for LoadProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadProblem::CantRead(reason) => write!(f, "can't read file: {reason}"),
+            LoadProblem::FieldCount(count) => write!(
+                f,
+                "expected 1, 2, 5 or 7 comma-separated fields, found {count}"
+            ),
+            LoadProblem::NotANumber(field) => write!(f, "'{field}' is not a number"),
+            LoadProblem::AddressOutOfRange(addr) => {
+                write!(f, "address {addr} is out of range 0-3999")
+            }
+            LoadProblem::ByteOutOfRange(byte) => write!(f, "byte {byte} is out of range 0-63"),
+            LoadProblem::SignOutOfRange(sign) => write!(f, "sign {sign} should be 0 or -1"),
+            LoadProblem::ValueOutOfRange(value) => {
+                write!(f, "value {value} doesn't fit into a MIX field")
+            }
+            LoadProblem::DuplicateStartAddress(first_line) => {
+                write!(f, "start address is already set on line {first_line}")
+            }
+            LoadProblem::MissingStartAddress => write!(f, "start address is missing"),
+            LoadProblem::NotAnObject => write!(f, "not a MIX object file"),
+            LoadProblem::UnsupportedVersion(version) => {
+                write!(f, "unsupported object file version {version}")
+            }
+            LoadProblem::Truncated(offset) => write!(f, "object file is truncated at byte {offset}"),
+            LoadProblem::NotLinked => {
+                write!(f, "relocatable module, link it with `mixal link` first")
+            }
+            LoadProblem::EmptyDeck => write!(f, "card deck has no cards"),
+            LoadProblem::CardTooLong(columns) => {
+                write!(f, "card has {columns} columns, more than 80")
+            }
+            LoadProblem::UnknownCardSymbol(c) => write!(f, "'{c}' is not a MIX character"),
+        }
+    }
+}
+
+/// Malformed `.mix` program, object file or card deck: file, 1-based line number (0 for the whole file) and the problem
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadError {
+    pub path: String,
+    pub line: usize,
+    pub problem: LoadProblem,
+}
+
+impl LoadError {
+    pub fn new(path: &str, line: usize, problem: LoadProblem) -> LoadError {
+        LoadError {
+            path: path.to_string(),
+            line,
+            problem,
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.path, self.problem)
+        } else {
+            write!(f, "{}:{}: {}", self.path, self.line, self.problem)
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
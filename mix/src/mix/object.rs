@@ -0,0 +1,202 @@
+use crate::memory::word::Word;
+use crate::mix::load_error::LoadProblem;
+use mix_core::object::{MAGIC, SECTION_LINES, SECTION_MODULE, SECTION_SYMBOLS, VERSION};
+
+// the format of the object file is described in mix_core::object
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SymbolKind {
+    Equ,
+    Label,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectProgram {
+    pub start: u32,
+    pub segments: Vec<(u32, Vec<Word>)>,
+    pub symbols: Vec<Symbol>,
+    pub lines: Vec<(u32, u32)>, // address -> source line
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], LoadProblem> {
+        if self.pos + n > self.bytes.len() {
+            return Err(LoadProblem::Truncated(self.pos));
+        }
+        let result = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(result)
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadProblem> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, LoadProblem> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadProblem> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn address(&mut self) -> Result<u32, LoadProblem> {
+        let addr = self.u16()? as u32;
+        if addr > 3_999 {
+            return Err(LoadProblem::AddressOutOfRange(addr as i64));
+        }
+        Ok(addr)
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+}
+
+pub fn parse_object(bytes: &[u8]) -> Result<ObjectProgram, LoadProblem> {
+    let mut r = Reader { bytes, pos: 0 };
+
+    if r.take(4).map_err(|_| LoadProblem::NotAnObject)? != MAGIC {
+        return Err(LoadProblem::NotAnObject);
+    }
+    let version = r.u16()?;
+    if version != VERSION {
+        return Err(LoadProblem::UnsupportedVersion(version));
+    }
+
+    let start = r.address()?;
+    let segment_count = r.u16()?;
+
+    let mut segments = Vec::new();
+    for _ in 0..segment_count {
+        let addr = r.address()?;
+        let count = r.u16()? as u32;
+        if addr + count > 4_000 {
+            return Err(LoadProblem::AddressOutOfRange((addr + count - 1) as i64));
+        }
+
+        let mut words = Vec::new();
+        for _ in 0..count {
+            words.push(Word::new(r.u32()?));
+        }
+        segments.push((addr, words));
+    }
+
+    let mut symbols = Vec::new();
+    let mut lines = Vec::new();
+    while !r.is_end() {
+        let id = r.u8()?;
+        let len = r.u32()? as usize;
+        let payload = r.take(len)?;
+        let mut section = Reader {
+            bytes: payload,
+            pos: 0,
+        };
+
+        match id {
+            SECTION_SYMBOLS => {
+                while !section.is_end() {
+                    let kind = match section.u8()? {
+                        0 => SymbolKind::Equ,
+                        1 => SymbolKind::Label,
+                        k => SymbolKind::Other(k),
+                    };
+                    let value = section.u32()? as i32;
+                    let name_len = section.u8()? as usize;
+                    let name = String::from_utf8_lossy(section.take(name_len)?).to_string();
+                    symbols.push(Symbol { name, kind, value });
+                }
+            }
+            SECTION_LINES => {
+                while !section.is_end() {
+                    let addr = section.address()?;
+                    let line = section.u32()?;
+                    lines.push((addr, line));
+                }
+            }
+            SECTION_MODULE => return Err(LoadProblem::NotLinked),
+            _ => {}
+        }
+    }
+
+    Ok(ObjectProgram {
+        start,
+        segments,
+        symbols,
+        lines,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OBJECT: [u8; 53] = [
+        b'M', b'I', b'X', b'O', 0, 1, 0x0B, 0xB8, 0, 2, // header
+        0, 10, 0, 1, 0, 0, 0, 3, // segment 10
+        0x0B, 0xB8, 0, 2, 0, 0, 0, 1, 0x80, 0, 0, 2, // segment 3000
+        SECTION_SYMBOLS, 0, 0, 0, 7, 0, 0xFF, 0xFF, 0xFF, 0xFB, 1, b'X', // symbols
+        SECTION_LINES, 0, 0, 0, 6, 0x0B, 0xB8, 0, 0, 0, 7, // lines
+    ];
+
+    #[test]
+    fn parse() {
+        let object = parse_object(&OBJECT).expect("valid object");
+
+        assert_eq!(3000, object.start);
+        assert_eq!(
+            vec![
+                (10, vec![Word::new(3)]),
+                (3000, vec![Word::new(1), Word::new_from_signed(-2)])
+            ],
+            object.segments
+        );
+        assert_eq!(
+            vec![Symbol {
+                name: "X".to_string(),
+                kind: SymbolKind::Equ,
+                value: -5
+            }],
+            object.symbols
+        );
+        assert_eq!(vec![(3000, 7)], object.lines);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(Err(LoadProblem::NotAnObject), parse_object(b"3000, 1"));
+        assert_eq!(Err(LoadProblem::NotAnObject), parse_object(b"MI"));
+
+        let mut object = OBJECT.to_vec();
+        object[5] = 2;
+        assert_eq!(Err(LoadProblem::UnsupportedVersion(2)), parse_object(&object));
+
+        assert_eq!(Err(LoadProblem::Truncated(26)), parse_object(&OBJECT[..27]));
+
+        let mut object = OBJECT.to_vec();
+        object.extend_from_slice(&[SECTION_MODULE, 0, 0, 0, 3, 0, 2, 1]);
+        assert_eq!(Err(LoadProblem::NotLinked), parse_object(&object));
+
+        let mut object = OBJECT.to_vec();
+        object[18] = 0x0F;
+        assert_eq!(
+            Err(LoadProblem::AddressOutOfRange(0x0FB8)),
+            parse_object(&object)
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::devices::Devices;
+use crate::devices::{CARD_BLOCK, CARD_READER_UNIT, PRINTER_BLOCK, PRINTER_UNIT};
 use crate::memory::word::Word;
 use crate::memory::word_access::WordAccess;
 use crate::memory::Bytes;
@@ -26,6 +28,9 @@ pub mod load;
 pub mod miscellaneous;
 pub mod store;
 
+// cells the instructions can address, 0000-3999
+const MEMORY_SIZE: i32 = 4_000;
+
 // pub struct OperationDescription {
 // code: u32,
 // execution_time: u32,
@@ -42,6 +47,7 @@ pub struct OperationArgs<'a> {
     // instruction: Word,
     mem: &'a mut Memory,
     reg: &'a mut Registers,
+    devices: Option<&'a mut Devices>,
 }
 
 impl<'a> OperationArgs<'a> {
@@ -56,13 +62,21 @@ impl<'a> OperationArgs<'a> {
             // instruction,
             mem,
             reg,
+            devices: None,
         }
     }
+
+    pub fn with_devices(mut self, devices: &'a mut Devices) -> OperationArgs<'a> {
+        self.devices = Some(devices);
+        self
+    }
 }
 
 pub struct OperationResult {
     pub execution_time: u32,
     pub next_addr_instruction: u32,
+    pub is_halted: bool,
+    pub fault: Option<String>,
 }
 
 impl OperationResult {
@@ -70,6 +84,8 @@ impl OperationResult {
         OperationResult {
             execution_time: execution_time,
             next_addr_instruction: args.addr + 1,
+            is_halted: false,
+            fault: None,
         }
     }
 
@@ -77,6 +93,27 @@ impl OperationResult {
         OperationResult {
             execution_time,
             next_addr_instruction,
+            is_halted: false,
+            fault: None,
+        }
+    }
+
+    pub fn halt(execution_time: u32, args: OperationArgs) -> OperationResult {
+        OperationResult {
+            execution_time,
+            next_addr_instruction: args.addr + 1,
+            is_halted: true,
+            fault: None,
+        }
+    }
+
+    /// The instruction at `addr` can't be executed, the program is stopped on it
+    pub fn fault(addr: u32, message: String) -> OperationResult {
+        OperationResult {
+            execution_time: 0,
+            next_addr_instruction: addr,
+            is_halted: false,
+            fault: Some(message),
         }
     }
 }
@@ -93,8 +130,18 @@ impl Operations {
         instruction: Word,
         mem: &mut Memory,
         reg: &mut Registers,
+        devices: &mut Devices,
     ) -> OperationResult {
-        let op = self.get_operation(instruction);
+        if let Err(message) = check(instruction, reg) {
+            return OperationResult::fault(addr, message);
+        }
+        let op = match self.get_operation(instruction) {
+            Some(op) => op,
+            None => {
+                let message = format!("unsupported operation code {}", instruction.get_c());
+                return OperationResult::fault(addr, message);
+            }
+        };
 
         // println!(
         // "{}| {}: {} {} {} {}",
@@ -106,115 +153,152 @@ impl Operations {
         // instruction.get_c()
         // );
 
-        let args = OperationArgs::new(addr, mem, reg);
+        let args = OperationArgs::new(addr, mem, reg).with_devices(devices);
         op.execute(args)
     }
 
-    fn get_operation(&self, instruction: Word) -> Box<dyn Operation> {
+    fn get_operation(&self, instruction: Word) -> Option<Box<dyn Operation>> {
         let code = instruction.get_c();
         let f = instruction.get_byte(4);
         return match code {
-            0 => Box::new(NOP::new(instruction)),
+            0 => Some(Box::new(NOP::new(instruction))),
 
             // arithmetic
-            1 => Box::new(ADD::new(instruction)),
-            2 => Box::new(SUB::new(instruction)),
-            3 => Box::new(MUL::new(instruction)),
-            4 => Box::new(DIV::new(instruction)),
+            1 => Some(Box::new(ADD::new(instruction))),
+            2 => Some(Box::new(SUB::new(instruction))),
+            3 => Some(Box::new(MUL::new(instruction))),
+            4 => Some(Box::new(DIV::new(instruction))),
 
-            5 if f == 0 => Box::new(NUM::new(instruction)),
-            5 if f == 1 => Box::new(CHAR::new(instruction)),
-            5 if f == 2 => Box::new(HLT::new(instruction)),
+            5 if f == 0 => Some(Box::new(NUM::new(instruction))),
+            5 if f == 1 => Some(Box::new(CHAR::new(instruction))),
+            5 if f == 2 => Some(Box::new(HLT::new(instruction))),
 
             // shift
-            6 if f == 0 => Box::new(SLA::new(instruction)),
-            6 if f == 1 => Box::new(SRA::new(instruction)),
-            6 if f == 2 => Box::new(SLAX::new(instruction)),
-            6 if f == 3 => Box::new(SRAX::new(instruction)),
-            6 if f == 4 => Box::new(SLC::new(instruction)),
-            6 if f == 5 => Box::new(SRC::new(instruction)),
+            6 if f == 0 => Some(Box::new(SLA::new(instruction))),
+            6 if f == 1 => Some(Box::new(SRA::new(instruction))),
+            6 if f == 2 => Some(Box::new(SLAX::new(instruction))),
+            6 if f == 3 => Some(Box::new(SRAX::new(instruction))),
+            6 if f == 4 => Some(Box::new(SLC::new(instruction))),
+            6 if f == 5 => Some(Box::new(SRC::new(instruction))),
 
-            7 => Box::new(MOVE::new(instruction)),
+            7 => Some(Box::new(MOVE::new(instruction))),
 
             // load
-            8 => Box::new(LDA::new(instruction)),
-            9..=14 => Box::new(LDi::new(instruction)),
-            15 => Box::new(LDX::new(instruction)),
-            16 => Box::new(LDAN::new(instruction)),
-            17..=22 => Box::new(LDiN::new(instruction)),
-            23 => Box::new(LDXN::new(instruction)),
+            8 => Some(Box::new(LDA::new(instruction))),
+            9..=14 => Some(Box::new(LDi::new(instruction))),
+            15 => Some(Box::new(LDX::new(instruction))),
+            16 => Some(Box::new(LDAN::new(instruction))),
+            17..=22 => Some(Box::new(LDiN::new(instruction))),
+            23 => Some(Box::new(LDXN::new(instruction))),
 
             // store
-            24 => Box::new(STA::new(instruction)),
-            25..=30 => Box::new(STi::new(instruction)),
-            31 => Box::new(STX::new(instruction)),
-            32 => Box::new(STJ::new(instruction)),
-            33 => Box::new(STZ::new(instruction)),
+            24 => Some(Box::new(STA::new(instruction))),
+            25..=30 => Some(Box::new(STi::new(instruction))),
+            31 => Some(Box::new(STX::new(instruction))),
+            32 => Some(Box::new(STJ::new(instruction))),
+            33 => Some(Box::new(STZ::new(instruction))),
 
             //IO
-            35 => Box::new(IOC::new(instruction)),
-            36 => Box::new(IN::new(instruction)),
-            37 => Box::new(OUT::new(instruction)),
+            34 => Some(Box::new(JBUS::new(instruction))),
+            35 => Some(Box::new(IOC::new(instruction))),
+            36 => Some(Box::new(IN::new(instruction))),
+            37 => Some(Box::new(OUT::new(instruction))),
+            38 => Some(Box::new(JRED::new(instruction))),
 
             // jump
-            39 if f == 0 => Box::new(JMP::new(instruction)),
-            39 if f == 1 => Box::new(JSJ::new(instruction)),
-            39 if f == 2 => Box::new(JOV::new(instruction)),
-            39 if f == 3 => Box::new(JNOV::new(instruction)),
-            39 if f == 4 => Box::new(JL::new(instruction)),
-            39 if f == 5 => Box::new(JE::new(instruction)),
-            39 if f == 6 => Box::new(JG::new(instruction)),
-            39 if f == 7 => Box::new(JGE::new(instruction)),
-            39 if f == 8 => Box::new(JNE::new(instruction)),
-            39 if f == 9 => Box::new(JLE::new(instruction)),
-
-            40 if f == 0 => Box::new(JAN::new(instruction)),
-            40 if f == 1 => Box::new(JAZ::new(instruction)),
-            40 if f == 2 => Box::new(JAP::new(instruction)),
-            40 if f == 3 => Box::new(JANN::new(instruction)),
-            40 if f == 4 => Box::new(JANZ::new(instruction)),
-            40 if f == 5 => Box::new(JANP::new(instruction)),
-
-            41..=46 if f == 0 => Box::new(JiN::new(instruction)),
-            41..=46 if f == 1 => Box::new(JiZ::new(instruction)),
-            41..=46 if f == 2 => Box::new(JiP::new(instruction)),
-            41..=46 if f == 3 => Box::new(JiNN::new(instruction)),
-            41..=46 if f == 4 => Box::new(JiNZ::new(instruction)),
-            41..=46 if f == 5 => Box::new(JiNP::new(instruction)),
-
-            47 if f == 0 => Box::new(JXN::new(instruction)),
-            47 if f == 1 => Box::new(JXZ::new(instruction)),
-            47 if f == 2 => Box::new(JXP::new(instruction)),
-            47 if f == 3 => Box::new(JXNN::new(instruction)),
-            47 if f == 4 => Box::new(JXNZ::new(instruction)),
-            47 if f == 5 => Box::new(JXNP::new(instruction)),
+            39 if f == 0 => Some(Box::new(JMP::new(instruction))),
+            39 if f == 1 => Some(Box::new(JSJ::new(instruction))),
+            39 if f == 2 => Some(Box::new(JOV::new(instruction))),
+            39 if f == 3 => Some(Box::new(JNOV::new(instruction))),
+            39 if f == 4 => Some(Box::new(JL::new(instruction))),
+            39 if f == 5 => Some(Box::new(JE::new(instruction))),
+            39 if f == 6 => Some(Box::new(JG::new(instruction))),
+            39 if f == 7 => Some(Box::new(JGE::new(instruction))),
+            39 if f == 8 => Some(Box::new(JNE::new(instruction))),
+            39 if f == 9 => Some(Box::new(JLE::new(instruction))),
+
+            40 if f == 0 => Some(Box::new(JAN::new(instruction))),
+            40 if f == 1 => Some(Box::new(JAZ::new(instruction))),
+            40 if f == 2 => Some(Box::new(JAP::new(instruction))),
+            40 if f == 3 => Some(Box::new(JANN::new(instruction))),
+            40 if f == 4 => Some(Box::new(JANZ::new(instruction))),
+            40 if f == 5 => Some(Box::new(JANP::new(instruction))),
+
+            41..=46 if f == 0 => Some(Box::new(JiN::new(instruction))),
+            41..=46 if f == 1 => Some(Box::new(JiZ::new(instruction))),
+            41..=46 if f == 2 => Some(Box::new(JiP::new(instruction))),
+            41..=46 if f == 3 => Some(Box::new(JiNN::new(instruction))),
+            41..=46 if f == 4 => Some(Box::new(JiNZ::new(instruction))),
+            41..=46 if f == 5 => Some(Box::new(JiNP::new(instruction))),
+
+            47 if f == 0 => Some(Box::new(JXN::new(instruction))),
+            47 if f == 1 => Some(Box::new(JXZ::new(instruction))),
+            47 if f == 2 => Some(Box::new(JXP::new(instruction))),
+            47 if f == 3 => Some(Box::new(JXNN::new(instruction))),
+            47 if f == 4 => Some(Box::new(JXNZ::new(instruction))),
+            47 if f == 5 => Some(Box::new(JXNP::new(instruction))),
 
             // address_transfer
-            48 if f == 0 => Box::new(INCA::new(instruction)),
-            48 if f == 1 => Box::new(DECA::new(instruction)),
-            48 if f == 2 => Box::new(ENTA::new(instruction)),
-            48 if f == 3 => Box::new(ENNA::new(instruction)),
+            48 if f == 0 => Some(Box::new(INCA::new(instruction))),
+            48 if f == 1 => Some(Box::new(DECA::new(instruction))),
+            48 if f == 2 => Some(Box::new(ENTA::new(instruction))),
+            48 if f == 3 => Some(Box::new(ENNA::new(instruction))),
 
-            49..=54 if f == 0 => Box::new(INCi::new(instruction)),
-            49..=54 if f == 1 => Box::new(DECi::new(instruction)),
-            49..=54 if f == 2 => Box::new(ENTi::new(instruction)),
-            49..=54 if f == 3 => Box::new(ENNi::new(instruction)),
+            49..=54 if f == 0 => Some(Box::new(INCi::new(instruction))),
+            49..=54 if f == 1 => Some(Box::new(DECi::new(instruction))),
+            49..=54 if f == 2 => Some(Box::new(ENTi::new(instruction))),
+            49..=54 if f == 3 => Some(Box::new(ENNi::new(instruction))),
 
-            55 if f == 0 => Box::new(INCX::new(instruction)),
-            55 if f == 1 => Box::new(DECX::new(instruction)),
-            55 if f == 2 => Box::new(ENTX::new(instruction)),
-            55 if f == 3 => Box::new(ENNX::new(instruction)),
+            55 if f == 0 => Some(Box::new(INCX::new(instruction))),
+            55 if f == 1 => Some(Box::new(DECX::new(instruction))),
+            55 if f == 2 => Some(Box::new(ENTX::new(instruction))),
+            55 if f == 3 => Some(Box::new(ENNX::new(instruction))),
 
             // compare
-            56 => Box::new(CMPA::new(instruction)),
-            57..=62 => Box::new(CMPi::new(instruction)),
-            63 => Box::new(CMPX::new(instruction)),
+            56 => Some(Box::new(CMPA::new(instruction))),
+            57..=62 => Some(Box::new(CMPi::new(instruction))),
+            63 => Some(Box::new(CMPX::new(instruction))),
 
-            _ => panic!("unsupported operation code {code}"),
+            _ => None,
         };
     }
 }
 
+/// Why the instruction can't be executed: an unknown operation, an invalid field
+/// or an address out of memory
+fn check(instruction: Word, reg: &Registers) -> Result<(), String> {
+    mix_core::instruction::Instruction::decode(instruction).map_err(|e| e.to_string())?;
+
+    let in_memory = |from: i32, words: i32| match from >= 0 && from + words <= MEMORY_SIZE {
+        true => Ok(()),
+        false => Err(format!(
+            "address {from} is out of range 0-{}",
+            MEMORY_SIZE - 1
+        )),
+    };
+
+    let f = instruction.get_byte(4);
+    let m = get_indexed_addr(instruction, reg);
+    match instruction.get_c() {
+        1..=4 | 8..=33 | 56..=63 => {
+            let (left, right) = (f / 8, f % 8);
+            if left > right || right > 5 {
+                return Err(format!("field ({left}:{right}) is invalid"));
+            }
+            in_memory(m, 1)
+        }
+        7 => {
+            in_memory(m, f as i32)?;
+            in_memory(reg.get_i(1).get_signed_value(), f as i32)
+        }
+        // the jumps, JBUS and JRED check the address whether they jump or not
+        34 | 38 | 39..=47 => in_memory(m, 1),
+        36 if f == CARD_READER_UNIT => in_memory(m, CARD_BLOCK as i32),
+        37 if f == PRINTER_UNIT => in_memory(m, PRINTER_BLOCK as i32),
+        _ => Ok(()),
+    }
+}
+
 pub fn get_memory_cell(instruction: impl Instruction, mem: &Memory, reg: &Registers) -> Word {
     let mut addr = instruction.get_address();
 
@@ -246,6 +330,7 @@ mod tests {
         mem.set(2_000, 77);
 
         let mut reg = Registers::new();
+        let mut devices = Devices::new();
 
         let operations = Operations::new();
 
@@ -254,6 +339,7 @@ mod tests {
             Word::new_instruction(2_000, 0, WordAccess::new(0, 5), 8),
             &mut mem,
             &mut reg,
+            &mut devices,
         );
         assert_eq!(reg.get_a().get_signed_value(), 77);
 
@@ -262,6 +348,7 @@ mod tests {
             Word::new_instruction(2_000, 0, WordAccess::new(0, 5), 15),
             &mut mem,
             &mut reg,
+            &mut devices,
         );
         assert_eq!(reg.get_x().get_signed_value(), 77);
 
@@ -270,6 +357,7 @@ mod tests {
             Word::new_instruction(2_000, 1, WordAccess::new(0, 5), 9),
             &mut mem,
             &mut reg,
+            &mut devices,
         );
         assert_eq!(reg.get_i(1).get_signed_value(), 77);
 
@@ -278,6 +366,7 @@ mod tests {
             Word::new_instruction(2_000, 6, WordAccess::new(0, 5), 14),
             &mut mem,
             &mut reg,
+            &mut devices,
         );
         assert_eq!(reg.get_i(6).get_signed_value(), 77);
     }
@@ -194,7 +194,10 @@ impl Operation for INCi {
             return OperationResult::from_args(self.execution_time, args);
         }
 
-        panic!("INCi overflow {m}");
+        OperationResult::fault(
+            args.addr,
+            format!("INC{i} overflow, {result} doesn't fit into rI{i}"),
+        )
     }
 
     fn get_name(&self) -> String {
@@ -241,7 +244,10 @@ impl Operation for DECi {
             return OperationResult::from_args(self.execution_time, args);
         }
 
-        panic!("DNCi overflow {m}");
+        OperationResult::fault(
+            args.addr,
+            format!("DEC{i} overflow, {result} doesn't fit into rI{i}"),
+        )
     }
 
     fn get_name(&self) -> String {
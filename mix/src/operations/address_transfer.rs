@@ -128,7 +128,11 @@ impl Operation for ENTi {
         let from = self.instruction.get_i() as usize;
 
         let sign = self.instruction.get_sign();
-        let mut ri = args.reg.get_i(from);
+        // ENTi 0 without an index register is +0 or -0
+        let mut ri = match from {
+            0 => ShortWord::new(0),
+            _ => args.reg.get_i(from),
+        };
         ri.set_sign(sign);
         args.reg.set_i(to, ri);
 
@@ -230,7 +234,10 @@ impl Operation for ENNi {
         }
 
         let from = self.instruction.get_i() as usize;
-        let mut ri = args.reg.get_i(from);
+        let mut ri = match from {
+            0 => ShortWord::new(0),
+            _ => args.reg.get_i(from),
+        };
         ri.set_sign(sign);
         args.reg.set_i(to, ri);
 
@@ -395,6 +402,11 @@ mod tests {
         let op = ENTi::new(instruction);
         op.execute(args);
         assert_eq!(r.get_i(1), ShortWord::new_from_signed(-12));
+
+        let args = OperationArgs::new(1, &mut m, &mut r);
+        let op = ENTi::new(Word::new_instruction(0, 0, WordAccess::new_by_spec(2), 49));
+        op.execute(args);
+        assert_eq!(r.get_i(1), ShortWord::new(0));
     }
 
     #[test]
@@ -545,5 +557,12 @@ mod tests {
         let op = ENNi::new(instruction);
         op.execute(args);
         assert_eq!(r.get_i(1), ShortWord::new_from_signed(12));
+
+        // ENN1 0 is -0, F = 3 selects ENN1
+        let enn1 = Word::new_instruction(0, 0, WordAccess::new_by_spec(3), 49);
+        let result = Operations::new().execute(1, enn1, &mut m, &mut r, &mut Devices::new());
+        assert_eq!(None, result.fault);
+        assert_eq!(0, r.get_i(1).get_signed_value());
+        assert!(r.get_i(1).get_sign() < 0);
     }
 }
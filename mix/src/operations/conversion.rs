@@ -3,12 +3,7 @@ use crate::memory::word::MAX_5_BYTES;
 use crate::memory::Bytes;
 use crate::operations::*;
 
-pub const SYMBOLS: [char; 56] = [
-    ' ', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{0394}', 'J', 'K', 'L', 'M', 'N', 'O',
-    'P', 'Q', 'R', '\u{2211}', '\u{03A0}', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '0', '1', '2',
-    '3', '4', '5', '6', '7', '8', '9', '.', ',', '(', ')', '+', '-', '*', '/', '=', '$', '<', '>',
-    '@', ';', ':', '\'',
-];
+pub use mix_core::chars::SYMBOLS;
 
 pub struct NUM {
     code: u32,
@@ -1,3 +1,8 @@
+use crate::devices::Devices;
+use crate::devices::CARD_READER_UNIT;
+use crate::devices::PRINTER_BLOCK;
+use crate::devices::PRINTER_UNIT;
+use crate::memory::short_word::ShortWord;
 use crate::memory::word::Word;
 use crate::memory::word::MAX_5_BYTES;
 use crate::memory::Bytes;
@@ -11,9 +16,17 @@ use crate::registers::Registers;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
+use std::path::PathBuf;
 
 pub const IO_FILE_PREFIX: &str = "io_unit_";
 
+fn unit_path(devices: Option<&Devices>, io_unit: u8) -> PathBuf {
+    match devices {
+        Some(devices) => devices.unit_path(io_unit),
+        None => PathBuf::from(IO_FILE_PREFIX.to_string() + &io_unit.to_string()),
+    }
+}
+
 pub struct IO_UNIT {}
 
 impl IO_UNIT {}
@@ -33,14 +46,26 @@ impl IN {
     }
 }
 impl Operation for IN {
-    fn execute(&self, args: OperationArgs) -> OperationResult {
+    fn execute(&self, mut args: OperationArgs) -> OperationResult {
         let io_unit = self.instruction.get_byte(4);
-        if io_unit != 18 {
-            panic!("unsupported io unit {io_unit}");
+        if io_unit != CARD_READER_UNIT {
+            return OperationResult::fault(args.addr, format!("unsupported io unit {io_unit}"));
+        }
+        let start_from = get_indexed_addr(self.instruction, args.reg);
+
+        let card = match args.devices.as_deref_mut() {
+            None => Err(format!("io unit {io_unit} is not attached")),
+            Some(devices) => devices.card_reader().read(),
+        };
+        let card = match card {
+            Ok(card) => card,
+            Err(message) => return OperationResult::fault(args.addr, message),
+        };
+        for (i, w) in card.into_iter().enumerate() {
+            args.mem.set_word(start_from as usize + i, w);
         }
-        panic!("not implemented");
 
-        // OperationResult::from_args(self.execution_time, args)
+        OperationResult::from_args(self.execution_time, args)
     }
     fn get_name(&self) -> String {
         String::from("IN")
@@ -61,8 +86,7 @@ impl OUT {
         }
     }
 
-    fn write(&self, io_unit: u8, words: Vec<Word>) -> io::Result<()> {
-        let path = IO_FILE_PREFIX.to_string() + &io_unit.to_string();
+    fn write(&self, path: PathBuf, words: Vec<Word>) -> io::Result<()> {
         let mut file = File::options().create(true).append(true).open(path)?;
 
         let mut line = String::new();
@@ -81,19 +105,21 @@ impl OUT {
 impl Operation for OUT {
     fn execute(&self, args: OperationArgs) -> OperationResult {
         let io_unit = self.instruction.get_byte(4);
-        if io_unit != 18 {
-            panic!("unsupported io unit {io_unit}");
+        if io_unit != PRINTER_UNIT {
+            return OperationResult::fault(args.addr, format!("unsupported io unit {io_unit}"));
         }
-        let unit_block = 24;
         let start_from = get_indexed_addr(self.instruction, args.reg);
 
         let mut out_buffer = Vec::new();
-        for i in 0..unit_block {
+        for i in 0..PRINTER_BLOCK as i32 {
             let addr = (start_from + i) as usize;
             out_buffer.push(args.mem.get(addr));
         }
 
-        self.write(io_unit, out_buffer);
+        let path = unit_path(args.devices.as_deref(), io_unit);
+        if let Err(e) = self.write(path, out_buffer) {
+            return OperationResult::fault(args.addr, format!("can't write io unit {io_unit}: {e}"));
+        }
 
         OperationResult::from_args(self.execution_time, args)
     }
@@ -116,11 +142,7 @@ impl IOC {
         }
     }
 
-    fn write(&self, io_unit: u8) -> io::Result<()> {
-        // let path = "/home/max/Documents/Projects.git/mix/mix/target/";
-        // let mut file = File::create(path.to_string() + &IO_FILE_PREFIX.to_string() + &io_unit.to_string())?;
-
-        let path = IO_FILE_PREFIX.to_string() + &io_unit.to_string();
+    fn write(&self, path: PathBuf) -> io::Result<()> {
         let mut file = File::options().create(true).append(true).open(path)?;
 
         file.write_all(b"\n")?;
@@ -131,10 +153,13 @@ impl IOC {
 impl Operation for IOC {
     fn execute(&self, args: OperationArgs) -> OperationResult {
         let io_unit = self.instruction.get_byte(4);
-        if io_unit != 18 {
-            panic!("unsupported io unit {io_unit}");
+        if io_unit != PRINTER_UNIT {
+            return OperationResult::fault(args.addr, format!("unsupported io unit {io_unit}"));
+        }
+        let path = unit_path(args.devices.as_deref(), io_unit);
+        if let Err(e) = self.write(path) {
+            return OperationResult::fault(args.addr, format!("can't write io unit {io_unit}: {e}"));
         }
-        self.write(io_unit);
 
         OperationResult::from_args(self.execution_time, args)
     }
@@ -143,9 +168,79 @@ impl Operation for IOC {
     }
 }
 
+/// IO is synchronous, so a unit is never busy
+pub struct JBUS {
+    code: u32,
+    execution_time: u32,
+    instruction: Word,
+}
+impl JBUS {
+    pub fn new(instruction: Word) -> JBUS {
+        JBUS {
+            code: 34,
+            execution_time: 1,
+            instruction: instruction,
+        }
+    }
+}
+impl Operation for JBUS {
+    fn execute(&self, args: OperationArgs) -> OperationResult {
+        OperationResult::from_args(self.execution_time, args)
+    }
+    fn get_name(&self) -> String {
+        String::from("JBUS")
+    }
+}
+
+/// IO is synchronous, so a unit is always ready
+pub struct JRED {
+    code: u32,
+    execution_time: u32,
+    instruction: Word,
+}
+impl JRED {
+    pub fn new(instruction: Word) -> JRED {
+        JRED {
+            code: 38,
+            execution_time: 1,
+            instruction: instruction,
+        }
+    }
+}
+impl Operation for JRED {
+    fn execute(&self, args: OperationArgs) -> OperationResult {
+        args.reg.set_j(ShortWord::new(args.addr + 1));
+
+        let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
+        OperationResult::new(self.execution_time, next_addr)
+    }
+    fn get_name(&self) -> String {
+        String::from("JRED")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::devices::*;
+
+    #[test]
+    fn in_card_reader() {
+        let mut m = Memory::new();
+        let mut r = Registers::new();
+        let mut d = Devices::new();
+        d.set_card_reader(CardReader::new(vec!["HELLO WORLD".to_string()]));
+
+        r.set_i(1, ShortWord::new(100));
+        let args = OperationArgs::new(1, &mut m, &mut r).with_devices(&mut d);
+        let op = IN::new(Word::new_by_bytes(0, &[0, 0, 1, 16, 36]));
+        op.execute(args);
+
+        assert_eq!(Word::new_by_bytes(0, &[8, 5, 13, 13, 16]), m.get(100));
+        assert_eq!(Word::new_by_bytes(0, &[0, 26, 16, 19, 13]), m.get(101));
+        assert_eq!(Word::new_by_bytes(0, &[4, 0, 0, 0, 0]), m.get(102));
+        assert!(d.card_reader().is_empty());
+    }
 
     // #[test]
     fn out() {
@@ -23,7 +23,7 @@ impl Operation for JMP {
     fn execute(&self, args: OperationArgs) -> OperationResult {
         args.reg.set_j(ShortWord::new(args.addr + 1));
 
-        let next_addr = self.instruction.get_address() as u32; //TODO: should be indexed??
+        let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
         OperationResult::new(self.execution_time, next_addr)
     }
     fn get_name(&self) -> String {
@@ -49,7 +49,7 @@ impl JSJ {
 }
 impl Operation for JSJ {
     fn execute(&self, args: OperationArgs) -> OperationResult {
-        let next_addr = self.instruction.get_address() as u32;
+        let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
         OperationResult::new(self.execution_time, next_addr)
     }
     fn get_name(&self) -> String {
@@ -79,7 +79,7 @@ impl Operation for JOV {
             args.reg.set_overflow(false);
 
             args.reg.set_j(ShortWord::new(args.addr + 1));
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -111,7 +111,7 @@ impl Operation for JNOV {
         return if !args.reg.is_overflow() {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             args.reg.set_overflow(false);
@@ -144,7 +144,7 @@ impl Operation for JL {
         return if args.reg.get_comparison() == Comparison::LESS {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -176,7 +176,7 @@ impl Operation for JE {
         return if args.reg.get_comparison() == Comparison::EQUAL {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -208,7 +208,7 @@ impl Operation for JG {
         return if args.reg.get_comparison() == Comparison::GREATHER {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -242,7 +242,7 @@ impl Operation for JGE {
         {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -276,7 +276,7 @@ impl Operation for JNE {
         {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -310,7 +310,7 @@ impl Operation for JLE {
         {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -342,7 +342,7 @@ impl Operation for JAN {
         return if args.reg.get_a().get_signed_value() < 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -374,7 +374,7 @@ impl Operation for JAZ {
         return if args.reg.get_a().get_signed_value() == 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -406,7 +406,7 @@ impl Operation for JAP {
         return if args.reg.get_a().get_signed_value() > 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -438,7 +438,7 @@ impl Operation for JANN {
         return if args.reg.get_a().get_signed_value() >= 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -470,7 +470,7 @@ impl Operation for JANZ {
         return if args.reg.get_a().get_signed_value() != 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -502,7 +502,7 @@ impl Operation for JANP {
         return if args.reg.get_a().get_signed_value() <= 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -534,7 +534,7 @@ impl Operation for JXN {
         return if args.reg.get_x().get_signed_value() < 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -566,7 +566,7 @@ impl Operation for JXZ {
         return if args.reg.get_x().get_signed_value() == 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -598,7 +598,7 @@ impl Operation for JXP {
         return if args.reg.get_x().get_signed_value() > 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -630,7 +630,7 @@ impl Operation for JXNN {
         return if args.reg.get_x().get_signed_value() >= 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -662,7 +662,7 @@ impl Operation for JXNZ {
         return if args.reg.get_x().get_signed_value() != 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -694,7 +694,7 @@ impl Operation for JXNP {
         return if args.reg.get_x().get_signed_value() <= 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -727,7 +727,7 @@ impl Operation for JiN {
         return if args.reg.get_i(i).get_signed_value() < 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -761,7 +761,7 @@ impl Operation for JiZ {
         return if args.reg.get_i(i).get_signed_value() == 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -795,7 +795,7 @@ impl Operation for JiP {
         return if args.reg.get_i(i).get_signed_value() > 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -829,7 +829,7 @@ impl Operation for JiNN {
         return if args.reg.get_i(i).get_signed_value() >= 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -863,7 +863,7 @@ impl Operation for JiNZ {
         return if args.reg.get_i(i).get_signed_value() != 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
@@ -897,7 +897,7 @@ impl Operation for JiNP {
         return if args.reg.get_i(i).get_signed_value() <= 0 {
             args.reg.set_j(ShortWord::new(args.addr + 1));
 
-            let next_addr = self.instruction.get_address() as u32;
+            let next_addr = get_indexed_addr(self.instruction, args.reg) as u32;
             OperationResult::new(self.execution_time, next_addr)
         } else {
             OperationResult::from_args(self.execution_time, args)
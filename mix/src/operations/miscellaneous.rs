@@ -379,17 +379,20 @@ impl MOVE {
 }
 impl Operation for MOVE {
     fn execute(&self, args: OperationArgs) -> OperationResult {
-        let n_words = self.instruction.get_f().spec as u32;
+        let n_words = self.instruction.get_byte(4) as u32;
         if n_words == 0 {
             return OperationResult::from_args(self.execution_time, args);
         }
 
+        let start_from = get_indexed_addr(self.instruction, args.reg) as u32;
+        let start_to = args.reg.get_i(1).get();
         for i in 0..n_words {
-            let from = (self.instruction.get_address() as u32 + i) as usize;
-            let to = (args.reg.get_i(1).get() + i) as usize;
+            let from = (start_from + i) as usize;
+            let to = (start_to + i) as usize;
 
             args.mem.set(to, args.mem.get(from).get());
         }
+        args.reg.set_i(1, ShortWord::new(start_to + n_words));
 
         OperationResult::from_args(self.execution_time + 2 * n_words, args)
     }
@@ -439,7 +442,7 @@ impl HLT {
 }
 impl Operation for HLT {
     fn execute(&self, args: OperationArgs) -> OperationResult {
-        OperationResult::from_args(self.execution_time, args)
+        OperationResult::halt(self.execution_time, args)
     }
     fn get_name(&self) -> String {
         String::from("HLT")
@@ -715,6 +718,7 @@ mod tests {
         assert_eq!(m.get(1_000).get(), 3);
         assert_eq!(m.get(1_001).get(), 4);
         assert_eq!(m.get(1_002).get(), 4);
+        assert_eq!(r.get_i(1).get(), 1_002);
     }
 
     #[test]
@@ -742,6 +746,7 @@ mod tests {
         assert_eq!(m.get(1_001).get(), 2);
         assert_eq!(m.get(1_002).get(), 2);
         assert_eq!(m.get(1_003).get(), 2);
+        assert_eq!(r.get_i(1).get(), 1_004);
     }
 
     fn assert_by_bytes(
@@ -1,3 +1,4 @@
+use crate::devices::Devices;
 use crate::memory::short_word::ShortWord;
 use crate::memory::word::Word;
 use crate::memory::Bytes;
@@ -5,54 +6,179 @@ use crate::memory::Instruction;
 use crate::memory::Memory;
 use crate::operations::Operations;
 use crate::registers::Registers;
+use mix_core::opcodes;
+
+use std::time::Duration;
+use std::time::Instant;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RunOutcome {
+    /// HLT is executed
+    Halted,
+    /// the location counter ran past 3999, the last instruction isn't a jump
+    Exited,
+    /// the step limit is reached before the program stopped
+    StepLimit,
+    /// the u-time limit is reached before the program stopped
+    TimeLimit,
+    /// the wall-clock timeout is expired before the program stopped
+    Timeout,
+    /// the instruction at `addr` can't be executed, e.g. an invalid field
+    /// or an address out of memory
+    Fault { addr: u32, message: String },
+}
+
+/// Budgets of one `execute` call, checked before every instruction
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_time: Option<u64>, // u
+    pub timeout: Option<Duration>,
+}
+
+// the clock is read once per this many instructions
+const TIMEOUT_CHECK_STEPS: u64 = 1024;
 
 pub struct Processor {
     addr: u32,
+    steps: u64,
+    time: u64,
+    limits: Limits,
+    trace: bool,
+}
+
+impl Default for Processor {
+    fn default() -> Processor {
+        Processor::new()
+    }
 }
 
 impl Processor {
     pub fn new() -> Processor {
-        Processor { addr: 0 }
+        Processor::start_from(0)
     }
 
     pub fn start_from(addr: u32) -> Processor {
-        Processor { addr }
+        Processor {
+            addr,
+            steps: 0,
+            time: 0,
+            limits: Limits::default(),
+            trace: false,
+        }
     }
 
     pub fn set_addr(&mut self, addr: u32) {
         self.addr = addr;
     }
 
-    pub fn execute(&mut self, mem: &mut Memory, reg: &mut Registers) {
-        let op = Operations::new();
-        let mut count = 0;
-        while true {
-            if self.addr > 3_999 {
-                break;
+    pub fn get_addr(&self) -> u32 {
+        self.addr
+    }
+
+    /// Executed instructions since the processor is created
+    pub fn get_steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Spent time in u
+    pub fn get_time(&self) -> u64 {
+        self.time
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Prints every executed instruction to stderr
+    pub fn set_trace(&mut self, trace: bool) {
+        self.trace = trace;
+    }
+
+    pub fn execute(
+        &mut self,
+        mem: &mut Memory,
+        reg: &mut Registers,
+        devices: &mut Devices,
+    ) -> RunOutcome {
+        let started = Instant::now();
+        let (start_steps, start_time) = (self.steps, self.time);
+        loop {
+            let steps = self.steps - start_steps;
+            if let Some(max_steps) = self.limits.max_steps {
+                if steps >= max_steps {
+                    return RunOutcome::StepLimit;
+                }
+            }
+            if let Some(max_time) = self.limits.max_time {
+                if self.time - start_time >= max_time {
+                    return RunOutcome::TimeLimit;
+                }
+            }
+            if let Some(timeout) = self.limits.timeout {
+                if steps.is_multiple_of(TIMEOUT_CHECK_STEPS) && started.elapsed() >= timeout {
+                    return RunOutcome::Timeout;
+                }
             }
 
-            let instruction = mem.get(self.addr as usize);
+            if let Some(outcome) = self.step(mem, reg, devices) {
+                return outcome;
+            }
+        }
+    }
 
-            let result = op.execute(self.addr, instruction, mem, reg);
+    /// Executes one instruction, returns the outcome if the program is stopped
+    pub fn step(
+        &mut self,
+        mem: &mut Memory,
+        reg: &mut Registers,
+        devices: &mut Devices,
+    ) -> Option<RunOutcome> {
+        if self.addr > 3_999 {
+            return Some(RunOutcome::Exited);
+        }
 
-            // println!("      {:#?}", reg);
-            // for i in 0..10 {
-            // println!("      {i}={}", mem.get(i as usize).get_signed_value());
-            // }
+        let instruction = mem.get(self.addr as usize);
+        if self.trace {
+            let name = opcodes::by_code(instruction.get_c(), instruction.get_byte(4))
+                .map_or("?", |op| op.name);
+            eprintln!(
+                "{:04} {:4} {},{}({}) | {:?}",
+                self.addr,
+                name,
+                instruction.get_address(),
+                instruction.get_i(),
+                instruction.get_byte(4),
+                reg
+            );
+        }
 
-            self.addr = result.next_addr_instruction;
+        let result = Operations::new().execute(self.addr, instruction, mem, reg, devices);
+        if let Some(message) = result.fault {
+            return Some(RunOutcome::Fault {
+                addr: self.addr,
+                message,
+            });
+        }
 
-            // count += 1;
-            // if count > 50 {
-            // break;
-            // }
+        self.steps += 1;
+        self.time += result.execution_time as u64;
+        self.addr = result.next_addr_instruction;
+        if result.is_halted {
+            return Some(RunOutcome::Halted);
         }
+        if self.addr > 3_999 {
+            return Some(RunOutcome::Exited);
+        }
+        None
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use mix_core::instruction::Instruction::*;
+    use mix_core::instruction::ProgramBuilder;
 
     #[test]
     fn maximum() {
@@ -66,21 +192,26 @@ mod tests {
 
         let mut p = Processor::start_from(3_000);
 
-        // programm code
-        m.set_instr_as_bytes(3_000, 3009, 0, 2, 32);
-        m.set_instr_as_bytes(3_001, 0, 1, 2, 51);
-        m.set_instr_as_bytes(3_002, 3005, 0, 0, 39);
-        m.set_instr_as_bytes(3_003, 1000, 3, 5, 56);
-        m.set_instr_as_bytes(3_004, 3007, 0, 7, 39);
-        m.set_instr_as_bytes(3_005, 0, 3, 2, 50);
-        m.set_instr_as_bytes(3_006, 1000, 3, 5, 8);
-        m.set_instr_as_bytes(3_007, 1, 0, 1, 51);
-        m.set_instr_as_bytes(3_008, 3003, 0, 2, 43);
-        m.set_instr_as_bytes(3_009, 3009, 0, 0, 39);
-
-        // n
+        // programm code, Algorithm M (1.3.1)
+        let (_, program) = ProgramBuilder::new(3_000)
+            .instruction(Stj { addr: 3009, minus_zero: false, index: 0, field: 2 })
+            .instruction(Ent3 { addr: 0, minus_zero: false, index: 1 })
+            .instruction(Jmp { addr: 3005, minus_zero: false, index: 0 })
+            .instruction(Cmpa { addr: 1000, minus_zero: false, index: 3, field: 5 })
+            .instruction(Jge { addr: 3007, minus_zero: false, index: 0 })
+            .instruction(Ent2 { addr: 0, minus_zero: false, index: 3 })
+            .instruction(Lda { addr: 1000, minus_zero: false, index: 3, field: 5 })
+            .instruction(Dec3 { addr: 1, minus_zero: false, index: 0 })
+            .instruction(J3p { addr: 3003, minus_zero: false, index: 0 })
+            .instruction(Jmp { addr: 3009, minus_zero: false, index: 0 })
+            .instruction(Hlt { addr: 0, minus_zero: false, index: 0 })
+            .build()
+            .expect("valid program");
+        m.set_words(&program);
+
+        // n, the subroutine returns to HLT
         r.set_i(1, ShortWord::new(10));
-        r.set_j(ShortWord::new(4_000));
+        r.set_j(ShortWord::new(3_010));
 
         // elements
         m.set(1_000, 1);
@@ -95,13 +226,149 @@ mod tests {
         m.set(1_008, 2);
         m.set(1_009, 10);
 
-        p.execute(&mut m, &mut r);
+        let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
 
         let i = r.get_i(2).get() as usize;
         let max = m.get(1_000 + i).get();
 
         assert_eq!(i, 7);
         assert_eq!(max, 33);
+        assert_eq!(RunOutcome::Halted, outcome);
+    }
+
+    #[test]
+    fn subroutine_linkage() {
+        let mut m = Memory::new();
+        let mut r = Registers::new();
+
+        let mut p = Processor::start_from(3_002);
+
+        // SUB STJ EXIT ; EXIT JMP * ; START JMP SUB ; HLT
+        let stj = opcodes::by_name("STJ").expect("STJ");
+        let (_, program) = ProgramBuilder::new(3_000)
+            .instruction(Stj { addr: 3001, minus_zero: false, index: 0, field: stj.f })
+            .instruction(Jmp { addr: 3001, minus_zero: false, index: 0 })
+            .instruction(Jmp { addr: 3000, minus_zero: false, index: 0 })
+            .instruction(Hlt { addr: 0, minus_zero: false, index: 0 })
+            .build()
+            .expect("valid program");
+        m.set_words(&program);
+
+        let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
+        assert_eq!(RunOutcome::Halted, outcome);
+        assert_eq!(4, p.get_steps());
+        // only the address of the JMP is replaced
+        assert_eq!(Ok(m.get(3_001)), Jmp { addr: 3003, minus_zero: false, index: 0 }.encode());
+    }
+
+    #[test]
+    fn faults() {
+        let cases = [
+            // CMP1 3000(5:6)
+            ((3_000, 0, 46, 57), 0, "field (5:6) is invalid"),
+            ((0, 0, 9, 5), 0, "unknown operation C=5 F=9"),
+            ((0, 7, 5, 8), 0, "index register 7 is out of range 0-6"),
+            // LDA 4000, LDA -1,1
+            ((4_000, 0, 5, 8), 0, "address 4000 is out of range 0-3999"),
+            ((0, 1, 5, 8), -1, "address -1 is out of range 0-3999"),
+            // OUT 3990(18) needs 24 words
+            ((3_990, 0, 18, 37), 0, "address 3990 is out of range 0-3999"),
+            // JMP -100,1 and J1N 4000
+            ((0, 1, 0, 39), -100, "address -100 is out of range 0-3999"),
+            ((4_000, 0, 0, 41), -1, "address 4000 is out of range 0-3999"),
+            // INC1 4095 with rI1 = 4095
+            ((4_095, 0, 0, 49), 4_095, "INC1 overflow, 8190 doesn't fit into rI1"),
+            ((0, 0, 17, 36), 0, "unsupported io unit 17"),
+            ((0, 0, 16, 36), 0, "card reader is empty, no card 1"),
+        ];
+
+        for ((addr, index, field, code), ri1, message) in cases {
+            let mut m = Memory::new();
+            let mut r = Registers::new();
+            r.set_i(1, ShortWord::new_from_signed(ri1));
+            m.set_instr_as_bytes(3_000, addr, index, field, code);
+
+            let mut p = Processor::start_from(3_000);
+            let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
+
+            let fault = RunOutcome::Fault {
+                addr: 3_000,
+                message: message.to_string(),
+            };
+            assert_eq!(fault, outcome);
+            assert_eq!(0, p.get_steps());
+            assert_eq!(3_000, p.get_addr());
+        }
+    }
+
+    #[test]
+    fn step_limit() {
+        let mut m = Memory::new();
+        let mut r = Registers::new();
+
+        let mut p = Processor::start_from(3_000);
+        p.set_limits(Limits {
+            max_steps: Some(10),
+            ..Limits::default()
+        });
+
+        // INC1 1 ; JMP 3000
+        m.set_instr_as_bytes(3_000, 1, 0, 0, 49);
+        m.set_instr_as_bytes(3_001, 3000, 0, 0, 39);
+
+        let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
+        assert_eq!(RunOutcome::StepLimit, outcome);
+        assert_eq!(10, p.get_steps());
+        assert_eq!(3_000, p.get_addr());
+        assert_eq!(5, r.get_i(1).get_signed_value());
+
+        // the budget is per run, the state is intact to continue
+        let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
+        assert_eq!(RunOutcome::StepLimit, outcome);
+        assert_eq!(20, p.get_steps());
+        assert_eq!(10, r.get_i(1).get_signed_value());
+    }
+
+    #[test]
+    fn time_limit() {
+        let mut m = Memory::new();
+        let mut r = Registers::new();
+
+        let mut p = Processor::start_from(3_000);
+        p.set_limits(Limits {
+            max_time: Some(5),
+            ..Limits::default()
+        });
+
+        // INC1 1 ; JMP 3000
+        m.set_instr_as_bytes(3_000, 1, 0, 0, 49);
+        m.set_instr_as_bytes(3_001, 3000, 0, 0, 39);
+
+        let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
+        assert_eq!(RunOutcome::TimeLimit, outcome);
+        assert_eq!(5, p.get_time());
+        assert_eq!(3_001, p.get_addr());
+        assert_eq!(3, r.get_i(1).get_signed_value());
+    }
+
+    #[test]
+    fn timeout() {
+        let mut m = Memory::new();
+        let mut r = Registers::new();
+
+        let mut p = Processor::start_from(3_000);
+        p.set_limits(Limits {
+            timeout: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        });
+
+        // JMP 3000
+        m.set_instr_as_bytes(3_000, 3000, 0, 0, 39);
+
+        let outcome = p.execute(&mut m, &mut r, &mut Devices::new());
+        assert_eq!(RunOutcome::Timeout, outcome);
+        assert_eq!(3_000, p.get_addr());
+        assert!(p.get_steps() > 0);
     }
 
     // #[test]
@@ -157,6 +424,6 @@ mod tests {
         m.set_word(2_050, Word::new_from_signed(3));
         m.set_word(2_051, Word::new_from_signed(-499));
 
-        p.execute(&mut m, &mut r);
+        p.execute(&mut m, &mut r, &mut Devices::new());
     }
 }
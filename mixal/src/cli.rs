@@ -0,0 +1,346 @@
+use mixal::SourceFormat;
+
+pub const USAGE: &str = "usage: mixal [options] <program.mixal>
+       mixal link [-o program.mixo] [--base ADDR] <module.mixo>...
+                            link modules, -o is a.mixo by default, .mix writes text
+       mixal lsp            language server over stdin and stdout
+       mixal fmt [--check] [options] <program.mixal>
+                            lay out the source in columns, in place or to -o
+
+options:
+  -o PATH              write the program to PATH, the file is replaced
+  --format FORMAT      text (.mix, default), object (.mixo), deck (.deck) or json (.json)
+  --object             the same as --format object
+  --module             relocatable module for the linker, written as an object
+  --listing            write the listing next to the program (.lst)
+  --symbols            write the symbol table next to the program (.sym)
+  --symbols-json       write the symbol table as JSON next to the program (.sym.json)
+  --fixed, --free      source in card columns or separated by blanks, guessed by default
+  -I DIR               look for INCLUDE files in DIR, can be repeated
+  -D NAME[=VALUE]      define NAME for IF and IFDEF, VALUE is 1 by default
+  -q, --quiet          print nothing but errors
+  -v, --verbose        print the written files
+  -h, --help           print this help";
+
+/// Format of the assembled program
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// `.mix` lines of the emulator
+    Text,
+    /// binary `.mixo` object
+    Object,
+    /// self-loading card deck for the GO button
+    Deck,
+    /// memory image as JSON
+    Json,
+}
+
+impl OutputFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Text => "mix",
+            OutputFormat::Object => "mixo",
+            OutputFormat::Deck => "deck",
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub source: String,
+    pub output: Option<String>,
+    pub format: OutputFormat,
+    pub module: bool,
+    pub listing: bool,
+    pub symbols: bool,
+    pub symbols_json: bool,
+    pub source_format: SourceFormat,
+    pub include_paths: Vec<String>,
+    pub defines: Vec<(String, i32)>,
+    pub verbosity: Verbosity,
+    pub help: bool,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            source: String::new(),
+            output: None,
+            format: OutputFormat::Text,
+            module: false,
+            listing: false,
+            symbols: false,
+            symbols_json: false,
+            source_format: SourceFormat::Auto,
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            verbosity: Verbosity::Normal,
+            help: false,
+        }
+    }
+
+    /// `-o` or the source with the extension of the format
+    pub fn output_path(&self) -> String {
+        match &self.output {
+            Some(output) => output.clone(),
+            None => format!(
+                "{}.{}",
+                without_extension(&self.source),
+                self.format.extension()
+            ),
+        }
+    }
+
+    /// Path of a companion file: the program path with another extension
+    pub fn companion_path(&self, extension: &str) -> String {
+        format!("{}.{extension}", without_extension(&self.output_path()))
+    }
+}
+
+/// Parses the command line arguments without the program name
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::new();
+    let mut source: Option<String> = None;
+    let mut format: Option<OutputFormat> = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or(format!("{name} requires a value"))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "-q" | "--quiet" => options.verbosity = Verbosity::Quiet,
+            "-v" | "--verbose" => options.verbosity = Verbosity::Verbose,
+            "-o" => options.output = Some(value("-o")?),
+            "--format" => {
+                format = Some(match value("--format")?.as_str() {
+                    "text" => OutputFormat::Text,
+                    "object" => OutputFormat::Object,
+                    "deck" => OutputFormat::Deck,
+                    "json" => OutputFormat::Json,
+                    other => {
+                        return Err(format!(
+                            "unknown format '{other}', it should be text, object, deck or json"
+                        ))
+                    }
+                })
+            }
+            "--object" => format = Some(OutputFormat::Object),
+            "--module" => options.module = true,
+            "--listing" => options.listing = true,
+            "--symbols" => options.symbols = true,
+            "--symbols-json" => options.symbols_json = true,
+            "--fixed" => options.source_format = SourceFormat::Fixed,
+            "--free" => options.source_format = SourceFormat::Free,
+            "-I" => options.include_paths.push(value("-I")?),
+            "-D" => options.defines.push(parse_define(&value("-D")?)?),
+            _ if arg.starts_with("-I") => options.include_paths.push(arg[2..].to_string()),
+            _ if arg.starts_with("-D") => options.defines.push(parse_define(&arg[2..])?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => match source {
+                Some(_) => return Err(format!("unexpected argument {arg}")),
+                None => source = Some(arg.to_string()),
+            },
+        }
+    }
+
+    // a module can be written only as an object, the other formats have no relocation
+    options.format = match (format, options.module) {
+        (None, true) | (Some(OutputFormat::Object), true) => OutputFormat::Object,
+        (Some(format), true) => {
+            return Err(format!(
+                "--module is written as an object, it can't be in {} format",
+                format.extension()
+            ))
+        }
+        (format, false) => format.unwrap_or(OutputFormat::Text),
+    };
+
+    match source {
+        Some(source) => options.source = source,
+        None if options.help => {}
+        None => return Err("source path is missing".to_string()),
+    }
+
+    Ok(options)
+}
+
+/// Options of `mixal link`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkOptions {
+    pub modules: Vec<String>,
+    pub output: String,
+    pub base: u32,
+    pub help: bool,
+}
+
+/// Parses the arguments of `mixal link`, without `link`
+pub fn parse_link_args(args: &[String]) -> Result<LinkOptions, String> {
+    let mut options = LinkOptions {
+        modules: Vec::new(),
+        output: "a.mixo".to_string(),
+        base: 0,
+        help: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or(format!("{name} requires a value"))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "-o" => options.output = value("-o")?,
+            "--base" => {
+                let base = value("--base")?;
+                options.base = base
+                    .parse()
+                    .ok()
+                    .filter(|base| *base < 4000)
+                    .ok_or(format!("--base {base}: the address should be 0-3999"))?;
+            }
+            _ if arg.starts_with('-') => return Err(format!("unknown option {arg}")),
+            _ => options.modules.push(arg.to_string()),
+        }
+    }
+
+    if options.modules.is_empty() && !options.help {
+        return Err("modules to link are missing".to_string());
+    }
+    Ok(options)
+}
+
+/// NAME or NAME=VALUE of -D
+fn parse_define(define: &str) -> Result<(String, i32), String> {
+    match define.split_once('=') {
+        Some((name, value)) => {
+            let value = value
+                .parse()
+                .map_err(|_| format!("-D{define}: '{value}' is not a valid number"))?;
+            Ok((name.to_string(), value))
+        }
+        None if define.is_empty() => Err("-D requires a name".to_string()),
+        None => Ok((define.to_string(), 1)),
+    }
+}
+
+/// The path without the extension of its file name
+fn without_extension(path: &str) -> &str {
+    let name_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match path[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => &path[..name_start + dot],
+        _ => path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let options = parse_args(&args(
+            "--format deck -o out/primes.cards --listing --symbols --fixed -I lib -Iinc -D DEBUG \
+             -DSIZE=-5 -q primes.mixal",
+        ))
+        .expect("valid options");
+
+        assert_eq!("primes.mixal", options.source);
+        assert_eq!(OutputFormat::Deck, options.format);
+        assert_eq!("out/primes.cards", options.output_path());
+        assert_eq!("out/primes.lst", options.companion_path("lst"));
+        assert!(options.listing && options.symbols && !options.symbols_json);
+        assert_eq!(SourceFormat::Fixed, options.source_format);
+        assert_eq!(vec!["lib", "inc"], options.include_paths);
+        assert_eq!(
+            vec![("DEBUG".to_string(), 1), ("SIZE".to_string(), -5)],
+            options.defines
+        );
+        assert_eq!(Verbosity::Quiet, options.verbosity);
+
+        let options = parse_args(&args("../dir.v2/primes.mixal")).expect("valid options");
+        assert_eq!(OutputFormat::Text, options.format);
+        assert_eq!("../dir.v2/primes.mix", options.output_path());
+        assert_eq!(
+            "../dir.v2/primes.sym.json",
+            options.companion_path("sym.json")
+        );
+
+        let options = parse_args(&args("--module lib.mixal")).expect("valid options");
+        assert_eq!(OutputFormat::Object, options.format);
+        assert_eq!("lib.mixo", options.output_path());
+        let options = parse_args(&args("--format json -o image prog")).expect("valid options");
+        assert_eq!("image.json", options.companion_path("json"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = |line: &str| parse_args(&args(line)).expect_err("invalid options");
+
+        assert_eq!(
+            "unknown format 'hex', it should be text, object, deck or json",
+            error("--format hex p.mixal")
+        );
+        assert_eq!("-o requires a value", error("p.mixal -o"));
+        assert_eq!(
+            "--module is written as an object, it can't be in deck format",
+            error("--module --format deck p.mixal")
+        );
+        assert_eq!("-DN=x: 'x' is not a valid number", error("-DN=x p.mixal"));
+        assert_eq!("unknown option --list", error("--list p.mixal"));
+        assert_eq!("unexpected argument q.mixal", error("p.mixal q.mixal"));
+        assert_eq!("source path is missing", error("--listing"));
+        assert!(parse_args(&args("-h")).expect("help").help);
+    }
+
+    #[test]
+    fn parse_link_options() {
+        let options =
+            parse_link_args(&args("a.mixo --base 100 -o prog.mix b.mixo")).expect("valid options");
+        assert_eq!(vec!["a.mixo", "b.mixo"], options.modules);
+        assert_eq!("prog.mix", options.output);
+        assert_eq!(100, options.base);
+
+        let options = parse_link_args(&args("a.mixo")).expect("valid options");
+        assert_eq!("a.mixo", options.output);
+        assert_eq!(0, options.base);
+    }
+
+    #[test]
+    fn parse_link_errors() {
+        let error = |line: &str| parse_link_args(&args(line)).expect_err("invalid options");
+
+        assert_eq!("-o requires a value", error("a.mixo -o"));
+        assert_eq!("--base requires a value", error("a.mixo --base"));
+        assert_eq!(
+            "--base 4000: the address should be 0-3999",
+            error("--base 4000 a.mixo")
+        );
+        assert_eq!(
+            "--base ten: the address should be 0-3999",
+            error("--base ten a.mixo")
+        );
+        assert_eq!("unknown option --bse", error("--bse 10 a.mixo"));
+        assert_eq!("modules to link are missing", error("-o prog.mixo"));
+        assert!(parse_link_args(&args("--help")).expect("help").help);
+    }
+}
@@ -0,0 +1,224 @@
+use crate::parser::Assembly;
+use mix_core::chars::code_char;
+use mix_core::word::{Word, ABS, SIGN};
+
+/*
+ * Self-loading card deck, one card per line
+ *
+ * loader cards  -> the two cards of the loader of exercise 1.3.1-26, GO reads
+ *                  the first one into 0000-0015 and jumps to 0000
+ * data card         -> name (columns 1-5) || n (6) || address (7-10) || n * word (10 columns each)
+ * transfer card -> "TRANS0" || start address (7-10)
+ *
+ * word is its magnitude in 10 digits, a negative word has the sign punched over
+ * its last digit: 0-9 become the characters with codes 10-19 (Δ, J, K ... R)
+ */
+
+/// Words on a data card
+pub const WORDS_PER_CARD: usize = 7;
+
+/// Cards of the loader, the second one is read into 0016-0031
+pub const LOADER: [&str; 2] = [
+    " O O6 Z O6    I C O4 0 EH A  F F CF 0  E   EU 0 IH G BB   EJ  CA. Z EU   EH E BA",
+    "   EU 2A-H S BB  C U 1AEH 2AEN V  E  CLU  ABG Z EH E BB J B. A  9",
+];
+
+/// Memory of the loader: its address counter in 0000, the code and the card
+/// buffer in 0029-0044
+pub const LOADER_MEMORY: u32 = 45;
+
+/// Loader cards, data cards with the words of the program and the transfer card,
+/// `name` is punched in the first columns of every data card
+pub fn to_deck(program: &Assembly, name: &str) -> Result<Vec<String>, String> {
+    let mut words = program.words.to_vec();
+    words.sort_by_key(|(addr, _)| *addr);
+    // the same address is assembled twice, the last word wins
+    words.reverse();
+    words.dedup_by_key(|(addr, _)| *addr);
+    words.reverse();
+
+    let overwritten: Vec<u32> = words
+        .iter()
+        .map(|(addr, _)| *addr)
+        .filter(|addr| *addr < LOADER_MEMORY)
+        .collect();
+    if let [first, others @ ..] = &overwritten[..] {
+        return Err(format!(
+            "the loader uses 0000-{:04}, {} at {first:04} can't be loaded from cards",
+            LOADER_MEMORY - 1,
+            match others.len() {
+                0 => "the word".to_string(),
+                n => format!("{} words from the one", n + 1),
+            }
+        ));
+    }
+
+    let name = card_name(name);
+    let mut cards: Vec<String> = LOADER.iter().map(|card| card.to_string()).collect();
+    let mut card: Vec<(u32, Word)> = Vec::new();
+    for (addr, word) in words {
+        let consecutive = card.last().map(|(last, _)| *last + 1 == addr);
+        if consecutive == Some(false) || card.len() == WORDS_PER_CARD {
+            cards.push(data_card(&name, &card));
+            card.clear();
+        }
+        card.push((addr, word));
+    }
+    if !card.is_empty() {
+        cards.push(data_card(&name, &card));
+    }
+
+    cards.push(format!("TRANS0{:04}", program.start));
+    Ok(cards)
+}
+
+fn data_card(name: &str, words: &[(u32, Word)]) -> String {
+    let mut card = format!("{name}{}{:04}", words.len(), words[0].0);
+    for (_, word) in words {
+        card += &punch(*word);
+    }
+    card
+}
+
+/// 10 digits of the magnitude with the sign over the last one
+fn punch(word: Word) -> String {
+    let mut digits = format!("{:010}", word.get() & ABS);
+    if word.get() & SIGN != 0 {
+        let last = digits.pop().and_then(|d| d.to_digit(10)).unwrap_or(0);
+        digits.push(code_char(10 + last as u8).unwrap_or(' '));
+    }
+    digits
+}
+
+/// Name of the program in 5 columns: MIX letters and digits of `name` in upper case
+fn card_name(name: &str) -> String {
+    let name: String = name
+        .to_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .take(5)
+        .collect();
+    format!("{name:5}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Options};
+
+    #[test]
+    fn data_cards() {
+        let source = " ORIG 100\n\
+                      START CON -1\n\
+                      \x20CON 1073741823\n\
+                      \x20ORIG 200\n";
+        let values: String = (1..=8).map(|v| format!(" CON {v}\n")).collect();
+        let source = source.to_string() + &values + " CON -10\n END START\n";
+        let program = assemble(&source, &Options::new("p.mixal")).expect("valid program");
+
+        let deck = to_deck(&program, "p").expect("words after the loader");
+        assert_eq!(LOADER.to_vec(), deck[..2]);
+        assert_eq!(
+            vec![
+                "P    20100000000000J1073741823",
+                "P    702000000000001000000000200000000030000000004000000000500000000060000000007",
+                "P    202070000000008000000001\u{0394}",
+                "TRANS00100",
+            ],
+            deck[2..]
+        );
+        assert_eq!("PRIME", card_name("primes"));
+
+        let program = assemble(
+            " ORIG 44\nSTART HLT\n END START\n",
+            &Options::new("p.mixal"),
+        );
+        assert_eq!(
+            Err(
+                "the loader uses 0000-0044, the word at 0044 can't be loaded from cards"
+                    .to_string()
+            ),
+            to_deck(&program.expect("valid program"), "p")
+        );
+    }
+
+    #[test]
+    fn card_layout() {
+        let source = " ORIG 3000\nSTART ENTA -2\n CON -123456789\n ORIG 3010\n HLT\n END START\n";
+        let program = assemble(source, &Options::new("p.mixal")).expect("valid program");
+        let deck = to_deck(&program, "layout").expect("words after the loader");
+        assert_eq!(5, deck.len());
+
+        // name in 1-5, the number of words in 6, the address in 7-10, 10 columns a word
+        let card: Vec<char> = deck[2].chars().collect();
+        let columns = |from: usize, to: usize| card[from - 1..to].iter().collect::<String>();
+        assert_eq!(30, card.len());
+        assert_eq!("LAYOU", columns(1, 5));
+        assert_eq!("2", columns(6, 6));
+        assert_eq!("3000", columns(7, 10));
+        // ENTA -2 is - 0002 00 02 48 = -524464, its last digit 4 is punched as M
+        assert_eq!("000052446M", columns(11, 20));
+        // 0 is Δ, 1 is J ... 9 is R
+        assert_eq!("012345678R", columns(21, 30));
+
+        // a gap starts a new card, the transfer card has the start address in 7-10
+        assert_eq!("LAYOU13010", &deck[3][..10]);
+        assert_eq!("TRANS03000", deck[4]);
+    }
+
+    #[test]
+    fn loader_memory_refused() {
+        let source = " ORIG 10\nSTART HLT\n NOP\n ORIG 3000\n HLT\n END START\n";
+        let program = assemble(source, &Options::new("p.mixal")).expect("valid program");
+        assert_eq!(
+            Err(
+                "the loader uses 0000-0044, 2 words from the one at 0010 can't be loaded from cards"
+                    .to_string()
+            ),
+            to_deck(&program, "p")
+        );
+
+        // 0045 is the first free word
+        let program = assemble(" ORIG 45\nSTART HLT\n END START\n", &Options::new("p.mixal"))
+            .expect("valid program");
+        assert!(to_deck(&program, "p").is_ok());
+    }
+
+    #[test]
+    fn go() {
+        // sums 10 numbers, two of them negative, the program takes three data cards
+        let values: String = [1, 2, -3, 4, 5, 6, 7, -8, 9, 10]
+            .iter()
+            .map(|v| format!(" CON {v}\n"))
+            .collect();
+        let source = " ORIG 1000\nDATA EQU *\n".to_string()
+            + &values
+            + "START ENT1 0\n\
+               \x20ENTA 0\n\
+               LOOP ADD DATA,1\n\
+               \x20INC1 1\n\
+               \x20CMP1 =10=\n\
+               \x20JL LOOP\n\
+               \x20STA SUM\n\
+               \x20HLT\n\
+               SUM CON 0\n\
+               \x20END START\n";
+        let program = assemble(&source, &Options::new("sum.mixal")).expect("valid program");
+        let deck = to_deck(&program, "sum").expect("words after the loader");
+        assert_eq!(6, deck.len());
+
+        let path = std::env::temp_dir().join(format!("mixal_go_{}.deck", std::process::id()));
+        std::fs::write(&path, deck.join("\n") + "\n").expect("deck");
+        let mut mix = mix::MIX::new();
+        let loaded = mix.load_deck(&path.to_string_lossy());
+        std::fs::remove_file(&path).expect("deck");
+        loaded.expect("deck should load");
+
+        assert_eq!(mix::RunOutcome::Halted, mix.go());
+        let sum = program.symbols.iter().find(|s| s.name == "SUM").expect("SUM");
+        assert_eq!(33, mix.memory().get(sum.value as usize).get_signed_value());
+        assert_eq!(-8, mix.memory().get(1007).get_signed_value());
+        assert_eq!(33, mix.registers().get_a().get_signed_value());
+        assert_eq!(10, mix.registers().get_i(1).get_signed_value());
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Problem in a source line, `line` is 1-based, 0 for the whole program.
+/// `columns` are 0-based character positions in the line, if known.
+/// `file` is set for lines of included files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<String>,
+    pub line: usize,
+    pub columns: Option<Range<usize>>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: None,
+            line,
+            columns: None,
+            message,
+        }
+    }
+
+    pub fn warning(line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(line, message)
+        }
+    }
+
+    pub fn with_columns(mut self, columns: Range<usize>) -> Diagnostic {
+        self.columns = Some(columns);
+        self
+    }
+
+    pub fn with_line(mut self, line: usize) -> Diagnostic {
+        self.line = line;
+        self
+    }
+
+    pub fn in_file(mut self, file: Option<String>) -> Diagnostic {
+        self.file = file;
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// All problems found in a program, `name` is the source name used in messages.
+/// When `source` is set, the offending line is printed with a caret under the columns,
+/// `included` has the names and the lines of included files for the same
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostics {
+    pub name: String,
+    pub source: Vec<String>,
+    pub included: Vec<(String, Vec<String>)>,
+    pub items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    pub fn new(name: &str) -> Diagnostics {
+        Diagnostics {
+            name: name.to_string(),
+            source: Vec::new(),
+            included: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+
+    pub fn with_source(mut self, source: &str) -> Diagnostics {
+        self.source = source.lines().map(|l| l.to_string()).collect();
+        self
+    }
+
+    pub fn with_included(mut self, name: &str, source: &str) -> Diagnostics {
+        let lines = source.lines().map(|l| l.to_string()).collect();
+        self.included.push((name.to_string(), lines));
+        self
+    }
+
+    pub fn push(&mut self, line: usize, message: String) {
+        self.items.push(Diagnostic::new(line, message));
+    }
+
+    pub fn append(&mut self, other: Diagnostics) {
+        self.items.extend(other.items);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.items.iter().filter(|d| d.is_error()).count()
+    }
+
+    fn write_snippet(&self, f: &mut fmt::Formatter<'_>, d: &Diagnostic) -> fmt::Result {
+        let source = match &d.file {
+            None => &self.source,
+            Some(file) => match self.included.iter().find(|(name, _)| name == file) {
+                Some((_, lines)) => lines,
+                None => return Ok(()),
+            },
+        };
+        let text = match d.line.checked_sub(1).and_then(|i| source.get(i)) {
+            None => return Ok(()),
+            Some(text) => text,
+        };
+        writeln!(f, "{:>5} | {}", d.line, text)?;
+
+        if let Some(columns) = &d.columns {
+            // keep tabs so the caret is under the same character
+            let indent: String = text
+                .chars()
+                .chain(std::iter::repeat(' '))
+                .take(columns.start)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let width = columns.len().max(1);
+            writeln!(f, "{:>5} | {}{}", "", indent, "^".repeat(width))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for d in &self.items {
+            let name = d.file.as_ref().unwrap_or(&self.name);
+            match (d.line, &d.columns) {
+                (0, _) => write!(f, "{}", name)?,
+                (line, None) => write!(f, "{}:{}", name, line)?,
+                (line, Some(columns)) => write!(f, "{}:{}:{}", name, line, columns.start + 1)?,
+            }
+            writeln!(f, ": {}: {}", d.severity, d.message)?;
+            self.write_snippet(f, d)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let mut diagnostics = Diagnostics::new("p.mixal").with_source(" ORIG 3000\nSTART LDA X\n");
+        diagnostics
+            .items
+            .push(Diagnostic::new(2, "symbol X is not defined".to_string()).with_columns(10..11));
+        diagnostics
+            .items
+            .push(Diagnostic::warning(0, "END is missing".to_string()));
+        diagnostics.push(1, "something".to_string());
+        let mut diagnostics = diagnostics.with_included("lib/print.mixal", " OUT 0(18)\n");
+        diagnostics.items.push(
+            Diagnostic::new(1, "unit 18".to_string())
+                .in_file(Some("lib/print.mixal".to_string()))
+                .with_columns(5..10),
+        );
+
+        assert_eq!(3, diagnostics.errors());
+        assert_eq!(
+            "p.mixal:2:11: error: symbol X is not defined\n\
+             \x20   2 | START LDA X\n\
+             \x20     |           ^\n\
+             p.mixal: warning: END is missing\n\
+             p.mixal:1: error: something\n\
+             \x20   1 |  ORIG 3000\n\
+             lib/print.mixal:1:6: error: unit 18\n\
+             \x20   1 |  OUT 0(18)\n\
+             \x20     |      ^^^^^\n",
+            diagnostics.to_string()
+        );
+    }
+}
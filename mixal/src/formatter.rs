@@ -0,0 +1,266 @@
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use crate::lexer::{self, SourceFormat};
+use crate::macros;
+use crate::object::to_object;
+use crate::preprocessor::{self, DIRECTIVES};
+use crate::tags::MixInstructions;
+use crate::{assemble, Options};
+
+/*
+ * Canonical layout of a source file
+ *
+ * LOC        OP   ADDRESS          comment
+ * 1-10       12-15 17-             one column for the file, 2 blanks after the longest field
+ *
+ * A longer LOC or OP moves the rest of its line. Mnemonics are written in upper case,
+ * `*` comment lines and blank lines are kept. ALF takes its 5 characters from column 17
+ * when the file is in card columns, otherwise one blank after ALF.
+ * INCLUDE and IF lines keep everything after the directive as it is
+ */
+
+/// Fields of a source line, the address has no blanks but for ALF
+struct Line {
+    loc: String,
+    op: String,
+    address: String,
+    comment: String,
+    alf: bool,
+}
+
+/// Source laid out in the canonical columns. The program is assembled before and after,
+/// the source is refused if it has errors or the formatted one doesn't give the same
+/// memory image, start address and module
+pub fn format(source: &str, options: &Options) -> Result<String, Diagnostics> {
+    let before = assemble(source, options)?;
+
+    let mix_inst = MixInstructions::new();
+    let format = assembled_format(&mix_inst, source, options);
+    let lines: Vec<Option<Line>> = source
+        .lines()
+        .map(|text| split(&mix_inst, text, format))
+        .collect();
+
+    let mut formatted = layout(source, &lines, true);
+    let card_columns = match options.format {
+        SourceFormat::Auto => assembled_format(&mix_inst, &formatted, options),
+        format => format,
+    };
+    if card_columns != SourceFormat::Fixed {
+        formatted = layout(source, &lines, false);
+    }
+
+    let unchanged = match assemble(&formatted, options) {
+        Ok(after) => to_object(&before, false) == to_object(&after, false),
+        Err(_) => false,
+    };
+    match unchanged {
+        true => Ok(formatted),
+        false => Err(Diagnostics {
+            items: vec![Diagnostic::new(
+                0,
+                "formatting would change the assembled program, the source is left as it is"
+                    .to_string(),
+            )],
+            ..Diagnostics::new(&options.name)
+        }),
+    }
+}
+
+/// Format the assembler sees, it's guessed from the lines left after INCLUDE and the macros
+fn assembled_format(mix_inst: &MixInstructions, source: &str, options: &Options) -> SourceFormat {
+    if options.format != SourceFormat::Auto {
+        return options.format;
+    }
+    let source_lines: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+    let (lines, _, _) = preprocessor::preprocess(mix_inst, &source_lines, options);
+    let (expanded, _) = macros::expand(mix_inst, &lines);
+    let assembled: Vec<String> = expanded
+        .into_iter()
+        .filter(|l| l.assembled)
+        .map(|l| l.text)
+        .collect();
+    lexer::detect_format(mix_inst, &assembled)
+}
+
+/// Fields of a line, None for comments, blank lines and lines the lexer can't split
+fn split(mix_inst: &MixInstructions, text: &str, format: SourceFormat) -> Option<Line> {
+    let fields = macros::fields(text)?;
+    let chars: Vec<char> = text.chars().collect();
+    let rest = |from: usize| {
+        chars
+            .iter()
+            .skip(from)
+            .collect::<String>()
+            .trim()
+            .to_string()
+    };
+
+    // a file name or an expression is taken as it is, ENDIF can start the line
+    let directive = |op: &String| DIRECTIVES.contains(&&op[..]);
+    if directive(&fields.op) || directive(&fields.label) {
+        let (loc, op, from) = match directive(&fields.op) {
+            true => (
+                fields.label,
+                fields.op.clone(),
+                fields.op_column + fields.op.chars().count(),
+            ),
+            false => (
+                String::new(),
+                fields.label.clone(),
+                fields.label.chars().count(),
+            ),
+        };
+        return Some(Line {
+            loc,
+            op,
+            address: rest(from),
+            comment: String::new(),
+            alf: false,
+        });
+    }
+    if fields.op == "MACRO" || fields.op == "ENDM" {
+        let (address, column) = fields.address;
+        let end = column + address.chars().count();
+        return Some(Line {
+            loc: fields.label,
+            op: fields.op,
+            address,
+            comment: rest(end),
+            alf: false,
+        });
+    }
+
+    let format = match format == SourceFormat::Fixed && lexer::fits_columns(mix_inst, text) {
+        true => SourceFormat::Fixed,
+        false => SourceFormat::Free,
+    };
+    let fields = lexer::line_fields(mix_inst, text, format).ok()?;
+    let (op, _) = fields.op;
+    let op = match lexer::is_operation(mix_inst, &op) {
+        true => op.to_uppercase(),
+        false => op,
+    };
+    let alf = op == "ALF";
+    let (address, column) = fields.address;
+    let mut end = column + address.chars().count();
+    if alf && chars.get(end) == Some(&'"') {
+        end += 1;
+    }
+
+    Some(Line {
+        loc: fields.loc.0,
+        op,
+        address,
+        comment: rest(end),
+        alf,
+    })
+}
+
+/// Formatted source, `card_columns` puts the ALF value in the columns 17-21
+fn layout(source: &str, lines: &[Option<Line>], card_columns: bool) -> String {
+    let fields = |line: &Line| match line.alf {
+        true if card_columns => format!("{:10} {:4} {:5}", line.loc, line.op, line.address),
+        true => format!("{:10} {} {:5}", line.loc, line.op, line.address),
+        false => format!("{:10} {:4} {}", line.loc, line.op, line.address),
+    };
+
+    // the comments start after the longest fields, an empty address stays empty
+    let comment_column = lines
+        .iter()
+        .flatten()
+        .filter(|line| !line.comment.is_empty())
+        .map(|line| fields(line).chars().count() + 2)
+        .max()
+        .unwrap_or(0);
+
+    let mut result = String::new();
+    for (text, line) in source.lines().zip(lines) {
+        let formatted = match line {
+            None => text.to_string(),
+            Some(line) if line.comment.is_empty() => fields(line),
+            Some(line) => format!("{:comment_column$}{}", fields(line), line.comment),
+        };
+        result.push_str(formatted.trim_end());
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn card_columns() {
+        let source = "* EXAMPLE\n\
+                      \n\
+                      PRINTER equ 18   unit\n\
+                      START   ioc 0(PRINTER)  skip to a new page\n\
+                      \tld1 =1-3=\n\
+                      TITLE alf \"FIRST\"\n\
+                      \tALF  IVE comment\n\
+                      \thlt\n\
+                      \tend START\n";
+
+        let formatted = format(source, &Options::new("p.mixal")).expect("formatted");
+        assert_eq!(
+            "* EXAMPLE\n\
+             \n\
+             PRINTER    EQU  18          unit\n\
+             START      IOC  0(PRINTER)  skip to a new page\n\
+             \x20          LD1  =1-3=\n\
+             TITLE      ALF  FIRST\n\
+             \x20          ALF   IVE        comment\n\
+             \x20          HLT\n\
+             \x20          END  START\n",
+            formatted
+        );
+        // formatting again changes nothing
+        assert_eq!(
+            formatted,
+            format(&formatted, &Options::new("p.mixal")).expect("formatted")
+        );
+    }
+
+    #[test]
+    fn free_columns() {
+        let source = " orig 3000\n\
+                      LONGLABEL1 ENTA 5 five\n\
+                      \tALF  IVE\n\
+                      SAME MACRO\n\
+                      \tnop  0\n\
+                      \tENDM\n\
+                      \tSAME\n\
+                      IFDEF DEBUG\n\
+                      \thlt\n\
+                      ENDIF\n\
+                      \tend  LONGLABEL1\n";
+
+        let options = Options {
+            format: SourceFormat::Free,
+            ..Options::new("p.mixal")
+        };
+        let formatted = format(source, &options).expect("formatted");
+        assert_eq!(
+            "           ORIG 3000\n\
+             LONGLABEL1 ENTA 5  five\n\
+             \x20          ALF  IVE\n\
+             SAME       MACRO\n\
+             \x20          NOP  0\n\
+             \x20          ENDM\n\
+             \x20          SAME\n\
+             \x20          IFDEF DEBUG\n\
+             \x20          HLT\n\
+             \x20          ENDIF\n\
+             \x20          END  LONGLABEL1\n",
+            formatted
+        );
+    }
+
+    #[test]
+    fn refused() {
+        let options = Options::new("p.mixal");
+        let errors = format(" LDA X\n HLT\n", &options).expect_err("undefined symbol");
+        assert!(errors.errors() > 0);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::parser::Assembly;
+use mix_core::word::SIGN;
+use mix_core::Bytes;
+
+/*
+ * Memory image as JSON, the words are sorted by address
+ *
+ * {
+ *   "start": 3000,
+ *   "words": [
+ *     {"address": 3000, "value": -2, "sign": "-", "bytes": [0, 0, 0, 0, 2]}
+ *   ]
+ * }
+ */
+
+pub fn to_json(program: &Assembly) -> String {
+    let mut words = program.words.to_vec();
+    words.sort_by_key(|(addr, _)| *addr);
+    // the same address is assembled twice, the last word wins
+    words.reverse();
+    words.dedup_by_key(|(addr, _)| *addr);
+    words.reverse();
+
+    let items: Vec<String> = words
+        .iter()
+        .map(|(addr, word)| {
+            let bytes: Vec<String> = (1..=5).map(|i| word.get_byte(i).to_string()).collect();
+            format!(
+                "    {{\"address\": {addr}, \"value\": {}, \"sign\": \"{}\", \"bytes\": [{}]}}",
+                word.get_signed_value(),
+                if word.get() & SIGN != 0 { '-' } else { '+' },
+                bytes.join(", ")
+            )
+        })
+        .collect();
+
+    let words = match items.is_empty() {
+        true => "[]".to_string(),
+        false => format!("[\n{}\n  ]", items.join(",\n")),
+    };
+    format!(
+        "{{\n  \"start\": {},\n  \"words\": {words}\n}}\n",
+        program.start
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Options};
+
+    #[test]
+    fn memory_image() {
+        let source = " ORIG 10\nSTART ENTA -0\n CON 1(1:1),2(5:5)\n END START\n";
+        let program = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        assert_eq!(
+            "{\n  \"start\": 10,\n  \"words\": [\n    \
+             {\"address\": 10, \"value\": -176, \"sign\": \"-\", \"bytes\": [0, 0, 0, 2, 48]},\n    \
+             {\"address\": 11, \"value\": 16777218, \"sign\": \"+\", \"bytes\": [1, 0, 0, 0, 2]}\n  \
+             ]\n}\n",
+            to_json(&program)
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use crate::new_if_presudo_op;
+use crate::diagnostic::Diagnostic;
+use crate::pseudo_op::new_if_presudo_op;
+use crate::tags::MixInstructions;
 use crate::tags::Tag;
-use crate::MixInstructions;
+use mix_core::word::MAX_5_BYTES;
 
 use crate::lexer::token::*;
 
@@ -15,10 +17,24 @@ pub struct ProgramLine<'a> {
     pub loc: Token,
     pub op: OpToken<'a>,
     pub addr: Vec<Token>,
+    pub line_num: usize, // 1-based source line, 0 for generated lines
+    pub op_column: usize,
+    pub addr_column: usize,
 }
 impl<'a> ProgramLine<'a> {
     pub fn new(loc: Token, op: OpToken<'a>, addr: Vec<Token>) -> ProgramLine<'a> {
-        ProgramLine { loc, op, addr }
+        ProgramLine {
+            loc,
+            op,
+            addr,
+            line_num: 0,
+            op_column: 0,
+            addr_column: 0,
+        }
+    }
+    pub fn with_line_num(mut self, line_num: usize) -> ProgramLine<'a> {
+        self.line_num = line_num;
+        self
     }
 }
 impl fmt::Debug for ProgramLine<'_> {
@@ -37,146 +53,374 @@ impl Clone for ProgramLine<'_> {
             loc: self.loc.clone(),
             op: self.op.clone(),
             addr: self.addr.to_vec(),
+            line_num: self.line_num,
+            op_column: self.op_column,
+            addr_column: self.addr_column,
         }
     }
 }
 
-pub struct Lexer {}
+/// Layout of the source lines
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SourceFormat {
+    /// fixed if every line fits the card columns, free otherwise
+    Auto,
+    /// LOC, OP and ADDRESS separated by spaces or tabs, a line without LOC starts with
+    /// a blank, ALF takes 5 characters after one blank or a "quoted" value
+    Free,
+    /// card columns of Knuth: LOC 1-10, OP 12-15, ADDRESS from 16, ALF 17-21
+    Fixed,
+}
+
+/// Fields of a source line with their columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fields {
+    pub loc: (String, usize),
+    pub op: (String, usize),
+    pub address: (String, usize),
+}
+
+pub struct Lexer {
+    format: SourceFormat,
+}
 impl Lexer {
     pub fn new() -> Lexer {
-        Lexer {}
+        Lexer::with_format(SourceFormat::Auto)
+    }
+
+    pub fn with_format(format: SourceFormat) -> Lexer {
+        Lexer { format }
     }
 
+    /// Splits source lines into tokens, problems of all lines are reported together.
+    /// A wrong line is kept as NOP with its label, so the next lines get their
+    /// addresses and labels and don't cause more errors
     pub fn parse_program_lines<'a>(
         &'a self,
         mix_inst: &'a MixInstructions,
         lines: Vec<String>,
-    ) -> Vec<ProgramLine> {
+    ) -> (Vec<ProgramLine<'a>>, Vec<Diagnostic>) {
         let mut result = Vec::new();
+        let mut diagnostics = Vec::new();
 
-        for line in lines {
-            if line.starts_with("*") {
+        let format = match self.format {
+            SourceFormat::Auto => detect_format(mix_inst, &lines),
+            format => format,
+        };
+
+        for (i, line) in lines.into_iter().enumerate() {
+            if line.trim().is_empty() || line.starts_with("*") {
                 continue;
             }
 
-            let (loc, line) = line.split_once(" ").expect("wrong line format");
+            match self.parse_program_line(mix_inst, &line, format) {
+                Ok(pr_line) => result.push(pr_line.with_line_num(i + 1)),
+                Err(diagnostic) => {
+                    diagnostics.push(diagnostic.with_line(i + 1));
 
-            let (op, line) = split_whitespace_once(line);
+                    let loc: String = line.chars().take_while(|c| !is_blank(*c)).collect();
+                    let nop = ProgramLine::new(
+                        Token::new_symbols(loc),
+                        OpToken::new_mix_op(mix_inst.get("NOP")),
+                        Vec::new(),
+                    );
+                    result.push(nop.with_line_num(i + 1));
+                }
+            }
+        }
 
-            let mut op_token;
+        (result, diagnostics)
+    }
 
-            let mut address = String::new();
-            let pseudo_op = new_if_presudo_op(op, line);
-            if pseudo_op.is_none() {
-                let (left, _) = split_whitespace_once(line);
-                address = left.to_string();
-                let mix_op = mix_inst.get(op);
-                op_token = OpToken::new_mix_op(mix_op);
-            } else {
-                let pseudo_op = pseudo_op.expect("err");
-                address = pseudo_op.parse_address();
+    fn parse_program_line<'a>(
+        &'a self,
+        mix_inst: &'a MixInstructions,
+        line: &str,
+        format: SourceFormat,
+    ) -> Result<ProgramLine<'a>, Diagnostic> {
+        let fields = line_fields(mix_inst, line, format)?;
+        let (loc, loc_column) = fields.loc;
+        let (op, op_column) = fields.op;
+        // mnemonics are accepted in any case
+        let op = match is_operation(mix_inst, &op) {
+            true => op.to_uppercase(),
+            false => op,
+        };
+        let (address, addr_column) = fields.address;
+
+        let op_token;
+        let tokens;
+        match new_if_presudo_op(&op, &address) {
+            None => {
+                if !mix_inst.is_instruction(&op) {
+                    return Err(Diagnostic::new(0, format!("unknown operation '{op}'"))
+                        .with_columns(op_column..op_column + op.chars().count()));
+                }
+                tokens = self.parse_address(&address, addr_column)?;
+                op_token = OpToken::new_mix_op(mix_inst.get(&op));
+            }
+            Some(pseudo_op) => {
+                // ALF characters are taken as they are
+                tokens = match &pseudo_op.get_name()[..] {
+                    "ALF" => Vec::new(),
+                    _ => self.parse_address(&pseudo_op.parse_address(), addr_column)?,
+                };
                 op_token = OpToken::new_mixal_op(pseudo_op);
             }
-
-            let pr_line = ProgramLine::new(
-                Token::new_symbols(loc.to_string()),
-                op_token,
-                self.parse_address(address),
-            );
-            println!("{:#?}", &pr_line);
-            result.push(pr_line);
         }
 
-        result
+        let loc_columns = loc_column..loc_column + loc.chars().count();
+        let loc = Token::new_symbols(loc).with_columns(loc_columns);
+        let mut program_line = ProgramLine::new(loc, op_token, tokens);
+        program_line.op_column = op_column;
+        program_line.addr_column = addr_column;
+        Ok(program_line)
     }
 
-    fn parse_address(&self, address: String) -> Vec<Token> {
+    /// Tokens of the address field, `start` is its column in the source line
+    pub fn parse_address(&self, address: &str, start: usize) -> Result<Vec<Token>, Diagnostic> {
         let mut result = Vec::new();
-        let mut chars = address.chars();
-
-        let mut ch = chars.next();
-        loop {
-            match ch {
-                None => break,
-                Some(c) => match c {
-                    '-' => {
-                        result.push(Token::new(Tag::MINUS, c.to_string()));
-                    }
-                    '+' => {
-                        result.push(Token::new(Tag::PLUS, c.to_string()));
-                    }
-                    '*' => {
-                        result.push(Token::new(Tag::MULTIPLY, c.to_string()));
-                    }
-                    ':' => {
-                        result.push(Token::new(Tag::F_OP, c.to_string()));
-                    }
-                    ',' => {
-                        result.push(Token::new(Tag::COMMA, c.to_string()));
-                    }
-                    '=' => {
-                        result.push(Token::new(Tag::EQUAL, c.to_string()));
-                    }
-                    '(' => {
-                        result.push(Token::new(Tag::OPEN_BR, c.to_string()));
-                    }
-                    ')' => {
-                        result.push(Token::new(Tag::CLOSE_BR, c.to_string()));
-                    }
-                    '/' => {
-                        ch = chars.next();
-                        if ch == Some('/') {
-                            result.push(Token::new(Tag::MOD, "//".to_string()));
-                        } else {
-                            result.push(Token::new(Tag::DEVIDE, c.to_string()));
-                        }
-                        continue;
-                    }
-                    _ => {
-                        let mut c = c;
-                        let mut is_number = true;
-                        let mut symbols = String::new();
-
-                        loop {
-                            if (!c.is_alphanumeric() && c != ' ') {
-                                break;
-                            }
-                            if (!c.is_numeric()) {
-                                is_number = false;
-                            }
-
-                            symbols += &c.to_string()[..];
-
-                            ch = chars.next();
-                            if ch == None {
-                                break;
-                            }
-                            c = ch.expect("error");
-                        }
-                        if !symbols.is_empty() {
-                            if is_number {
-                                let num = i32::from_str_radix(&symbols[..], 10)
-                                    .expect("error parse number");
-                                result.push(Token::new_number(num));
-                            } else {
-                                result.push(Token::new_symbols(symbols));
-                            }
-                            continue;
-                        }
-                    }
-                },
+        let chars: Vec<char> = address.chars().collect();
+
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let tag = match c {
+                '-' => Some(Tag::MINUS),
+                '+' => Some(Tag::PLUS),
+                '*' => Some(Tag::MULTIPLY),
+                ':' => Some(Tag::F_OP),
+                ',' => Some(Tag::COMMA),
+                '=' => Some(Tag::EQUAL),
+                '(' => Some(Tag::OPEN_BR),
+                ')' => Some(Tag::CLOSE_BR),
+                _ => None,
             };
-            ch = chars.next();
-            // println!("{}", c);
+            if let Some(tag) = tag {
+                result.push(Token::new(tag, c.to_string()).with_columns(start + i..start + i + 1));
+                i += 1;
+                continue;
+            }
+
+            if c == '/' {
+                if chars.get(i + 1) == Some(&'/') {
+                    result.push(
+                        Token::new(Tag::MOD, "//".to_string()).with_columns(start + i..start + i + 2),
+                    );
+                    i += 2;
+                } else {
+                    result.push(
+                        Token::new(Tag::DEVIDE, c.to_string()).with_columns(start + i..start + i + 1),
+                    );
+                    i += 1;
+                }
+                continue;
+            }
+
+            let first = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == ' ') {
+                i += 1;
+            }
+            let columns = start + first..start + i;
+            if first == i {
+                return Err(Diagnostic::new(0, format!("unexpected character '{c}'"))
+                    .with_columns(columns.start..columns.start + 1));
+            }
+
+            let symbols: String = chars[first..i].iter().collect();
+            if symbols.chars().all(|c| c.is_numeric()) {
+                let num = i32::from_str_radix(&symbols[..], 10)
+                    .ok()
+                    .filter(|n| *n <= MAX_5_BYTES)
+                    .ok_or_else(|| {
+                        Diagnostic::new(0, format!("number {symbols} is too large"))
+                            .with_columns(columns.clone())
+                    })?;
+                result.push(Token::new_number(num).with_columns(columns));
+            } else {
+                result.push(Token::new_symbols(symbols).with_columns(columns));
+            }
         }
 
-        result
+        Ok(result)
+    }
+}
+
+pub fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+/// Instruction or pseudo-operation, in any case
+pub fn is_operation(mix_inst: &MixInstructions, name: &str) -> bool {
+    let name = name.to_uppercase();
+    mix_inst.is_instruction(&name) || new_if_presudo_op(&name, "").is_some()
+}
+
+/// Start and end of the next word from `from`
+pub fn next_word(chars: &[char], from: usize) -> Option<(usize, usize)> {
+    let start = (from..chars.len()).find(|i| !is_blank(chars[*i]))?;
+    let end = (start..chars.len())
+        .find(|i| is_blank(chars[*i]))
+        .unwrap_or(chars.len());
+    Some((start, end))
+}
+
+/// LOC, OP and ADDRESS of a line that is not blank or a comment,
+/// `format` is Fixed or Free
+pub fn line_fields(
+    mix_inst: &MixInstructions,
+    line: &str,
+    format: SourceFormat,
+) -> Result<Fields, Diagnostic> {
+    match format {
+        SourceFormat::Fixed => fixed_fields(line),
+        _ => free_fields(mix_inst, line),
     }
 }
 
+/// Fixed when every line has an operation in the columns 12-15
+pub fn detect_format(mix_inst: &MixInstructions, lines: &[String]) -> SourceFormat {
+    let mut lines = lines
+        .iter()
+        .filter(|l| !l.trim().is_empty() && !l.starts_with('*'))
+        .peekable();
+    if lines.peek().is_none() {
+        return SourceFormat::Free;
+    }
+
+    match lines.all(|line| fits_columns(mix_inst, line)) {
+        true => SourceFormat::Fixed,
+        false => SourceFormat::Free,
+    }
+}
+
+/// The line has an operation in the columns 12-15 and a blank around it
+pub fn fits_columns(mix_inst: &MixInstructions, line: &str) -> bool {
+    let chars: Vec<char> = line.chars().collect();
+    let op: String = chars.iter().skip(11).take(4).collect();
+    !line.contains('\t')
+        && chars.len() > 11
+        && chars[10] == ' '
+        && chars[11] != ' '
+        && chars.get(15).is_none_or(|c| *c == ' ')
+        && is_operation(mix_inst, op.trim_end())
+}
+
+fn fixed_fields(line: &str) -> Result<Fields, Diagnostic> {
+    let mut chars: Vec<char> = line.chars().collect();
+    let end = chars.len();
+    if let Some(i) = chars.iter().position(|c| *c == '\t') {
+        return Err(
+            Diagnostic::new(0, "tab in a fixed-column line".to_string()).with_columns(i..i + 1)
+        );
+    }
+    if chars.len() < 21 {
+        chars.resize(21, ' ');
+    }
+
+    let field = |from: usize, to: usize| -> String { chars[from..to].iter().collect() };
+    if chars[10] != ' ' {
+        return Err(
+            Diagnostic::new(0, "column 11 should be blank".to_string()).with_columns(10..11)
+        );
+    }
+
+    let loc = field(0, 10).trim_end().to_string();
+    let op = field(11, 15).trim_end().to_string();
+    if op.is_empty() || op.starts_with(' ') {
+        return Err(
+            Diagnostic::new(0, "operation should be in the columns 12-15".to_string())
+                .with_columns(11.min(end)..15.min(end).max(12)),
+        );
+    }
+
+    let address = match op.eq_ignore_ascii_case("ALF") {
+        true => (field(16, 21), 16),
+        false => match next_word(&chars, 15) {
+            Some((start, end)) if start == 15 || start == 16 => (field(start, end), start),
+            _ => (String::new(), 15.min(end)),
+        },
+    };
+
+    Ok(Fields {
+        loc: (loc, 0),
+        op: (op, 11),
+        address,
+    })
+}
+
+fn free_fields(mix_inst: &MixInstructions, line: &str) -> Result<Fields, Diagnostic> {
+    let chars: Vec<char> = line.chars().collect();
+    let word = |(start, end): (usize, usize)| -> (String, usize) {
+        (chars[start..end].iter().collect(), start)
+    };
+
+    let first = next_word(&chars, 0).expect("not empty line");
+    let second = next_word(&chars, first.1);
+
+    // a line without LOC starts with a blank, an operation alone or before an address
+    // is accepted at the start of the line too
+    let has_loc = first.0 == 0
+        && match second {
+            None => !is_operation(mix_inst, &word(first).0),
+            Some(second) => {
+                !is_operation(mix_inst, &word(first).0) || is_operation(mix_inst, &word(second).0)
+            }
+        };
+    let (loc, op) = match (has_loc, second) {
+        (false, _) => ((String::new(), 0), first),
+        (true, Some(second)) => (word(first), second),
+        (true, None) => {
+            let end = chars.len();
+            return Err(Diagnostic::new(
+                0,
+                format!("operation is missing after '{}'", word(first).0),
+            )
+            .with_columns(end..end + 1));
+        }
+    };
+
+    let address = match word(op).0.eq_ignore_ascii_case("ALF") {
+        true => alf_value(&chars, op.1)?,
+        false => match next_word(&chars, op.1) {
+            Some(address) => word(address),
+            None => (String::new(), chars.len()),
+        },
+    };
+
+    Ok(Fields {
+        loc,
+        op: word(op),
+        address,
+    })
+}
+
+/// 5 characters after one blank, or a value in quotes
+fn alf_value(chars: &[char], op_end: usize) -> Result<(String, usize), Diagnostic> {
+    let start = op_end + 1;
+    if chars.get(start) == Some(&'"') {
+        let close = (start + 1..chars.len())
+            .find(|i| chars[*i] == '"')
+            .ok_or_else(|| {
+                Diagnostic::new(0, "'\"' is missing at the end of the ALF value".to_string())
+                    .with_columns(start..chars.len())
+            })?;
+        if close - start - 1 > 5 {
+            return Err(
+                Diagnostic::new(0, "ALF value is longer than 5 characters".to_string())
+                    .with_columns(start..close + 1),
+            );
+        }
+        return Ok((chars[start + 1..close].iter().collect(), start + 1));
+    }
+
+    let value: String = chars.iter().skip(start).take(5).collect();
+    Ok((value, start.min(chars.len())))
+}
+
 pub fn split_whitespace_once(line: &str) -> (&str, &str) {
     return match line.split_once(" ") {
-        None => (line, ""),
+        None => (line, &line[line.len()..]),
         Some((op, line)) => (op, line),
     };
 }
@@ -190,7 +434,9 @@ mod tests {
         let mix_inst = MixInstructions::new();
         let lexer = Lexer::new();
         let sourse = read_programm("./programs/print_500_primes.mixal");
-        lexer.parse_program_lines(&mix_inst, sourse);
+        let (lines, errors) = lexer.parse_program_lines(&mix_inst, sourse);
+        assert!(errors.is_empty());
+        assert_eq!(Some(3), lines.first().map(|l| l.line_num));
     }
 
     #[test]
@@ -198,7 +444,141 @@ mod tests {
         let mix_inst = MixInstructions::new();
         let lexer = Lexer::new();
         let sourse = read_programm("./programs/mystery_program.mixal");
-        lexer.parse_program_lines(&mix_inst, sourse);
+        let (_, errors) = lexer.parse_program_lines(&mix_inst, sourse);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn lexer_errors() {
+        let mix_inst = MixInstructions::new();
+        let lexer = Lexer::new();
+        let sourse = vec![
+            " LDA 1".to_string(),
+            "START".to_string(),
+            " LDB 1".to_string(),
+            " LDA 99999999999".to_string(),
+            " LDA 1#".to_string(),
+        ];
+
+        let (lines, errors) = lexer.parse_program_lines(&mix_inst, sourse);
+        assert_eq!(5, lines.len());
+        assert_eq!(
+            vec![
+                Diagnostic::new(2, "operation is missing after 'START'".to_string())
+                    .with_columns(5..6),
+                Diagnostic::new(3, "unknown operation 'LDB'".to_string()).with_columns(1..4),
+                Diagnostic::new(4, "number 99999999999 is too large".to_string())
+                    .with_columns(5..16),
+                Diagnostic::new(5, "unexpected character '#'".to_string()).with_columns(6..7),
+            ],
+            errors
+        );
+    }
+
+    fn fields(line: &ProgramLine) -> (String, String, String) {
+        let address = match line.op.get_tag() {
+            Tag::MIXAL_OP if line.op.get_mixal_op().get_name() == "ALF" => {
+                line.op.get_mixal_op().parse_address()
+            }
+            _ => line.addr.iter().map(|t| t.to_text()).collect(),
+        };
+        (line.loc.get_symbols(), format!("{:?}", line.op), address)
+    }
+
+    #[test]
+    fn lexer_free_format() {
+        let mix_inst = MixInstructions::new();
+        let lexer = Lexer::with_format(SourceFormat::Free);
+        let sourse = vec![
+            "START\tLDA\t1000,1\tcomment".to_string(),
+            "HLT".to_string(),
+            "ENTA 5".to_string(),
+            "\tALF  FIVE".to_string(),
+            " ALF \"A B\" quoted".to_string(),
+            "X ALF AB".to_string(),
+        ];
+
+        let (lines, errors) = lexer.parse_program_lines(&mix_inst, sourse);
+        assert_eq!(Vec::<Diagnostic>::new(), errors);
+        let fields: Vec<_> = lines.iter().map(fields).collect();
+        let expected = [
+            ("START", "|LDA|", "1000,1"),
+            ("", "|HLT|", ""),
+            ("", "|ENTA|", "5"),
+            ("", "|ALF|", " FIVE"),
+            ("", "|ALF|", "A B  "),
+            ("X", "|ALF|", "AB   "),
+        ];
+        for (expected, actual) in expected.iter().zip(&fields) {
+            assert_eq!(
+                (
+                    expected.0.to_string(),
+                    expected.1.to_string(),
+                    expected.2.to_string()
+                ),
+                *actual
+            );
+        }
+        assert_eq!(
+            10..16,
+            lines[0].addr[0].get_columns().start..lines[0].addr[2].get_columns().end
+        );
+    }
+
+    #[test]
+    fn lexer_fixed_format() {
+        let mix_inst = MixInstructions::new();
+        let card = |loc: &str, op: &str, address: &str| format!("{loc:10} {op:4} {address}");
+        let sourse = vec![
+            "* EXAMPLE PROGRAM".to_string(),
+            card("PRINTER", "EQU", "18 unit"),
+            card("START", "IOC", "0(PRINTER)"),
+            card("", "LD1", "=1-L="),
+            card("", "HLT", ""),
+            card("TITLE", "ALF", " FIVE"),
+            card("", "END", "START"),
+        ];
+
+        // the format is detected
+        let lexer = Lexer::new();
+        let (lines, errors) = lexer.parse_program_lines(&mix_inst, sourse);
+        assert_eq!(Vec::<Diagnostic>::new(), errors);
+        let fields: Vec<_> = lines.iter().map(fields).collect();
+        let expected = [
+            ("PRINTER", "|EQU|", "18"),
+            ("START", "|IOC|", "0(PRINTER)"),
+            ("", "|LD1|", "=1-L="),
+            ("", "|HLT|", ""),
+            ("TITLE", "|ALF|", " FIVE"),
+            ("", "|END|", "START"),
+        ];
+        for (expected, actual) in expected.iter().zip(&fields) {
+            assert_eq!(
+                (
+                    expected.0.to_string(),
+                    expected.1.to_string(),
+                    expected.2.to_string()
+                ),
+                *actual
+            );
+        }
+        assert_eq!(16..17, lines[1].addr[0].get_columns());
+
+        let sourse = vec![
+            card("", "LDA", "1"),
+            " LDA 1".to_string(),
+            "START\tHLT".to_string(),
+        ];
+        let lexer = Lexer::with_format(SourceFormat::Fixed);
+        let (_, errors) = lexer.parse_program_lines(&mix_inst, sourse);
+        assert_eq!(
+            vec![
+                Diagnostic::new(2, "operation should be in the columns 12-15".to_string())
+                    .with_columns(6..12),
+                Diagnostic::new(3, "tab in a fixed-column line".to_string()).with_columns(5..6),
+            ],
+            errors
+        );
     }
 
     fn read_programm(path: &str) -> Vec<String> {
@@ -210,7 +590,7 @@ mod tests {
         for line in lines {
             let line = line.expect("some err in lines");
 
-            if line.len() <= 0 {
+            if line.is_empty() {
                 continue;
             }
             result.push(line.to_string());
@@ -1,15 +1,17 @@
 // use crate::new_if_presudo_op;
 use crate::pseudo_op::*;
 use crate::tags::Tag;
-use crate::MixInstruction;
+use crate::tags::MixInstruction;
 
 use std::fmt;
+use std::ops::Range;
 
 pub struct Token {
     tag: Tag,
 
     num_value: Option<i32>,
     symbols_value: Option<String>,
+    columns: Range<usize>, // position in the source line, for messages
 }
 impl Token {
     pub fn new(tag: Tag, value: String) -> Token {
@@ -17,6 +19,7 @@ impl Token {
             tag: tag,
             num_value: None,
             symbols_value: Some(value),
+            columns: 0..0,
         }
     }
     pub fn new_number(value: i32) -> Token {
@@ -24,6 +27,7 @@ impl Token {
             tag: Tag::NUMBER,
             num_value: Some(value),
             symbols_value: None,
+            columns: 0..0,
         }
     }
     pub fn new_symbols(value: String) -> Token {
@@ -37,17 +41,32 @@ impl Token {
             tag: tag,
             num_value: None,
             symbols_value: Some(value),
+            columns: 0..0,
         }
     }
+    pub fn with_columns(mut self, columns: Range<usize>) -> Token {
+        self.columns = columns;
+        self
+    }
     pub fn get_tag(&self) -> Tag {
         self.tag
     }
+    pub fn get_columns(&self) -> Range<usize> {
+        self.columns.clone()
+    }
     pub fn get_number(&self) -> i32 {
         return match self.num_value {
             Some(x) => x,
             _ => panic!("token doesn't have num value"),
         };
     }
+    /// Source text of the token, for messages
+    pub fn to_text(&self) -> String {
+        match self.num_value {
+            Some(x) => x.to_string(),
+            None => self.get_symbols(),
+        }
+    }
     pub fn get_symbols(&self) -> String {
         return match &self.symbols_value {
             Some(x) => String::from(&x[..]),
@@ -75,6 +94,7 @@ impl Clone for Token {
             tag: self.tag,
             num_value: self.num_value,
             symbols_value: self.symbols_value.clone(),
+            columns: self.columns.clone(),
         }
     }
 }
@@ -0,0 +1,843 @@
+//! MIXAL assembler from "The Art of Computer Programming" by Donald Knuth
+//!
+//! ```
+//! use mixal::{assemble, Options};
+//!
+//! let source = " ORIG 3000\nSTART ENTA 5\n HLT\n END START\n";
+//! let assembly = assemble(source, &Options::new("program.mixal")).expect("valid program");
+//!
+//! assert_eq!(3000, assembly.start);
+//! assert_eq!(2, assembly.words.len());
+//! ```
+
+use crate::diagnostic::Diagnostics;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::preprocessor::SourceLine;
+use crate::tags::MixInstructions;
+use mix_core::Bytes;
+
+pub mod deck;
+pub mod diagnostic;
+pub mod formatter;
+pub mod image;
+pub mod lexer;
+pub mod linker;
+pub mod lsp;
+pub mod macros;
+pub mod object;
+pub mod parser;
+pub mod preprocessor;
+pub mod pseudo_op;
+pub mod tags;
+pub mod xref;
+
+pub use crate::diagnostic::Diagnostic;
+pub use crate::diagnostic::Severity;
+pub use crate::lexer::SourceFormat;
+pub use crate::parser::symbol_table::Symbol;
+pub use crate::parser::symbol_table::SymbolKind;
+pub use mix_core::word::Word;
+pub use crate::parser::Assembly;
+
+/// Assembler settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// source name used in diagnostics, usually the file path,
+    /// INCLUDE looks for files next to it first
+    pub name: String,
+    pub format: SourceFormat,
+    /// directories for INCLUDE
+    pub include_paths: Vec<String>,
+    /// symbols for IF and IFDEF
+    pub defines: Vec<(String, i32)>,
+    /// relocatable module for the linker, its addresses start at 0
+    pub relocatable: bool,
+}
+
+impl Options {
+    pub fn new(name: &str) -> Options {
+        Options {
+            name: name.to_string(),
+            format: SourceFormat::Auto,
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            relocatable: false,
+        }
+    }
+}
+
+/// Assembles MIXAL source into a memory image, all problems are reported together
+pub fn assemble(source: &str, options: &Options) -> Result<Assembly, Diagnostics> {
+    let source_lines: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+
+    let mix_inst = MixInstructions::new();
+    let lexer = Lexer::with_format(options.format);
+    let (lines, included, mut diagnostics) =
+        preprocessor::preprocess(&mix_inst, &source_lines, options);
+    let to_diagnostics = |items| Diagnostics {
+        items,
+        ..with_included(
+            Diagnostics::new(&options.name).with_source(source),
+            &included,
+        )
+    };
+
+    // the lines are numbered in the expanded program until the assembly is done
+    let (expanded, macro_diagnostics) = macros::expand(&mix_inst, &lines);
+    diagnostics.extend(macro_diagnostics);
+    let lines: Vec<String> = expanded
+        .iter()
+        .map(|l| if l.assembled { l.text.clone() } else { String::new() })
+        .collect();
+    let to_source = |d: Diagnostic| to_source_line(&expanded, d);
+
+    let (tokens, lexer_diagnostics) = lexer.parse_program_lines(&mix_inst, lines);
+    diagnostics.extend(lexer_diagnostics.into_iter().map(to_source));
+    let parser = match options.relocatable {
+        true => Parser::relocatable(),
+        false => Parser::new(),
+    }
+    .with_locations(expanded.iter().map(location).collect());
+    let mut assembly = match parser.parse(tokens) {
+        Ok(assembly) if diagnostics.is_empty() => assembly,
+        Ok(assembly) => {
+            diagnostics.extend(assembly.warnings.into_iter().map(to_source));
+            sort(&mut diagnostics);
+            return Err(to_diagnostics(diagnostics));
+        }
+        Err(more) => {
+            diagnostics.extend(more.into_iter().map(to_source));
+            sort(&mut diagnostics);
+            return Err(to_diagnostics(diagnostics));
+        }
+    };
+
+    assembly.listing = listing(&expanded, &assembly);
+    assembly.warnings = assembly.warnings.into_iter().map(to_source).collect();
+    let source_line = |line: usize| match line {
+        0 => 0,
+        line => expanded[line - 1].main_line,
+    };
+    for (_, line) in assembly.line_nums.iter_mut() {
+        *line = source_line(*line);
+    }
+    for symbol in assembly.symbols.iter_mut() {
+        symbol.line = source_line(symbol.line);
+        symbol.uses = symbol.uses.iter().map(|l| source_line(*l)).collect();
+        symbol.uses.dedup();
+    }
+    assembly.included = included;
+    Ok(assembly)
+}
+
+/// Diagnostics with the sources of the included files for the snippets
+pub fn with_included(diagnostics: Diagnostics, included: &[(String, String)]) -> Diagnostics {
+    included.iter().fold(diagnostics, |d, (name, source)| {
+        d.with_included(name, source)
+    })
+}
+
+/// Problems of the main source first, then by file, all by line
+fn sort(diagnostics: &mut [Diagnostic]) {
+    diagnostics.sort_by(|a, b| a.file.cmp(&b.file).then(a.line.cmp(&b.line)));
+}
+
+/// Where an expanded line is, `line 6` in the main source or `inc/lib.mixal:2`
+fn location(line: &SourceLine) -> String {
+    match &line.file {
+        None => format!("line {}", line.line),
+        Some(file) => format!("{file}:{}", line.line),
+    }
+}
+
+/// Diagnostic of an expanded line moved to its source line, the columns are
+/// dropped for lines of macros, they are not in the source
+fn to_source_line(expanded: &[SourceLine], d: Diagnostic) -> Diagnostic {
+    if d.line == 0 {
+        return d;
+    }
+    let line = &expanded[d.line - 1];
+    match &line.macro_name {
+        None => d.with_line(line.line).in_file(line.file.clone()),
+        Some(name) => Diagnostic {
+            file: line.file.clone(),
+            line: line.line,
+            columns: None,
+            message: format!("{} (in macro {name})", d.message),
+            ..d
+        },
+    }
+}
+
+/// Source lines with the location and the assembled word as `+ AA I F C`,
+/// expanded lines of macros are marked with `+` instead of the line number,
+/// words without a source line (literals) are at the end
+fn listing(source_lines: &[SourceLine], assembly: &Assembly) -> Vec<String> {
+    let word_of = |addr: u32| {
+        assembly
+            .words
+            .iter()
+            .find(|(a, _)| *a == addr)
+            .map(|(_, w)| *w)
+            .unwrap_or(Word::new(0))
+    };
+
+    let mut result = Vec::new();
+    for (i, source) in source_lines.iter().enumerate() {
+        let line_num = i + 1;
+        let number = match source.macro_name {
+            None => source.line.to_string(),
+            Some(_) => "+".to_string(),
+        };
+        match assembly.line_nums.iter().find(|(_, l)| *l == line_num) {
+            Some((addr, _)) => result.push(format!(
+                "{:04}: {} {:>5} {}",
+                addr,
+                knuth_word(&word_of(*addr)),
+                number,
+                source.text
+            )),
+            None => result.push(format!("{:21} {:>5} {}", "", number, source.text)),
+        }
+    }
+
+    for (addr, word) in &assembly.words {
+        if !assembly.line_nums.iter().any(|(a, _)| a == addr) {
+            result.push(format!("{:04}: {}", addr, knuth_word(word)));
+        }
+    }
+    result
+}
+
+/// Word as in the listings of Knuth: sign, address, index, field and operation code
+fn knuth_word(word: &Word) -> String {
+    let sign = if word.get_sign() < 0 { '-' } else { '+' };
+    let aa = ((word.get_byte(1) as u32) << 6) | word.get_byte(2) as u32;
+    format!(
+        "{} {:04} {:02} {:02} {:02}",
+        sign,
+        aa,
+        word.get_byte(3),
+        word.get_byte(4),
+        word.get_byte(5)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mix_core::word_access::WordAccess;
+    use mix_core::Instruction;
+
+    #[test]
+    fn assemble_program() {
+        let source = std::fs::read_to_string("./programs/print_500_primes.mixal")
+            .expect("file not found");
+        let assembly = assemble(&source, &Options::new("primes")).expect("valid program");
+
+        assert_eq!(3000, assembly.start);
+        assert!(assembly
+            .symbols
+            .iter()
+            .any(|s| s.name == "BUF0" && s.kind == SymbolKind::Equ && s.value == 2000));
+        assert_eq!(source.lines().count() + 2, assembly.listing.len());
+        assert_eq!(
+            "                          1 * EXAMPLE...",
+            assembly.listing[0]
+        );
+        assert_eq!(
+            "3000: + 0000 00 18 35     9 START IOC 0(PRINTER) Skip to new page",
+            assembly.listing[8]
+        );
+        assert_eq!(
+            "3001: + 2050 00 05 09    10  LD1 =1-L=",
+            assembly.listing[9]
+        );
+        // the literal pool
+        assert_eq!("2050: - 0000 00 07 51", assembly.listing[assembly.listing.len() - 2]);
+        assert_eq!("2051: + 0000 00 00 03", assembly.listing[assembly.listing.len() - 1]);
+    }
+
+    #[test]
+    fn assemble_mystery_program() {
+        let source = std::fs::read_to_string("./programs/mystery_program.mixal")
+            .expect("file not found");
+        let assembly = assemble(&source, &Options::new("mystery")).expect("valid program");
+
+        assert_eq!(3000, assembly.start);
+        assert_eq!(
+            "3013: + 2400 00 03 50    16  ENN2 2400",
+            assembly.listing[15]
+        );
+        assert_eq!(
+            "3014: + 2400 02 18 37    17  OUT BUF+2400,2(18)",
+            assembly.listing[16]
+        );
+    }
+
+    #[test]
+    fn knuth_listing() {
+        let source = " ORIG 3000\n\
+                      START LDA =5=\n\
+                      \x20ADD =-1=\n\
+                      \x20ENTA -0\n\
+                      X CON 7\n\
+                      \x20HLT\n\
+                      \x20END START\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        // location, sign, AA I F C, the line number and the source,
+        // the literal pool follows END without a source line
+        assert_eq!(
+            vec![
+                "                          1  ORIG 3000",
+                "3000: + 3005 00 05 08     2 START LDA =5=",
+                "3001: + 3006 00 05 01     3  ADD =-1=",
+                "3002: - 0000 00 02 48     4  ENTA -0",
+                "3003: + 0000 00 00 07     5 X CON 7",
+                "3004: + 0000 00 02 05     6  HLT",
+                "                          7  END START",
+                "3005: + 0000 00 00 05",
+                "3006: - 0000 00 00 01",
+            ],
+            assembly.listing
+        );
+    }
+
+    #[test]
+    fn subroutine_linkage() {
+        // STJ puts rJ into the address of the JMP only, its default field is (0:2)
+        let source = " ORIG 3000\nSUB STJ EXIT\nEXIT JMP *\nSTART JMP SUB\n HLT\n END START\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        let stj = mix_core::instruction::Instruction::Stj {
+            addr: 3001,
+            minus_zero: false,
+            index: 0,
+            field: 2,
+        };
+        assert_eq!(Ok(assembly.words[0].1), stj.encode());
+    }
+
+    #[test]
+    fn minus_zero() {
+        // the text format writes the sign with the bytes, "100, 0" would load +0
+        let source = " ORIG 100\n ENTA -0\n CON -0\n LDA =-0=\n HLT\n END 100\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        assert_eq!(
+            vec![
+                "100, -1, 0, 0, 0, 2, 48",
+                "101, -1, 0, 0, 0, 0, 0",
+                "102, 104,0,5,8",
+                "103, 0,0,2,5",
+                "104, -1, 0, 0, 0, 0, 0",
+                "100",
+            ],
+            assembly.lines
+        );
+        assert_eq!(Word::new_by_bytes(-1, &[0, 0, 0, 2, 48]), assembly.words[0].1);
+        assert_eq!(Word::new_by_bytes(-1, &[0, 0, 0, 0, 0]), assembly.words[1].1);
+    }
+
+    #[test]
+    fn assemble_errors() {
+        let source = " ORIG 3000\nSTART LDA X\n LDB 1\n JMP 2F\n HLT\n END START\n";
+        let err = assemble(source, &Options::new("p.mixal")).expect_err("invalid program");
+
+        assert_eq!(
+            vec![
+                Diagnostic::new(2, "symbol X is not defined".to_string()).with_columns(10..11),
+                Diagnostic::new(3, "unknown operation 'LDB'".to_string()).with_columns(1..4),
+                Diagnostic::new(4, "local symbol 2F is not defined".to_string()).with_columns(5..7),
+            ],
+            err.items
+        );
+
+        let source = " ORIG 3000\n\
+                      START LDA X\n\
+                      \x20JMP 2F\n\
+                      \x20LDA =1//0=\n\
+                      START HLT\n\
+                      \x20LDA 1(0:2\n\
+                      \x20LDA 1,2)\n\
+                      \x20END START\n";
+        let err = assemble(source, &Options::new("p.mixal")).expect_err("invalid program");
+        assert_eq!(6, err.errors());
+        assert_eq!(
+            "p.mixal:2:11: error: symbol X is not defined\n\
+             \x20   2 | START LDA X\n\
+             \x20     |           ^\n\
+             p.mixal:3:6: error: local symbol 2F is not defined\n\
+             \x20   3 |  JMP 2F\n\
+             \x20     |      ^^\n\
+             p.mixal:4:8: error: division by zero\n\
+             \x20   4 |  LDA =1//0=\n\
+             \x20     |        ^^\n\
+             p.mixal:5:1: error: symbol START is already defined\n\
+             \x20   5 | START HLT\n\
+             \x20     | ^^^^^\n\
+             p.mixal:6:11: error: ')' is missing\n\
+             \x20   6 |  LDA 1(0:2\n\
+             \x20     |           ^\n\
+             p.mixal:7:9: error: unexpected ')' in address\n\
+             \x20   7 |  LDA 1,2)\n\
+             \x20     |         ^\n",
+            err.to_string()
+        );
+
+        let errors = |source: &str| {
+            let err = assemble(source, &Options::new("p.mixal")).expect_err("invalid program");
+            err.items
+                .into_iter()
+                .map(|d| (d.line, d.message))
+                .collect::<Vec<_>>()
+        };
+        let error = |line: usize, message: &str| vec![(line, message.to_string())];
+
+        // words and the start address outside the memory
+        assert_eq!(
+            error(1, "location -5 is out of range 0-3999"),
+            errors(" ORIG -5\n HLT\n END 0\n")
+        );
+        assert_eq!(
+            error(3, "location 4000 is out of range 0-3999"),
+            errors(" ORIG 3999\n HLT\n CON 1\n END 3999\n")
+        );
+        assert_eq!(
+            error(3, "literal constant =1= at 4000 is out of range 0-3999"),
+            errors(" ORIG 3999\n LDA =1=\n END 3999\n")
+        );
+        assert_eq!(
+            error(2, "start address 4000 is out of range 0-3999"),
+            errors(" HLT\n END 4000\n")
+        );
+        assert_eq!(
+            error(2, "start address 5000 is out of range 0-3999"),
+            errors("X HLT\n END X+5000\n")
+        );
+        // symbols are 1-10 letters A-Z and digits with a letter
+        assert_eq!(
+            error(1, "symbol ÄÖ can have only letters A-Z and digits"),
+            errors("ÄÖ HLT\n END 0\n")
+        );
+        assert_eq!(
+            error(1, "symbol ABCDEFGHIJK is longer than 10 characters"),
+            errors("ABCDEFGHIJK EQU 1\n HLT\n END 0\n")
+        );
+        assert_eq!(
+            error(1, "symbol 12345 needs a letter"),
+            errors("12345 EQU 1\n HLT\n END 0\n")
+        );
+        assert_eq!(
+            error(1, "symbol 2X3é can have only letters A-Z and digits"),
+            errors(" ENTRY 2X3é\n HLT\n END 0\n")
+        );
+        // the location counter can be moved after the last word
+        let source = " ORIG 3999\n HLT\n ORIG 4000\nE END 3999\n";
+        assert!(assemble(source, &Options::new("p.mixal")).is_ok());
+    }
+
+    #[test]
+    fn assemble_local_symbols() {
+        // the location counter goes back, dB and dF follow the source lines
+        let source = " ORIG 3000\n\
+                      1H JMP 1F\n\
+                      \x20ORIG 2000\n\
+                      1H JMP 1B\n\
+                      \x20ORIG 1000\n\
+                      1H JMP 1B\n\
+                      \x20JMP 1H\n\
+                      \x20END 1B\n";
+        let err = assemble(source, &Options::new("p.mixal")).expect_err("invalid program");
+        assert_eq!(
+            vec![Diagnostic::new(
+                7,
+                "local symbol 1H can't be referenced, use 1B or 1F".to_string()
+            )
+            .with_columns(5..7)],
+            err.items
+        );
+
+        let source = source.replace(" JMP 1H\n", "");
+        let assembly = assemble(&source, &Options::new("p.mixal")).expect("valid program");
+        let jmp = |addr| Word::new_instruction(addr, 0, WordAccess::new_by_spec(0), 39);
+        assert_eq!(
+            vec![(3000, jmp(2000)), (2000, jmp(3000)), (1000, jmp(2000))],
+            assembly.words
+        );
+        assert_eq!(1000, assembly.start);
+    }
+
+    #[test]
+    fn assemble_literals() {
+        let source = " ORIG 3000\n\
+                      START LDA =1=\n\
+                      \x20LDX =2-1=,1(0:2)\n\
+                      \x20LDA =-7=\n\
+                      \x20HLT\n\
+                      AFTER END START\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        // =1= and =2-1= share one constant, the pool follows the program
+        let lda = |addr, i, f, c| Word::new_instruction(addr, i, WordAccess::new_by_spec(f), c);
+        assert_eq!(
+            vec![
+                (3000, lda(3004, 0, 5, 8)),
+                (3001, lda(3004, 1, 2, 15)),
+                (3002, lda(3005, 0, 5, 8)),
+                (3003, lda(0, 0, 2, 5)),
+                (3004, Word::new_from_signed(1)),
+                (3005, Word::new_from_signed(-7)),
+            ],
+            assembly.words
+        );
+        assert!(assembly
+            .symbols
+            .iter()
+            .any(|s| s.name == "AFTER" && s.value == 3006));
+
+        let source = " ORIG 3000\n\
+                      \x20LDA =X=\n\
+                      \x20LDA =100+200+30=\n\
+                      \x20LDA =1\n\
+                      X EQU 5\n\
+                      \x20END 3000\n\
+                      \x20HLT\n";
+        let err = assemble(source, &Options::new("p.mixal")).expect_err("invalid program");
+        assert_eq!(
+            vec![
+                Diagnostic::new(
+                    2,
+                    "literal constant can't refer to a symbol defined later".to_string()
+                )
+                .with_columns(6..7),
+                Diagnostic::new(
+                    3,
+                    "literal constant is longer than 9 characters".to_string()
+                )
+                .with_columns(6..16),
+                Diagnostic::new(
+                    4,
+                    "'=' is missing at the end of the literal constant".to_string()
+                )
+                .with_columns(5..7),
+                Diagnostic::warning(7, "the line after END at line 6 is ignored".to_string()),
+            ],
+            err.items
+        );
+    }
+
+    #[test]
+    fn end_in_included_file() {
+        let dir = std::env::temp_dir().join(format!("mixal_end_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("inc")).expect("temp dir");
+        let lib = dir.join("inc").join("lib2.mixal");
+        std::fs::write(&lib, " HLT\n END 3000\n NOP\n").expect("file");
+
+        let main = dir.join("main.mixal");
+        let source = " ORIG 3000\n INCLUDE \"inc/lib2.mixal\"\n";
+        let assembly = assemble(source, &Options::new(&main.to_string_lossy()))
+            .expect("valid program");
+        std::fs::remove_dir_all(&dir).expect("temp dir");
+
+        let lib = lib.to_string_lossy().to_string();
+        assert_eq!(
+            vec![Diagnostic::warning(
+                3,
+                format!("the line after END at {lib}:2 is ignored")
+            )
+            .in_file(Some(lib))],
+            assembly.warnings
+        );
+    }
+
+    #[test]
+    fn assemble_warnings() {
+        let source = " ORIG 3000\n HLT\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        assert_eq!(
+            vec![Diagnostic::warning(
+                0,
+                "END is missing, the program starts at 0000".to_string()
+            )],
+            assembly.warnings
+        );
+    }
+
+    #[test]
+    fn assemble_expressions() {
+        let source = " ORIG 3000\n\
+                      X EQU -0\n\
+                      \x20CON 1//3\n\
+                      \x20CON 2:5\n\
+                      \x20CON -1+1\n\
+                      \x20CON 1-1\n\
+                      \x20CON -1*0\n\
+                      \x20CON X\n\
+                      \x20ENTA -0\n\
+                      \x20CON 1+2*3\n\
+                      \x20CON *+1\n\
+                      \x20LDA =-0=\n\
+                      \x20LDA =0=\n\
+                      \x20END 3000\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+        let words: Vec<Word> = assembly.words.iter().map(|(_, w)| *w).collect();
+        let minus_zero = Word::new(1 << 31);
+
+        assert_eq!(Word::new(357_913_941), words[0]);
+        assert_eq!(Word::new(21), words[1]);
+        // the sign of a zero sum is the sign of the left operand
+        assert_eq!(minus_zero, words[2]);
+        assert_eq!(Word::new(0), words[3]);
+        assert_eq!(minus_zero, words[4]);
+        assert_eq!(minus_zero, words[5]);
+        // ENTA -0
+        assert_eq!(Word::new((1 << 31) | (2 << 6) | 48), words[6]);
+        // left to right
+        assert_eq!(Word::new(9), words[7]);
+        assert_eq!(Word::new(3009), words[8]);
+        // =-0= and =0= are different constants
+        assert_eq!((3011, minus_zero), assembly.words[11]);
+        assert_eq!((3012, Word::new(0)), assembly.words[12]);
+
+        let source = " CON 1000000000*2\n\
+                      \x20CON 3//2\n\
+                      \x20CON 1/0\n\
+                      \x20CON 200000000:0\n\
+                      \x20CON 1073741824\n\
+                      \x20END 0\n";
+        let errors = assemble(source, &Options::new("p.mixal")).expect_err("invalid program");
+        assert_eq!(
+            vec![
+                Diagnostic::new(1, "overflow, 1000000000 * 2 doesn't fit in 5 bytes".to_string())
+                    .with_columns(15..16),
+                Diagnostic::new(2, "overflow, 3 // 2 doesn't fit in 5 bytes".to_string())
+                    .with_columns(6..8),
+                Diagnostic::new(3, "division by zero".to_string()).with_columns(6..7),
+                Diagnostic::new(4, "overflow, 200000000 : 0 doesn't fit in 5 bytes".to_string())
+                    .with_columns(14..15),
+                Diagnostic::new(5, "number 1073741824 is too large".to_string())
+                    .with_columns(5..15),
+            ],
+            errors.items
+        );
+    }
+
+    #[test]
+    fn assemble_w_values() {
+        let source = " ORIG 1000\n\
+                      X EQU 1(4:4)\n\
+                      \x20CON 1(1:2),3(4:5)\n\
+                      \x20CON 1,-5(0:0)\n\
+                      \x20CON 4(3:3),5\n\
+                      \x20CON X\n\
+                      \x20LDA =2000(1:2)=\n\
+                      \x20END 1000\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+        let words: Vec<Word> = assembly.words.iter().map(|(_, w)| *w).collect();
+
+        assert_eq!(Word::new((1 << 18) | 3), words[0]);
+        assert_eq!(Word::new_from_signed(-1), words[1]);
+        assert_eq!(Word::new(5), words[2]);
+        assert_eq!(Word::new(64), words[3]);
+        // 2000 is 31 16, only the rightmost bytes are stored
+        assert_eq!(Word::new((31 << 24) | (16 << 18)), words[5]);
+
+        let source = " CON 1(1:7)\n CON 1(3:2)\n CON 1(-1)\n CON ,\n CON 1,\n END 0\n";
+        let errors = assemble(source, &Options::new("p.mixal")).expect_err("invalid fields");
+        let expected = [
+            (1, "field (1:7) is invalid, it should be (L:R) with 0 <= L <= R <= 5", 6..11),
+            (2, "field (3:2) is invalid, it should be (L:R) with 0 <= L <= R <= 5", 6..11),
+            (3, "field -1 is invalid, it should be (L:R) with 0 <= L <= R <= 5", 6..10),
+            (4, "expression is missing", 5..6),
+            (5, "expression is missing", 7..8),
+        ];
+        assert_eq!(
+            expected
+                .iter()
+                .map(|(line, message, columns)| {
+                    Diagnostic::new(*line, message.to_string()).with_columns(columns.clone())
+                })
+                .collect::<Vec<_>>(),
+            errors.items
+        );
+    }
+
+    #[test]
+    fn assemble_operands() {
+        let source = " LDA 1,9\n JMP 1(12)\n STA 1(1:7)\n OUT 0(21)\n LDA 5000\n\
+                      \x20LDA -4095,6(0:5)\n JMP 1(0)\n MOVE 1(63)\n\
+                      \x20LDA 1,\n LDA 1,(0:2)\n END 0\n";
+        let errors = assemble(source, &Options::new("p.mixal")).expect_err("invalid operands");
+        let expected = [
+            (1, "index 9 is out of range 0-6", 7..8),
+            (2, "field of JMP is fixed to 0, it can't be 12", 6..10),
+            (3, "field (1:7) is invalid, it should be (L:R) with 0 <= L <= R <= 5", 6..11),
+            (4, "unit 21 is out of range 0-20", 6..10),
+            (5, "address 5000 doesn't fit in 2 bytes, it should be -4095..4095", 5..9),
+            (9, "expression is missing", 7..8),
+            (10, "expression is missing", 7..8),
+        ];
+        assert_eq!(
+            expected
+                .iter()
+                .map(|(line, m, columns)| Diagnostic::new(*line, m.to_string())
+                    .with_columns(columns.clone()))
+                .collect::<Vec<_>>(),
+            errors.items
+        );
+    }
+
+    #[test]
+    fn assemble_macros() {
+        let source = "INCR     MACRO R,N=1\n\
+                      \x20        INC1  N\n\
+                      \x20        ST1   R\n\
+                      \x20        ENDM\n\
+                      \x20        ORIG  1000\n\
+                      START    INCR  X\n\
+                      \x20        INCR  X,N=5\n\
+                      \x20        HLT\n\
+                      X        CON   0\n\
+                      \x20        END   START\n";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        assert_eq!(6, assembly.words.len());
+        assert_eq!(Word::new((1 << 18) | 49), assembly.words[0].1);
+        assert_eq!(Word::new((1005 << 18) | (5 << 6) | 25), assembly.words[1].1);
+        assert_eq!(Word::new((5 << 18) | 49), assembly.words[2].1);
+        // expanded lines have the line of the invocation
+        assert_eq!(vec![(1000, 6), (1001, 6), (1002, 7), (1003, 7)], assembly.line_nums[..4]);
+        assert!(assembly
+            .symbols
+            .iter()
+            .any(|s| s.name == "START" && s.line == 6 && s.uses == vec![10]));
+        assert_eq!(
+            vec![
+                "                          6 START    INCR  X",
+                "                          + START      EQU  *",
+                "1000: + 0001 00 00 49     +          INC1  1",
+                "1001: + 1005 00 05 25     +          ST1   X",
+            ],
+            assembly.listing[5..9]
+        );
+
+        let source = "M MACRO A\n LDA A\n ENDM\n M Y\n END 0\n";
+        let errors = assemble(source, &Options::new("p.mixal")).expect_err("Y is not defined");
+        assert_eq!(
+            vec![Diagnostic::new(4, "symbol Y is not defined (in macro M)".to_string())],
+            errors.items
+        );
+    }
+
+    #[test]
+    fn assemble_macros_in_card_columns() {
+        // the local label and the arguments are longer than the names they replace
+        let source = "INCR       MACRO R,N=1\n\
+                      %L         INC1 N           ADD N\n\
+                      \x20          ST1  R           SAVE R\n\
+                      \x20          ENDM\n\
+                      \x20          ORIG 1000\n\
+                      START      INCR COUNTER\n\
+                      \x20          HLT\n\
+                      COUNTER    CON  0\n\
+                      \x20          END  START\n";
+        let options = Options {
+            format: SourceFormat::Fixed,
+            ..Options::new("p.mixal")
+        };
+        let assembly = assemble(source, &options).expect("valid program");
+
+        assert_eq!(Word::new((1 << 18) | 49), assembly.words[0].1);
+        assert_eq!(Word::new((1003 << 18) | (5 << 6) | 25), assembly.words[1].1);
+        assert_eq!(
+            vec![
+                "1000: + 0001 00 00 49     + LM1        INC1 1           ADD N",
+                "1001: + 1003 00 05 25     +            ST1  COUNTER     SAVE R",
+            ],
+            assembly.listing[7..9]
+        );
+    }
+
+    #[test]
+    fn assemble_modules() {
+        use crate::parser::relocation::{Entry, Field};
+
+        let source = " EXTERN PRINT\n ENTRY START,SIZE\n\
+                      SIZE EQU 2\n\
+                      START LDA BUF,1\n\
+                      \x20JMP PRINT+1\n\
+                      BUF CON *\n\
+                      LAST EQU BUF+SIZE\n\
+                      \x20CON LAST-BUF\n\
+                      \x20END START\n";
+        let options = Options {
+            relocatable: true,
+            ..Options::new("p.mixal")
+        };
+        let assembly = assemble(source, &options).expect("valid module");
+        let module = assembly.module.expect("module");
+
+        assert_eq!(0, assembly.start);
+        assert_eq!(4, module.size);
+        assert!(module.has_start);
+        assert_eq!(vec![(0, Field::Address), (2, Field::Word)], module.relocations);
+        assert_eq!(vec![(1, Field::Address, "PRINT".to_string())], module.externals);
+        assert_eq!(Word::new((1 << 18) | 39), assembly.words[1].1);
+        assert_eq!(
+            vec![
+                Entry {
+                    name: "START".to_string(),
+                    value: 0,
+                    relative: true
+                },
+                Entry {
+                    name: "SIZE".to_string(),
+                    value: 2,
+                    relative: false
+                },
+            ],
+            module.entries
+        );
+
+        let source = " EXTERN X\n ENTRY Z\n\
+                      A EQU X\n\
+                      START LDA X*2\n\
+                      \x20LDA START+X\n\
+                      \x20LDA =START=\n\
+                      \x20LDA 0,START\n\
+                      \x20CON START(1:2)\n\
+                      \x20END 100\n";
+        let errors = assemble(source, &options).expect_err("invalid module");
+        let expected = [
+            (2, "symbol Z is not defined", 7..8),
+            (3, "EQU can't refer to an external symbol", 6..7),
+            (4, "'*' can't be applied to a relocatable value, only + and - can", 11..12),
+            (5, "external symbol X can only be added to a constant", 5..12),
+            (6, "literal constant can't be relocatable, use CON", 6..11),
+            (7, "index can't be relocatable", 7..12),
+            (8, "relocatable value should be the whole W-value", 5..15),
+            (9, "start address of a module should be its address", 5..8),
+        ];
+        assert_eq!(
+            expected
+                .iter()
+                .map(|(line, m, columns)| Diagnostic::new(*line, m.to_string())
+                    .with_columns(columns.clone()))
+                .collect::<Vec<_>>(),
+            errors.items
+        );
+
+        let errors = assemble(" EXTERN X\n END 0\n", &Options::new("p.mixal")).expect_err("EXTERN");
+        assert_eq!(
+            vec![
+                Diagnostic::new(1, "EXTERN is allowed only in relocatable modules".to_string())
+                    .with_columns(8..9)
+            ],
+            errors.items
+        );
+    }
+}
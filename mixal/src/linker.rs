@@ -0,0 +1,343 @@
+use crate::diagnostic::Diagnostic;
+use crate::object::Object;
+use crate::parser::relocation::Field;
+use crate::parser::text_line;
+use crate::parser::symbol_table::{Symbol, SymbolKind};
+use crate::parser::{Assembly, MEMORY_SIZE};
+use mix_core::word::{Word, ABS, MAX_5_BYTES, SIGN};
+
+/*
+ * Linker of relocatable modules written by `mixal --module`
+ *
+ * 1. objects without relocation take the addresses of their words
+ * 2. every module takes `size` words at the first free place from the base, in order
+ * 3. ENTRY symbols get the base of their module, a symbol is defined only once
+ * 4. relocated fields get the base of the module or the value of the external symbol
+ *
+ * The result is an absolute program, END of exactly one module gives the start address,
+ * objects without relocation only fill the memory.
+ */
+
+const MAX_ADDRESS: i32 = 4095; // the address of an instruction is a sign and 2 bytes
+
+/// Links the objects, `name` of each object is used in messages.
+/// Modules are placed from `base` without overlapping each other or the absolute objects
+pub fn link(objects: &[(String, Object)], base: u32) -> Result<Assembly, Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    let error =
+        |name: &String, message: String| Diagnostic::new(0, message).in_file(Some(name.clone()));
+
+    // used memory: start, end and the object
+    let mut used: Vec<(u32, u32, usize)> = Vec::new();
+    let mut bases = vec![0; objects.len()];
+    for (i, (name, object)) in objects.iter().enumerate() {
+        if object.module.is_some() {
+            continue;
+        }
+        for (addr, words) in &object.segments {
+            let range = (*addr, addr + words.len() as u32, i);
+            match used.iter().find(|u| overlaps(u, &range)) {
+                Some(u) => diagnostics.push(error(
+                    name,
+                    format!("words at {:04} overlap {}", addr.max(&u.0), objects[u.2].0),
+                )),
+                None => used.push(range),
+            }
+        }
+    }
+
+    let mut free = base;
+    for (i, (name, object)) in objects.iter().enumerate() {
+        let size = match &object.module {
+            None => continue,
+            Some(module) => module.size,
+        };
+        let mut range = (free, free + size, i);
+        while let Some(u) = used.iter().find(|u| size > 0 && overlaps(u, &range)) {
+            range = (u.1, u.1 + size, i);
+        }
+        if range.1 > MEMORY_SIZE {
+            diagnostics.push(error(
+                name,
+                format!("module of {size} words doesn't fit in memory after {free:04}"),
+            ));
+            continue;
+        }
+        bases[i] = range.0;
+        free = range.1;
+        used.push(range);
+    }
+
+    let mut entries: Vec<(String, i32, usize)> = Vec::new();
+    for (i, (name, object)) in objects.iter().enumerate() {
+        let module = match &object.module {
+            None => continue,
+            Some(module) => module,
+        };
+        for entry in &module.entries {
+            if let Some((_, _, j)) = entries.iter().find(|(e, _, _)| *e == entry.name) {
+                diagnostics.push(error(
+                    name,
+                    format!(
+                        "symbol {} is already defined in {}",
+                        entry.name, objects[*j].0
+                    ),
+                ));
+                continue;
+            }
+            let value = match entry.relative {
+                true => entry.value + bases[i] as i32,
+                false => entry.value,
+            };
+            entries.push((entry.name.clone(), value, i));
+        }
+    }
+
+    let mut starts: Vec<(u32, usize)> = Vec::new();
+    let mut words: Vec<(u32, Word)> = Vec::new();
+    for (i, (name, object)) in objects.iter().enumerate() {
+        let base = bases[i];
+        let mut object_words: Vec<(u32, Word)> = object
+            .segments
+            .iter()
+            .flat_map(|(addr, seg_words)| {
+                seg_words
+                    .iter()
+                    .enumerate()
+                    .map(move |(j, w)| (addr + j as u32, *w))
+            })
+            .collect();
+
+        let module = match &object.module {
+            None => {
+                words.extend(object_words);
+                continue;
+            }
+            Some(module) => module,
+        };
+        if module.has_start {
+            starts.push((object.start + base, i));
+        }
+
+        let fixups = module
+            .relocations
+            .iter()
+            .map(|(addr, field)| (*addr, *field, base as i32, None))
+            .chain(module.externals.iter().map(|(addr, field, symbol)| {
+                let value = entries.iter().find(|(e, _, _)| e == symbol).map(|e| e.1);
+                (*addr, *field, value.unwrap_or(0), Some((symbol, value)))
+            }));
+        for (addr, field, value, external) in fixups {
+            if let Some((symbol, None)) = external {
+                diagnostics.push(error(
+                    name,
+                    format!("symbol {symbol} used at {addr:04} is not defined by any module"),
+                ));
+                continue;
+            }
+            match object_words.iter_mut().find(|(a, _)| *a == addr) {
+                Some((_, word)) => match add_to_field(*word, field, value) {
+                    Some(relocated) => *word = relocated,
+                    None => diagnostics.push(error(
+                        name,
+                        format!("relocated value at {addr:04} doesn't fit in its field"),
+                    )),
+                },
+                None => {
+                    diagnostics.push(error(name, format!("relocation at {addr:04} has no word")))
+                }
+            }
+        }
+        words.extend(object_words.into_iter().map(|(addr, w)| (addr + base, w)));
+    }
+
+    let start = match &starts[..] {
+        [(start, _)] => *start,
+        [] => {
+            diagnostics.push(Diagnostic::new(
+                0,
+                "start address is missing, END of one module should have it".to_string(),
+            ));
+            0
+        }
+        [(start, first), others @ ..] => {
+            for (_, i) in others {
+                diagnostics.push(error(
+                    &objects[*i].0,
+                    format!("start address is already given by {}", objects[*first].0),
+                ));
+            }
+            *start
+        }
+    };
+
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+
+    words.sort_by_key(|(addr, _)| *addr);
+    let mut lines: Vec<String> = words
+        .iter()
+        .map(|(addr, word)| text_line(*addr, word.get_signed_value().to_string(), *word))
+        .collect();
+    lines.push(start.to_string());
+
+    let mut symbols: Vec<Symbol> = entries
+        .into_iter()
+        .map(|(name, value, _)| Symbol {
+            name,
+            kind: SymbolKind::Label,
+            value,
+            line: 0,
+            uses: Vec::new(),
+        })
+        .collect();
+    symbols.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(Assembly {
+        start,
+        lines,
+        words,
+        line_nums: Vec::new(),
+        symbols,
+        listing: Vec::new(),
+        warnings: Vec::new(),
+        included: Vec::new(),
+        module: None,
+    })
+}
+
+fn overlaps(a: &(u32, u32, usize), b: &(u32, u32, usize)) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+/// Adds `value` to the address or to the whole word, the sign of a zero result
+/// is kept as it is
+fn add_to_field(word: Word, field: Field, value: i32) -> Option<Word> {
+    let negative = word.get() & SIGN != 0;
+    let (old, limit) = match field {
+        Field::Address => ((word.get() & ABS) >> 18, MAX_ADDRESS),
+        Field::Word => (word.get() & ABS, MAX_5_BYTES),
+    };
+    let old = if negative { -(old as i32) } else { old as i32 };
+    let new = old + value;
+    if new.abs() > limit {
+        return None;
+    }
+
+    let sign = match new {
+        0 => word.get() & SIGN,
+        new if new < 0 => SIGN,
+        _ => 0,
+    };
+    let magnitude = new.unsigned_abs();
+    Some(Word::new(match field {
+        Field::Address => sign | (magnitude << 18) | (word.get() & 0o777777),
+        Field::Word => sign | magnitude,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::{parse_object, to_object};
+    use crate::{assemble, Options};
+
+    fn module(name: &str, source: &str) -> (String, Object) {
+        let options = Options {
+            relocatable: true,
+            ..Options::new(name)
+        };
+        let assembly = assemble(source, &options).expect("valid module");
+        let object = parse_object(&to_object(&assembly, false)).expect("valid object");
+        (name.to_string(), object)
+    }
+
+    #[test]
+    fn link_modules() {
+        let main = module(
+            "main.mixal",
+            " EXTERN PRINT,MSG\n\
+             START LDA MSG+1\n\
+             \x20JMP PRINT\n\
+             \x20JMP *-1\n\
+             \x20HLT\n\
+             \x20END START\n",
+        );
+        let print = module(
+            "print.mixal",
+            " ENTRY PRINT,MSG,UNIT\n\
+             UNIT EQU 18\n\
+             PRINT OUT MSG(UNIT)\n\
+             \x20JMP 0\n\
+             MSG ALF HELLO\n\
+             \x20CON MSG\n\
+             \x20END\n",
+        );
+        let fixed = (
+            "data.mixal".to_string(),
+            parse_object(&to_object(
+                &assemble(" ORIG 4\n CON 7\n END 0\n", &Options::new("data.mixal")).expect("valid"),
+                false,
+            ))
+            .expect("valid object"),
+        );
+
+        // main takes 0-3, the absolute word at 4 moves print to 5-8
+        let program = link(&[main, fixed.clone(), print], 0).expect("linked");
+        let word = |addr: u32| {
+            program
+                .words
+                .iter()
+                .find(|(a, _)| *a == addr)
+                .map(|(_, w)| *w)
+        };
+        let instruction = |a: u32, f: u32, c: u32| Some(Word::new((a << 18) | (f << 6) | c));
+
+        assert_eq!(instruction(8, 5, 8), word(0)); // LDA MSG+1
+        assert_eq!(instruction(5, 0, 39), word(1)); // JMP PRINT
+        assert_eq!(instruction(1, 0, 39), word(2)); // JMP *-1
+        assert_eq!(Some(Word::new(7)), word(4));
+        assert_eq!(instruction(7, 18, 37), word(5)); // OUT MSG(UNIT)
+        assert_eq!(instruction(0, 0, 39), word(6)); // JMP 0 is absolute
+        assert_eq!(Some(Word::new(7)), word(8)); // CON MSG
+        assert_eq!(0, program.start);
+        assert_eq!("8, 7", program.lines[program.lines.len() - 2]);
+        assert_eq!(
+            vec![("MSG", 7), ("PRINT", 5), ("UNIT", 18)],
+            program
+                .symbols
+                .iter()
+                .map(|s| (&s.name[..], s.value))
+                .collect::<Vec<_>>()
+        );
+
+        let missing = module("m.mixal", " EXTERN X\nSTART JMP X\n END START\n");
+        let twice = module("t.mixal", "START HLT\n END START\n");
+        let errors = link(&[missing, twice, fixed], 3990).expect_err("not linked");
+        assert_eq!(
+            vec![
+                "symbol X used at 0000 is not defined by any module",
+                "start address is already given by m.mixal",
+            ],
+            errors.iter().map(|d| &d.message[..]).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn relocated_fields() {
+        let word = |value: i32| Word::new_from_signed(value);
+        let lda = Word::new((5 << 18) | (1 << 12) | (5 << 6) | 8);
+        assert_eq!(
+            Some(Word::new((105 << 18) | (1 << 12) | (5 << 6) | 8)),
+            add_to_field(lda, Field::Address, 100)
+        );
+        assert_eq!(
+            Some(Word::new(SIGN | (95 << 18) | (5 << 6) | 8)),
+            add_to_field(Word::new(lda.get() & !(1 << 12)), Field::Address, -100)
+        );
+        assert_eq!(None, add_to_field(lda, Field::Address, 4091));
+        assert_eq!(Some(word(-3)), add_to_field(word(-10), Field::Word, 7));
+        assert_eq!(None, add_to_field(word(MAX_5_BYTES), Field::Word, 1));
+    }
+}
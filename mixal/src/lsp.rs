@@ -0,0 +1,490 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lsp::document::{CompletionKind, Document};
+use crate::lsp::json::Json;
+
+use std::io;
+use std::io::prelude::*;
+use std::ops::Range;
+
+pub mod document;
+pub mod json;
+
+/*
+ * Language server of MIXAL over stdin and stdout: `mixal lsp`
+ *
+ * message -> "Content-Length: " n "\r\n" (other headers "\r\n")* "\r\n" n bytes of JSON-RPC
+ *
+ * The whole text is sent on every change, diagnostics are published after it.
+ * Positions of the protocol count UTF-16 code units, the documents count characters.
+ */
+
+const METHOD_NOT_FOUND: i32 = -32601;
+const INVALID_PARAMS: i32 = -32602;
+
+/// Serves the editor until `exit` or the end of the input
+pub fn run(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(message) = read_message(input)? {
+        let message = match json::parse(&message) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+        for reply in server.handle(&message) {
+            write_message(output, &reply)?;
+        }
+        if server.exit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length: Option<usize> = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Content-Length is missing"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(String::from_utf8_lossy(&body).to_string()))
+}
+
+fn write_message(output: &mut impl Write, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    output.flush()
+}
+
+pub struct Server {
+    documents: Vec<(String, Document)>, // uri, document
+    exit: bool,
+}
+
+impl Server {
+    pub fn new() -> Server {
+        Server {
+            documents: Vec::new(),
+            exit: false,
+        }
+    }
+
+    /// Replies and notifications for a message of the editor
+    pub fn handle(&mut self, message: &Json) -> Vec<Json> {
+        let method = message.get("method").as_str().unwrap_or_default();
+        let params = message.get("params");
+        let id = message.get("id");
+
+        match method {
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let uri = params.get("textDocument").get("uri").as_str();
+                let text = match method {
+                    "textDocument/didOpen" => params.get("textDocument").get("text").as_str(),
+                    _ => params
+                        .get("contentChanges")
+                        .as_array()
+                        .and_then(|changes| changes.last())
+                        .and_then(|change| change.get("text").as_str()),
+                };
+                match (uri, text) {
+                    (Some(uri), Some(text)) => vec![self.open(uri, text)],
+                    _ => Vec::new(),
+                }
+            }
+            "textDocument/didClose" => {
+                let uri = params.get("textDocument").get("uri").as_str();
+                self.documents.retain(|(u, _)| Some(&u[..]) != uri);
+                match uri {
+                    Some(uri) => vec![publish(uri, Vec::new())],
+                    None => Vec::new(),
+                }
+            }
+            "exit" => {
+                self.exit = true;
+                Vec::new()
+            }
+            // other notifications are ignored
+            _ if *id == Json::Null => Vec::new(),
+            _ => vec![match self.request(method, params) {
+                Ok(result) => Json::object(vec![
+                    ("jsonrpc", Json::string("2.0")),
+                    ("id", id.clone()),
+                    ("result", result),
+                ]),
+                Err((code, message)) => Json::object(vec![
+                    ("jsonrpc", Json::string("2.0")),
+                    ("id", id.clone()),
+                    (
+                        "error",
+                        Json::object(vec![
+                            ("code", Json::Number(code as f64)),
+                            ("message", Json::String(message)),
+                        ]),
+                    ),
+                ]),
+            }],
+        }
+    }
+
+    fn request(&self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+        match method {
+            "initialize" => return Ok(capabilities()),
+            "shutdown" => return Ok(Json::Null),
+            "textDocument/hover"
+            | "textDocument/definition"
+            | "textDocument/references"
+            | "textDocument/completion"
+            | "textDocument/documentSymbol" => {}
+            _ => {
+                return Err((
+                    METHOD_NOT_FOUND,
+                    format!("method {method} is not supported"),
+                ))
+            }
+        }
+
+        let uri = params
+            .get("textDocument")
+            .get("uri")
+            .as_str()
+            .ok_or((INVALID_PARAMS, "textDocument is missing".to_string()))?;
+        let document = match self.documents.iter().find(|(u, _)| u == uri) {
+            Some((_, document)) => document,
+            None => return Ok(Json::Null),
+        };
+        let position = params.get("position");
+        let line = position.get("line").as_usize().unwrap_or(0);
+        let column = char_column(
+            document.line(line),
+            position.get("character").as_usize().unwrap_or(0),
+        );
+        let location = |(line, columns): (usize, Range<usize>)| {
+            Json::object(vec![
+                ("uri", Json::string(uri)),
+                ("range", range(document, line, columns)),
+            ])
+        };
+
+        Ok(match method {
+            "textDocument/hover" => match document.hover(line, column) {
+                None => Json::Null,
+                Some((columns, text)) => Json::object(vec![
+                    (
+                        "contents",
+                        Json::object(vec![
+                            ("kind", Json::string("markdown")),
+                            ("value", Json::String(text)),
+                        ]),
+                    ),
+                    ("range", range(document, line, columns)),
+                ]),
+            },
+            "textDocument/definition" => match document.definition(line, column) {
+                None => Json::Null,
+                Some(definition) => location(definition),
+            },
+            "textDocument/references" => {
+                let with_definition = params
+                    .get("context")
+                    .get("includeDeclaration")
+                    .as_bool()
+                    .unwrap_or(true);
+                Json::Array(
+                    document
+                        .references(line, column, with_definition)
+                        .into_iter()
+                        .map(location)
+                        .collect(),
+                )
+            }
+            "textDocument/completion" => Json::Array(
+                document
+                    .completions()
+                    .into_iter()
+                    .map(|c| {
+                        let kind = match c.kind {
+                            CompletionKind::Operation | CompletionKind::PseudoOperation => 14,
+                            CompletionKind::Symbol => 6,
+                        };
+                        Json::object(vec![
+                            ("label", Json::String(c.label)),
+                            ("kind", Json::from(kind)),
+                            ("detail", Json::String(c.detail)),
+                        ])
+                    })
+                    .collect(),
+            ),
+            _ => Json::Array(
+                document
+                    .outline()
+                    .into_iter()
+                    .map(|label| {
+                        let range = range(document, label.line, label.columns);
+                        let detail = label.value.map(|v| v.to_string()).unwrap_or_default();
+                        Json::object(vec![
+                            ("name", Json::String(label.name)),
+                            ("detail", Json::String(detail)),
+                            // constant or function
+                            ("kind", Json::from(if label.is_equ { 14 } else { 12 })),
+                            ("range", range.clone()),
+                            ("selectionRange", range),
+                        ])
+                    })
+                    .collect(),
+            ),
+        })
+    }
+
+    fn open(&mut self, uri: &str, text: &str) -> Json {
+        match self.documents.iter_mut().find(|(u, _)| u == uri) {
+            Some((_, document)) => document.update(text),
+            None => self
+                .documents
+                .push((uri.to_string(), Document::new(&uri_path(uri), text))),
+        }
+        let document = &self
+            .documents
+            .iter()
+            .find(|(u, _)| u == uri)
+            .expect("opened")
+            .1;
+
+        let diagnostics = document
+            .diagnostics()
+            .iter()
+            .map(|d| to_lsp_diagnostic(document, d))
+            .collect();
+        publish(uri, diagnostics)
+    }
+}
+
+impl Default for Server {
+    fn default() -> Server {
+        Server::new()
+    }
+}
+
+fn capabilities() -> Json {
+    Json::object(vec![
+        (
+            "capabilities",
+            Json::object(vec![
+                ("textDocumentSync", Json::from(1)), // the whole text
+                ("hoverProvider", Json::Bool(true)),
+                ("definitionProvider", Json::Bool(true)),
+                ("referencesProvider", Json::Bool(true)),
+                ("completionProvider", Json::object(Vec::new())),
+                ("documentSymbolProvider", Json::Bool(true)),
+            ]),
+        ),
+        (
+            "serverInfo",
+            Json::object(vec![("name", Json::string("mixal"))]),
+        ),
+    ])
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("method", Json::string("textDocument/publishDiagnostics")),
+        (
+            "params",
+            Json::object(vec![
+                ("uri", Json::string(uri)),
+                ("diagnostics", Json::Array(diagnostics)),
+            ]),
+        ),
+    ])
+}
+
+/// A problem without a line or in an included file is shown on the first line
+fn to_lsp_diagnostic(document: &Document, diagnostic: &Diagnostic) -> Json {
+    let (line, columns, message) = match (&diagnostic.file, diagnostic.line) {
+        (Some(file), line) => (0, None, format!("{file}:{line}: {}", diagnostic.message)),
+        (None, 0) => (0, None, diagnostic.message.clone()),
+        (None, line) => (
+            line - 1,
+            diagnostic.columns.clone(),
+            diagnostic.message.clone(),
+        ),
+    };
+    let columns = columns.unwrap_or(0..document.line(line).chars().count());
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+
+    Json::object(vec![
+        ("range", range(document, line, columns)),
+        ("severity", Json::from(severity)),
+        ("source", Json::string("mixal")),
+        ("message", Json::String(message)),
+    ])
+}
+
+fn range(document: &Document, line: usize, columns: Range<usize>) -> Json {
+    let text = document.line(line);
+    let position = |column: usize| {
+        Json::object(vec![
+            ("line", Json::from(line)),
+            ("character", Json::from(utf16_column(text, column))),
+        ])
+    };
+    Json::object(vec![
+        ("start", position(columns.start)),
+        ("end", position(columns.end)),
+    ])
+}
+
+/// UTF-16 position of the character column, columns after the end are kept
+fn utf16_column(text: &str, column: usize) -> usize {
+    let count = text.chars().count();
+    let inside: usize = text.chars().take(column).map(char::len_utf16).sum();
+    inside + column.saturating_sub(count)
+}
+
+/// Character column of the UTF-16 position
+fn char_column(text: &str, utf16: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in text.chars().enumerate() {
+        if units >= utf16 {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    text.chars().count() + utf16.saturating_sub(units)
+}
+
+/// Path of a `file://` URI, escaped bytes are decoded
+fn uri_path(uri: &str) -> String {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = path.as_bytes();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes[i] {
+            b'%' => path
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                result.push(byte);
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(json: &str) -> String {
+        format!("Content-Length: {}\r\n\r\n{json}", json.len())
+    }
+
+    fn replies(output: &[u8]) -> Vec<Json> {
+        let mut output = output;
+        let mut result = Vec::new();
+        while let Some(body) = read_message(&mut output).expect("valid output") {
+            result.push(json::parse(&body).expect("valid JSON"));
+        }
+        result
+    }
+
+    #[test]
+    fn session() {
+        let uri = "file:///tmp/my%20dir/p.mixal";
+        let text = "START LDA X\\n HLT\\nX CON 7\\n END START\\n";
+        let input = [
+            r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.to_string(),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didOpen","params":{{"textDocument":{{"uri":"{uri}","languageId":"mixal","version":1,"text":"{text}"}}}}}}"#
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{{"textDocument":{{"uri":"{uri}"}},"position":{{"line":0,"character":10}}}}}}"#
+            ),
+            format!(
+                r#"{{"jsonrpc":"2.0","method":"textDocument/didChange","params":{{"textDocument":{{"uri":"{uri}","version":2}},"contentChanges":[{{"text":"{}"}}]}}}}"#,
+                text.replace("LDA X", "LDA Y")
+            ),
+            r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/rename","params":{}}"#.to_string(),
+            r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string(),
+            r#"{"jsonrpc":"2.0","id":5,"method":"shutdown"}"#.to_string(),
+        ];
+        let input: String = input.iter().map(|m| message(m)).collect();
+        let mut output = Vec::new();
+        run(&mut input.as_bytes(), &mut output).expect("served");
+        let replies = replies(&output);
+
+        assert_eq!(6, replies.len());
+        let capabilities = replies[0].get("result").get("capabilities");
+        assert_eq!(Some(true), capabilities.get("hoverProvider").as_bool());
+
+        assert_eq!(
+            Some("textDocument/publishDiagnostics"),
+            replies[1].get("method").as_str()
+        );
+        assert_eq!(
+            Some(&Vec::new()),
+            replies[1].get("params").get("diagnostics").as_array()
+        );
+
+        assert_eq!(
+            r#"{"jsonrpc":"2.0","id":2,"result":{"uri":"file:///tmp/my%20dir/p.mixal","range":{"start":{"line":2,"character":0},"end":{"line":2,"character":1}}}}"#,
+            replies[2].to_string()
+        );
+
+        let diagnostics = replies[3].get("params").get("diagnostics");
+        let diagnostic = &diagnostics.as_array().expect("diagnostics")[0];
+        assert_eq!(
+            r#"{"start":{"line":0,"character":10},"end":{"line":0,"character":11}}"#,
+            diagnostic.get("range").to_string()
+        );
+        assert_eq!(Some(1), diagnostic.get("severity").as_usize());
+
+        assert_eq!(
+            Some(METHOD_NOT_FOUND as f64),
+            match replies[4].get("error").get("code") {
+                Json::Number(code) => Some(*code),
+                _ => None,
+            }
+        );
+    }
+
+    #[test]
+    fn positions() {
+        assert_eq!(3, utf16_column("a😀b", 2));
+        assert_eq!(2, char_column("a😀b", 3));
+        assert_eq!(5, utf16_column("ab", 5));
+        assert_eq!(5, char_column("ab", 5));
+        assert_eq!(
+            "/tmp/my dir/p.mixal",
+            uri_path("file:///tmp/my%20dir/p.mixal")
+        );
+    }
+}
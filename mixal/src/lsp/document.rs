@@ -0,0 +1,540 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Lexer;
+use crate::parser::symbol_table::{Symbol, SymbolKind};
+use crate::tags::{MixInstructions, Tag};
+use crate::{assemble, xref, Options};
+
+use std::ops::Range;
+use std::panic::{self, AssertUnwindSafe};
+
+/*
+ * Source file open in the editor, all lines and columns are 0-based
+ *
+ * The lines are split into tokens to find the labels, the symbols of the addresses
+ * and the operations. The symbols are resolved by their names, dB and dF by the
+ * order of the lines. Values come from the last assembly without errors.
+ */
+
+/// Symbol in the LOC field (definition) or in the address of a line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occurrence {
+    pub name: String,
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub definition: bool,
+}
+
+/// Operation of a line: MIX instruction or pseudo-operation
+#[derive(Debug, Clone, PartialEq)]
+struct Operation {
+    name: String,
+    line: usize,
+    columns: Range<usize>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CompletionKind {
+    Operation,
+    PseudoOperation,
+    Symbol,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String,
+}
+
+/// Label in the outline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub name: String,
+    pub line: usize,
+    pub columns: Range<usize>,
+    pub is_equ: bool,
+    pub value: Option<i32>,
+}
+
+pub const PSEUDO_OPERATIONS: [(&str, &str); 7] = [
+    ("ALF", "word of 5 characters"),
+    ("CON", "word with the value of the W-value"),
+    (
+        "END",
+        "end of the program, the address is the start of the program",
+    ),
+    ("ENTRY", "symbols of the module used by other modules"),
+    ("EQU", "defines the symbol of LOC as the W-value"),
+    ("EXTERN", "symbols of other modules used in this one"),
+    ("ORIG", "sets the location counter to the W-value"),
+];
+
+pub struct Document {
+    name: String,
+    lines: Vec<String>,
+    occurrences: Vec<Occurrence>,
+    operations: Vec<Operation>,
+    symbols: Vec<Symbol>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    /// `name` is the path of the file, INCLUDE looks for files next to it
+    pub fn new(name: &str, text: &str) -> Document {
+        let mut document = Document {
+            name: name.to_string(),
+            lines: Vec::new(),
+            occurrences: Vec::new(),
+            operations: Vec::new(),
+            symbols: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        document.update(text);
+        document
+    }
+
+    /// New text of the document, the values of the symbols are kept when it has errors.
+    /// A panic of the lexer or the assembler becomes a diagnostic, the server goes on
+    pub fn update(&mut self, text: &str) {
+        self.lines = text.lines().map(|l| l.to_string()).collect();
+
+        let options = Options::new(&self.name);
+        let analysis = panic::catch_unwind(AssertUnwindSafe(|| {
+            self.index();
+            assemble(text, &options)
+        }));
+        match analysis {
+            Ok(Ok(assembly)) => {
+                self.symbols = assembly.symbols;
+                self.diagnostics = assembly.warnings;
+            }
+            Ok(Err(diagnostics)) => self.diagnostics = diagnostics.items,
+            Err(payload) => {
+                self.occurrences.clear();
+                self.operations.clear();
+                let reason = match (
+                    payload.downcast_ref::<&str>(),
+                    payload.downcast_ref::<String>(),
+                ) {
+                    (Some(reason), _) => reason.to_string(),
+                    (_, Some(reason)) => reason.clone(),
+                    _ => "unknown error".to_string(),
+                };
+                self.diagnostics = vec![Diagnostic::new(
+                    0,
+                    format!("the text can't be assembled: {reason}"),
+                )];
+            }
+        }
+    }
+
+    pub fn line(&self, line: usize) -> &str {
+        self.lines.get(line).map(|l| &l[..]).unwrap_or("")
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn index(&mut self) {
+        let mix_inst = MixInstructions::new();
+        let lexer = Lexer::new();
+        let (program_lines, _) = lexer.parse_program_lines(&mix_inst, self.lines.clone());
+
+        self.occurrences.clear();
+        self.operations.clear();
+        for program_line in program_lines {
+            let line = program_line.line_num - 1;
+            let text: Vec<char> = self.lines[line].chars().collect();
+
+            if program_line.loc.get_tag() == Tag::SYMBOLS {
+                let name = program_line.loc.get_symbols();
+                // a line with errors keeps the label from the start of the line
+                let columns = match program_line.loc.get_columns() {
+                    columns if columns.is_empty() => 0..name.chars().count(),
+                    columns => columns,
+                };
+                self.occurrences.push(Occurrence {
+                    name,
+                    line,
+                    columns,
+                    definition: true,
+                });
+            }
+
+            let op = match program_line.op.get_tag() {
+                Tag::MIX_OP => program_line.op.get_mix_op().name.to_string(),
+                _ => program_line.op.get_mixal_op().get_name(),
+            };
+            let columns = program_line.op_column..program_line.op_column + op.chars().count();
+            let in_source: String = text.get(columns.clone()).unwrap_or(&[]).iter().collect();
+            if in_source == op {
+                self.operations.push(Operation {
+                    name: op,
+                    line,
+                    columns,
+                });
+            }
+
+            for token in program_line.addr {
+                if token.get_tag() == Tag::SYMBOLS {
+                    self.occurrences.push(Occurrence {
+                        name: token.get_symbols(),
+                        line,
+                        columns: token.get_columns(),
+                        definition: false,
+                    });
+                }
+            }
+        }
+    }
+
+    fn occurrence_at(&self, line: usize, column: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|o| o.line == line && o.columns.start <= column && column <= o.columns.end)
+    }
+
+    /// Definition of the symbol, dB is the last dH before its line, dF the first one after
+    fn definition_of(&self, occurrence: &Occurrence) -> Option<&Occurrence> {
+        if occurrence.definition {
+            return self.occurrences.iter().find(|o| *o == occurrence);
+        }
+
+        let mut definitions = self.occurrences.iter().filter(|o| o.definition);
+        match local_reference(&occurrence.name) {
+            Some((digit, 'B')) => definitions
+                .rev()
+                .find(|o| o.line < occurrence.line && o.name == format!("{digit}H")),
+            Some((digit, _)) => {
+                definitions.find(|o| o.line > occurrence.line && o.name == format!("{digit}H"))
+            }
+            None => definitions.find(|o| o.name == occurrence.name),
+        }
+    }
+
+    /// Line and columns of the definition of the symbol at the position
+    pub fn definition(&self, line: usize, column: usize) -> Option<(usize, Range<usize>)> {
+        let occurrence = self.occurrence_at(line, column)?;
+        let definition = self.definition_of(occurrence)?;
+        Some((definition.line, definition.columns.clone()))
+    }
+
+    /// Lines and columns of the uses of the symbol at the position,
+    /// with its definition if `with_definition`
+    pub fn references(
+        &self,
+        line: usize,
+        column: usize,
+        with_definition: bool,
+    ) -> Vec<(usize, Range<usize>)> {
+        let occurrence = match self.occurrence_at(line, column) {
+            None => return Vec::new(),
+            Some(occurrence) => occurrence,
+        };
+        let definition = self.definition_of(occurrence);
+
+        self.occurrences
+            .iter()
+            .filter(|o| with_definition || !o.definition)
+            .filter(|o| match definition {
+                Some(definition) => self.definition_of(o) == Some(definition),
+                // undefined symbol, its uses are found by the name
+                None => o.name == occurrence.name,
+            })
+            .map(|o| (o.line, o.columns.clone()))
+            .collect()
+    }
+
+    /// Columns of the word at the position and its description in markdown
+    pub fn hover(&self, line: usize, column: usize) -> Option<(Range<usize>, String)> {
+        let operation = self
+            .operations
+            .iter()
+            .find(|o| o.line == line && o.columns.start <= column && column <= o.columns.end);
+        if let Some(operation) = operation {
+            return Some((
+                operation.columns.clone(),
+                describe_operation(&operation.name),
+            ));
+        }
+
+        let occurrence = self.occurrence_at(line, column)?;
+        let text = match self.definition_of(occurrence) {
+            None => format!("**{}** is not defined", occurrence.name),
+            Some(definition) => {
+                let symbol = self.symbol(definition);
+                let kind = match symbol {
+                    Some(symbol) => xref::kind_name(symbol.kind),
+                    None if local_reference(&definition.name).is_some() => "local",
+                    None => "label",
+                };
+                let value = match symbol {
+                    Some(symbol) => format!(" = {}", symbol.value),
+                    None => String::new(),
+                };
+                format!(
+                    "**{}** ({kind}){value}\n\ndefined on line {}",
+                    definition.name,
+                    definition.line + 1
+                )
+            }
+        };
+        Some((occurrence.columns.clone(), text))
+    }
+
+    /// Symbol of the last assembly defined by the occurrence
+    fn symbol(&self, definition: &Occurrence) -> Option<&Symbol> {
+        self.symbols.iter().find(|s| {
+            s.name == definition.name
+                && match s.kind {
+                    SymbolKind::Local => s.line == definition.line + 1,
+                    SymbolKind::Literal => false,
+                    _ => true,
+                }
+        })
+    }
+
+    /// Operations, pseudo-operations and the symbols of the document
+    pub fn completions(&self) -> Vec<Completion> {
+        let mix_inst = MixInstructions::new();
+        let mut result: Vec<Completion> = mix_inst
+            .names()
+            .into_iter()
+            .map(|name| {
+                let op = mix_inst.get(name);
+                let word = op.to_word().get();
+                Completion {
+                    label: name.to_string(),
+                    kind: CompletionKind::Operation,
+                    detail: format!("C = {}, F = {}", word & 63, (word >> 6) & 63),
+                }
+            })
+            .collect();
+        result.extend(PSEUDO_OPERATIONS.iter().map(|(name, meaning)| Completion {
+            label: name.to_string(),
+            kind: CompletionKind::PseudoOperation,
+            detail: meaning.to_string(),
+        }));
+
+        let mut names: Vec<&String> = self
+            .occurrences
+            .iter()
+            .filter(|o| o.definition && local_reference(&o.name).is_none())
+            .map(|o| &o.name)
+            .collect();
+        names.sort();
+        names.dedup();
+        result.extend(names.into_iter().map(|name| Completion {
+            label: name.clone(),
+            kind: CompletionKind::Symbol,
+            detail: String::new(),
+        }));
+        result
+    }
+
+    /// Labels of the document in the order of the lines, local symbols are left out
+    pub fn outline(&self) -> Vec<Label> {
+        self.occurrences
+            .iter()
+            .filter(|o| o.definition && local_reference(&o.name).is_none())
+            .map(|o| Label {
+                name: o.name.clone(),
+                line: o.line,
+                columns: o.columns.clone(),
+                is_equ: self
+                    .operations
+                    .iter()
+                    .any(|op| op.line == o.line && op.name == "EQU"),
+                value: self.symbol(o).map(|s| s.value),
+            })
+            .collect()
+    }
+}
+
+/// Digit and direction of dH, dB or dF
+fn local_reference(name: &str) -> Option<(char, char)> {
+    let mut chars = name.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some(digit), Some(direction), None)
+            if digit.is_ascii_digit() && "HBF".contains(direction) =>
+        {
+            Some((digit, direction))
+        }
+        _ => None,
+    }
+}
+
+/// Hover text of an operation: its code, what it does, F and the time
+fn describe_operation(name: &str) -> String {
+    if let Some((_, meaning)) = PSEUDO_OPERATIONS.iter().find(|(op, _)| *op == name) {
+        return format!("**{name}** pseudo-operation\n\n{meaning}");
+    }
+
+    let mix_inst = MixInstructions::new();
+    let op = mix_inst.get(name);
+    let word = op.to_word().get();
+    let (c, f) = (word & 63, (word >> 6) & 63);
+    format!(
+        "**{name}** C = {c}, F = {f}\n\n{}\n\n{}\n\ntime: {}u",
+        summary(name, c),
+        op.field_meaning(),
+        op.time()
+    )
+}
+
+/// What the operation does, V is the field F of the word at M
+fn summary(name: &str, c: u32) -> String {
+    let register = |i: u32| match i {
+        0 => "rA".to_string(),
+        7 => "rX".to_string(),
+        i => format!("rI{i}"),
+    };
+    match c {
+        0 => "no operation".to_string(),
+        1 => "rA = rA + V".to_string(),
+        2 => "rA = rA - V".to_string(),
+        3 => "rAX = rA * V".to_string(),
+        4 => "rA = rAX / V, rX = remainder".to_string(),
+        5 => match name {
+            "NUM" => "rA = number of the character codes of rAX".to_string(),
+            "CHAR" => "rAX = character codes of the digits of rA".to_string(),
+            _ => "stops the machine".to_string(),
+        },
+        6 => "shifts rA or rAX by M bytes".to_string(),
+        7 => "moves F words from M to the address in rI1".to_string(),
+        8..=15 => format!("{} = V", register(c - 8)),
+        16..=23 => format!("{} = -V", register(c - 16)),
+        24..=31 => format!("field F of M = {}", register(c - 24)),
+        32 => "field F of M = rJ".to_string(),
+        33 => "field F of M = 0".to_string(),
+        34 => "jumps to M if unit F is busy".to_string(),
+        35 => "control operation of unit F".to_string(),
+        36 => "reads a block of unit F into M".to_string(),
+        37 => "writes a block from M to unit F".to_string(),
+        38 => "jumps to M if unit F is ready".to_string(),
+        39 => "jumps to M, rJ = next address (not for JSJ)".to_string(),
+        40..=47 => format!("jumps to M by the value of {}", register(c - 40)),
+        48..=55 => format!("changes {} by M: INC, DEC, ENT or ENN", register(c - 48)),
+        _ => format!("compares field F of {} with V", register(c - 56)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "* program\n\
+                          N     EQU  5\n\
+                          \x20     ORIG 3000\n\
+                          START ENT1 N\n\
+                          2H    DEC1 1\n\
+                          \x20     J1P  2B\n\
+                          \x20     JMP  2F\n\
+                          2H    HLT\n\
+                          \x20     END  START\n";
+
+    #[test]
+    fn symbols() {
+        let document = Document::new("p.mixal", SOURCE);
+
+        assert_eq!(Some((3, 0..5)), document.definition(8, 13));
+        assert_eq!(Some((4, 0..2)), document.definition(5, 12)); // 2B
+        assert_eq!(Some((7, 0..2)), document.definition(6, 11)); // 2F
+        assert_eq!(None, document.definition(3, 8)); // ENT1
+
+        assert_eq!(vec![(3, 11..12)], document.references(1, 0, false));
+        assert_eq!(
+            vec![(4, 0..2), (5, 11..13)],
+            document.references(4, 1, true)
+        );
+
+        assert_eq!(
+            Some((11..12, "**N** (equ) = 5\n\ndefined on line 2".to_string())),
+            document.hover(3, 11)
+        );
+        assert_eq!(
+            Some((
+                0..2,
+                "**2H** (local) = 3004\n\ndefined on line 8".to_string()
+            )),
+            document.hover(7, 0)
+        );
+        let (columns, text) = document.hover(3, 7).expect("ENT1");
+        assert_eq!(6..10, columns);
+        assert_eq!(
+            "**ENT1** C = 49, F = 2\n\nchanges rI1 by M: INC, DEC, ENT or ENN\n\n\
+             F is 2, it selects ENT1\n\ntime: 1u",
+            text
+        );
+        assert!(document.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn errors_keep_values() {
+        let mut document = Document::new("p.mixal", SOURCE);
+        document.update(&SOURCE.replace("JMP  2F", "JMP  X"));
+
+        assert_eq!(1, document.diagnostics().len());
+        assert_eq!(7, document.diagnostics()[0].line);
+        assert_eq!(
+            Some((11..12, "**X** is not defined".to_string())),
+            document.hover(6, 11)
+        );
+        assert_eq!(
+            Some((
+                0..5,
+                "**START** (label) = 3000\n\ndefined on line 4".to_string()
+            )),
+            document.hover(3, 2)
+        );
+    }
+
+    #[test]
+    fn not_ascii_label() {
+        let mut document = Document::new("p.mixal", SOURCE);
+        document.update("é ORIG 3000\n HLT\n END 3000\n");
+        assert!(document.diagnostics().iter().all(|d| d.line == 1));
+        assert_eq!(None, document.definition(1, 1));
+    }
+
+    #[test]
+    fn completions_and_outline() {
+        let document = Document::new("p.mixal", SOURCE);
+        let completions = document.completions();
+
+        let lda = completions.iter().find(|c| c.label == "LDA").expect("LDA");
+        assert_eq!("C = 8, F = 5", lda.detail);
+        assert!(completions
+            .iter()
+            .any(|c| c.label == "ORIG" && c.kind == CompletionKind::PseudoOperation));
+        assert_eq!(
+            vec!["N", "START"],
+            completions
+                .iter()
+                .filter(|c| c.kind == CompletionKind::Symbol)
+                .map(|c| &c.label[..])
+                .collect::<Vec<_>>()
+        );
+
+        assert_eq!(
+            vec![
+                Label {
+                    name: "N".to_string(),
+                    line: 1,
+                    columns: 0..1,
+                    is_equ: true,
+                    value: Some(5),
+                },
+                Label {
+                    name: "START".to_string(),
+                    line: 3,
+                    columns: 0..5,
+                    is_equ: false,
+                    value: Some(3000),
+                },
+            ],
+            document.outline()
+        );
+    }
+}
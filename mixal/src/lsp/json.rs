@@ -0,0 +1,342 @@
+use std::fmt;
+
+/*
+ * JSON of the language server protocol, objects keep the order of their keys
+ *
+ * value  -> null | true | false | number | string | array | object
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn object(items: Vec<(&str, Json)>) -> Json {
+        Json::Object(
+            items
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+
+    pub fn string(text: &str) -> Json {
+        Json::String(text.to_string())
+    }
+
+    /// Value of the key, `Null` if it's missing or this isn't an object
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(items) => items
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v)
+                .unwrap_or(&Json::Null),
+            _ => &Json::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Json>> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{b}"),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) => write!(f, "{n}"),
+            Json::String(text) => write_string(f, text),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(items) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+pub fn parse(text: &str) -> Result<Json, String> {
+    let mut parser = JsonParser {
+        chars: text.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.value()?;
+    parser.skip_blanks();
+    match parser.pos < parser.chars.len() {
+        true => Err(format!("unexpected text at {}", parser.pos)),
+        false => Ok(value),
+    }
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl JsonParser {
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_blanks();
+        match self.chars.get(self.pos) {
+            None => Err("unexpected end of JSON".to_string()),
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('n') => self.keyword("null", Json::Null),
+            Some(_) => self.number(),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_blanks();
+        if self.eat('}') {
+            return Ok(Json::Object(items));
+        }
+        loop {
+            self.skip_blanks();
+            if self.chars.get(self.pos) != Some(&'"') {
+                return Err(format!("key is expected at {}", self.pos));
+            }
+            let key = self.string()?;
+            self.skip_blanks();
+            if !self.eat(':') {
+                return Err(format!("':' is expected at {}", self.pos));
+            }
+            items.push((key, self.value()?));
+            self.skip_blanks();
+            if self.eat('}') {
+                return Ok(Json::Object(items));
+            }
+            if !self.eat(',') {
+                return Err(format!("',' or '}}' is expected at {}", self.pos));
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.pos += 1;
+        let mut items = Vec::new();
+        self.skip_blanks();
+        if self.eat(']') {
+            return Ok(Json::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_blanks();
+            if self.eat(']') {
+                return Ok(Json::Array(items));
+            }
+            if !self.eat(',') {
+                return Err(format!("',' or ']' is expected at {}", self.pos));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut result = String::new();
+        loop {
+            let c = match self.chars.get(self.pos) {
+                None => return Err("string is not closed".to_string()),
+                Some(c) => *c,
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(result),
+                '\\' => {
+                    let escaped = self.chars.get(self.pos).copied();
+                    self.pos += 1;
+                    match escaped {
+                        Some('n') => result.push('\n'),
+                        Some('r') => result.push('\r'),
+                        Some('t') => result.push('\t'),
+                        Some('b') => result.push('\u{8}'),
+                        Some('f') => result.push('\u{c}'),
+                        Some('u') => result.push(self.unicode()?),
+                        Some(c) => result.push(c),
+                        None => return Err("string is not closed".to_string()),
+                    }
+                }
+                c => result.push(c),
+            }
+        }
+    }
+
+    /// \uXXXX after the 'u', a surrogate pair takes two of them
+    fn unicode(&mut self) -> Result<char, String> {
+        let first = self.hex()?;
+        let code = match first {
+            0xD800..=0xDBFF if self.chars.get(self.pos..self.pos + 2) == Some(&['\\', 'u']) => {
+                self.pos += 2;
+                let second = self.hex()?;
+                0x10000 + ((first - 0xD800) << 10) + (second.wrapping_sub(0xDC00) & 0x3FF)
+            }
+            code => code,
+        };
+        Ok(char::from_u32(code).unwrap_or('\u{FFFD}'))
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.iter().skip(self.pos).take(4).collect();
+        self.pos += 4;
+        match digits.len() {
+            4 => {
+                u32::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape \\u{digits}"))
+            }
+            _ => Err("string is not closed".to_string()),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c))
+        {
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse()
+            .map(Json::Number)
+            .map_err(|_| format!("invalid value at {start}"))
+    }
+
+    fn keyword(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let found: String = self.chars.iter().skip(self.pos).take(word.len()).collect();
+        if found != word {
+            return Err(format!("invalid value at {}", self.pos));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.chars.get(self.pos) == Some(&c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_print() {
+        let text =
+            r#"{"id": 3, "params": {"text": "A\tB \"C\" Δ😀", "list": [1, -2.5e1, true, null]}}"#;
+        let json = parse(text).expect("valid JSON");
+
+        assert_eq!(Some(3), json.get("id").as_usize());
+        let params = json.get("params");
+        assert_eq!(Some("A\tB \"C\" Δ😀"), params.get("text").as_str());
+        assert_eq!(
+            Some(&vec![
+                Json::Number(1.0),
+                Json::Number(-25.0),
+                Json::Bool(true),
+                Json::Null
+            ]),
+            params.get("list").as_array()
+        );
+        assert_eq!(&Json::Null, json.get("missing").get("deeper"));
+        assert_eq!(
+            r#"{"id":3,"params":{"text":"A\tB \"C\" Δ😀","list":[1,-25,true,null]}}"#,
+            json.to_string()
+        );
+
+        assert_eq!(
+            Some("\u{394}\u{1F600}"),
+            parse(r#""\u0394\ud83d\ude00""#).expect("escapes").as_str()
+        );
+
+        assert!(parse("{\"a\": 1,}").is_err());
+        assert!(parse("\"\\ud83d").is_err());
+        assert!(parse("[1 2]").is_err());
+        assert!(parse("\"open").is_err());
+        assert!(parse("1 x").is_err());
+    }
+}
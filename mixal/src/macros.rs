@@ -0,0 +1,635 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{is_blank, is_operation, next_word};
+use crate::preprocessor::SourceLine;
+use crate::tags::MixInstructions;
+
+use std::collections::{HashMap, HashSet};
+
+/*
+ * Macros are expanded in the source text before the lines are assembled:
+ *
+ * NAME     MACRO P1,P2=0      parameters with optional defaults
+ * %LOOP    LDA   P1           %LOOP is a label local to each expansion
+ *          JMP   %LOOP
+ *          ENDM
+ *
+ * LABEL    NAME  X,P2=5       arguments by position or by name
+ *
+ * Parameters are replaced as whole words in the label, operation and address of the
+ * body lines, not in the comments. A label of the invocation is defined at the first
+ * expanded line. Bodies can invoke other macros.
+ */
+
+pub const MAX_DEPTH: usize = 16;
+
+struct Macro {
+    name: String,
+    params: Vec<(String, Option<String>)>, // name, default
+    body: Vec<String>,
+}
+
+/// Label, operation and address of a line with the columns of the operation and the address
+pub struct Fields {
+    pub label: String,
+    pub op: String,
+    pub op_column: usize,
+    pub address: (String, usize),
+}
+
+struct Expander<'a> {
+    mix_inst: &'a MixInstructions<'a>,
+    macros: HashMap<String, Macro>,
+    words: HashSet<String>, // all words of the source, local labels don't collide with them
+    expansions: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Expands macros, the result has the lines and the expanded ones in order.
+/// Lines that are not assembled are kept as they are
+pub fn expand(
+    mix_inst: &MixInstructions,
+    lines: &[SourceLine],
+) -> (Vec<SourceLine>, Vec<Diagnostic>) {
+    let mut expander = Expander {
+        mix_inst,
+        macros: HashMap::new(),
+        words: lines.iter().flat_map(|l| words(&l.text)).collect(),
+        expansions: 0,
+        diagnostics: Vec::new(),
+    };
+
+    let in_definitions = expander.collect_definitions(lines);
+
+    let mut result = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if !line.assembled || in_definitions[i] {
+            result.push(SourceLine {
+                assembled: false,
+                ..line.clone()
+            });
+            continue;
+        }
+
+        match expander.invocation(&line.text) {
+            Some(fields) => {
+                result.push(SourceLine {
+                    assembled: false,
+                    ..line.clone()
+                });
+                expander.expand_invocation(&fields, line, 0, &mut result);
+            }
+            None => result.push(line.clone()),
+        }
+    }
+    (result, expander.diagnostics)
+}
+
+impl Expander<'_> {
+    /// Reads MACRO ... ENDM, true for the lines of definitions
+    fn collect_definitions(&mut self, lines: &[SourceLine]) -> Vec<bool> {
+        let mut in_definitions = vec![false; lines.len()];
+        let mut current: Option<(Macro, &SourceLine)> = None; // macro, line of MACRO
+
+        for (i, line) in lines.iter().enumerate() {
+            if !line.assembled {
+                continue;
+            }
+            let text = &line.text;
+            let fields = match fields(text) {
+                None => {
+                    if let Some((m, _)) = &mut current {
+                        m.body.push(text.clone());
+                        in_definitions[i] = true;
+                    }
+                    continue;
+                }
+                Some(fields) => fields,
+            };
+
+            match (&mut current, &fields.op[..]) {
+                (None, "MACRO") => {
+                    in_definitions[i] = true;
+                    match self.new_macro(&fields, line) {
+                        Ok(m) => current = Some((m, line)),
+                        Err(diagnostic) => {
+                            self.diagnostics.push(diagnostic);
+                            // the body is skipped as a nameless macro
+                            let m = Macro {
+                                name: String::new(),
+                                params: Vec::new(),
+                                body: Vec::new(),
+                            };
+                            current = Some((m, line));
+                        }
+                    }
+                }
+                (None, "ENDM") => {
+                    in_definitions[i] = true;
+                    self.diagnostics.push(
+                        line.diagnostic("ENDM without MACRO".to_string())
+                            .with_columns(op_columns(&fields)),
+                    );
+                }
+                (None, _) => {}
+                (Some(_), "MACRO") => {
+                    in_definitions[i] = true;
+                    self.diagnostics.push(
+                        line.diagnostic("macro definitions can't be nested".to_string())
+                            .with_columns(op_columns(&fields)),
+                    );
+                }
+                (Some(_), "ENDM") => {
+                    in_definitions[i] = true;
+                    let (m, _) = current.take().expect("macro");
+                    if !m.name.is_empty() {
+                        self.macros.insert(m.name.clone(), m);
+                    }
+                }
+                (Some((m, _)), _) => {
+                    in_definitions[i] = true;
+                    m.body.push(text.clone());
+                }
+            }
+        }
+
+        if let Some((m, line)) = current {
+            self.diagnostics
+                .push(line.diagnostic(format!("ENDM is missing for macro {}", m.name)));
+        }
+        in_definitions
+    }
+
+    fn new_macro(&self, fields: &Fields, line: &SourceLine) -> Result<Macro, Diagnostic> {
+        let name = fields.label.clone();
+        if name.is_empty() {
+            return Err(line.diagnostic("macro name is missing before MACRO".to_string()));
+        }
+        if is_operation(self.mix_inst, &name) || name == "MACRO" || name == "ENDM" {
+            return Err(line
+                .diagnostic(format!("macro name {name} is an operation"))
+                .with_columns(0..name.chars().count()));
+        }
+        if self.macros.contains_key(&name) {
+            return Err(line
+                .diagnostic(format!("macro {name} is already defined"))
+                .with_columns(0..name.chars().count()));
+        }
+
+        let (address, column) = &fields.address;
+        let mut params: Vec<(String, Option<String>)> = Vec::new();
+        for (param, start) in split_arguments(address) {
+            let (param_name, default) = match param.split_once('=') {
+                Some((n, d)) => (n.to_string(), Some(d.to_string())),
+                None => (param.clone(), None),
+            };
+            let columns = column + start..column + start + param.chars().count();
+            if param_name.is_empty() || !param_name.chars().all(|c| c.is_alphanumeric()) {
+                return Err(line
+                    .diagnostic(format!("invalid parameter '{param}' of macro {name}"))
+                    .with_columns(columns));
+            }
+            if params.iter().any(|(p, _)| *p == param_name) {
+                return Err(line
+                    .diagnostic(format!(
+                        "parameter {param_name} of macro {name} is repeated"
+                    ))
+                    .with_columns(columns));
+            }
+            params.push((param_name, default));
+        }
+
+        Ok(Macro {
+            name,
+            params,
+            body: Vec::new(),
+        })
+    }
+
+    /// Fields of the line if it invokes a macro, the label is empty if there is none
+    fn invocation(&self, text: &str) -> Option<Fields> {
+        let fields = fields(text)?;
+        if self.macros.contains_key(&fields.op) {
+            return Some(fields);
+        }
+        // a macro name at the start of a line without a label, as for operations
+        if self.macros.contains_key(&fields.label) && !self.is_known_op(&fields.op) {
+            return Some(Fields {
+                label: String::new(),
+                op: fields.label,
+                op_column: 0,
+                address: (fields.op, fields.op_column),
+            });
+        }
+        None
+    }
+
+    fn is_known_op(&self, op: &str) -> bool {
+        is_operation(self.mix_inst, op) || self.macros.contains_key(op)
+    }
+
+    /// `line` is the invocation in the source, the expanded lines get its place
+    fn expand_invocation(
+        &mut self,
+        fields: &Fields,
+        line: &SourceLine,
+        depth: usize,
+        result: &mut Vec<SourceLine>,
+    ) {
+        let m = &self.macros[&fields.op];
+        let (name, body) = (m.name.clone(), m.body.clone());
+        let bound = bind_arguments(m, &fields.address);
+        if depth >= MAX_DEPTH {
+            self.diagnostics.push(line.diagnostic(format!(
+                "macro {name} is nested too deep, the limit is {MAX_DEPTH} levels"
+            )));
+            return;
+        }
+
+        let values = match bound {
+            Ok(values) => values,
+            Err((message, columns)) => {
+                // columns are known only in the source line itself
+                let diagnostic = line.diagnostic(message);
+                self.diagnostics.push(match depth {
+                    0 => diagnostic.with_columns(columns),
+                    _ => diagnostic,
+                });
+                return;
+            }
+        };
+
+        self.expansions += 1;
+        let expansion = self.expansions;
+        let mut locals: HashMap<String, String> = HashMap::new();
+        let generated_line = |text: String| SourceLine {
+            text,
+            macro_name: Some(name.clone()),
+            assembled: true,
+            ..line.clone()
+        };
+
+        if !fields.label.is_empty() {
+            result.push(generated_line(format!("{:10} {:4} *", fields.label, "EQU")));
+        }
+        for body_line in body {
+            let text = substitute_line(&body_line, &values, |local| {
+                if !locals.contains_key(local) {
+                    let unique = self.local_name(local, expansion);
+                    locals.insert(local.to_string(), unique);
+                }
+                locals[local].clone()
+            });
+
+            match self.invocation(&text) {
+                Some(inner) => {
+                    result.push(SourceLine {
+                        assembled: false,
+                        ..generated_line(text)
+                    });
+                    self.expand_invocation(&inner, line, depth + 1, result);
+                }
+                None => result.push(generated_line(text)),
+            }
+        }
+    }
+
+    /// %NAME of an expansion, NAME with a suffix that isn't in the source
+    fn local_name(&self, local: &str, expansion: usize) -> String {
+        let mut suffix = format!("M{expansion}");
+        while self.words.contains(&format!("{local}{suffix}")) {
+            suffix.insert(0, 'M');
+        }
+        format!("{local}{suffix}")
+    }
+}
+
+/// Values of the parameters, an error has the columns in the address
+fn bind_arguments(
+    m: &Macro,
+    address: &(String, usize),
+) -> Result<HashMap<String, String>, (String, std::ops::Range<usize>)> {
+    let (text, column) = address;
+    let mut values: HashMap<String, String> = HashMap::new();
+    let mut position = 0;
+
+    for (argument, start) in split_arguments(text) {
+        let columns = column + start..column + start + argument.chars().count().max(1);
+        let named = argument
+            .split_once('=')
+            .filter(|(n, _)| m.params.iter().any(|(p, _)| p == n));
+        let (param, value) = match named {
+            Some((n, v)) => (n.to_string(), v.to_string()),
+            None => match m.params.get(position) {
+                Some((p, _)) => {
+                    position += 1;
+                    (p.clone(), argument)
+                }
+                None => {
+                    return Err((
+                        format!("macro {} has {} parameters", m.name, m.params.len()),
+                        columns,
+                    ))
+                }
+            },
+        };
+        if values.insert(param.clone(), value).is_some() {
+            return Err((
+                format!("argument {param} of macro {} is given twice", m.name),
+                columns,
+            ));
+        }
+    }
+
+    for (param, default) in &m.params {
+        if values.contains_key(param) {
+            continue;
+        }
+        match default {
+            Some(d) => {
+                values.insert(param.clone(), d.clone());
+            }
+            None => {
+                let end = column + text.chars().count();
+                return Err((
+                    format!("argument {param} of macro {} is missing", m.name),
+                    end..end + 1,
+                ));
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Arguments separated by commas outside of parentheses, with their columns
+fn split_arguments(text: &str) -> Vec<(String, usize)> {
+    let mut result = Vec::new();
+    if text.is_empty() {
+        return result;
+    }
+
+    let mut depth = 0;
+    let mut current = String::new();
+    let mut start = 0;
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push((std::mem::take(&mut current), start));
+                start = i + 1;
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    result.push((current, start));
+    result
+}
+
+/// Replaces the parameters and the %local labels in the label, operation and address
+/// of a body line, the comment is kept as it is. A field keeps its column if the one
+/// before still ends in front of it, so a line in card columns stays in them
+fn substitute_line(
+    text: &str,
+    values: &HashMap<String, String>,
+    mut local: impl FnMut(&str) -> String,
+) -> String {
+    let fields = match fields(text) {
+        Some(fields) => fields,
+        None => return text.to_string(),
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let (address, address_column) = &fields.address;
+    let address_end = address_column + address.chars().count();
+    let comment_column = (address_end..chars.len())
+        .find(|i| !is_blank(chars[*i]))
+        .unwrap_or(chars.len());
+    let comment: String = chars[comment_column..].iter().collect();
+
+    let pieces = [
+        (0, substitute(&fields.label, values, &mut local)),
+        (fields.op_column, substitute(&fields.op, values, &mut local)),
+        (*address_column, substitute(address, values, &mut local)),
+        (comment_column, comment),
+    ];
+    let ends = [
+        fields.label.chars().count(),
+        fields.op_column + fields.op.chars().count(),
+        address_end,
+    ];
+
+    let mut result = String::new();
+    let mut previous_end = 0; // in the body line
+    for (i, (column, piece)) in pieces.into_iter().enumerate() {
+        if piece.is_empty() {
+            continue;
+        }
+        let length = result.chars().count();
+        if length == previous_end {
+            result.extend(&chars[previous_end..column]);
+        } else if length < column {
+            result.push_str(&" ".repeat(column - length));
+        } else {
+            result.push(' ');
+        }
+        result.push_str(&piece);
+        previous_end = ends.get(i).copied().unwrap_or(chars.len());
+    }
+    result
+}
+
+/// Replaces the parameters and the %local labels in a field
+fn substitute(
+    text: &str,
+    values: &HashMap<String, String>,
+    mut local: impl FnMut(&str) -> String,
+) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let is_local = chars[i] == '%';
+        let start = if is_local { i + 1 } else { i };
+        let end = (start..chars.len())
+            .find(|j| !chars[*j].is_alphanumeric())
+            .unwrap_or(chars.len());
+        if end == start {
+            result.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        let word: String = chars[start..end].iter().collect();
+        match values.get(&word) {
+            _ if is_local => result.push_str(&local(&word)),
+            Some(value) => result.push_str(value),
+            None => result.push_str(&word),
+        }
+        i = end;
+    }
+    result
+}
+
+/// Fields of a line in free format, None for comments and empty lines
+pub fn fields(text: &str) -> Option<Fields> {
+    if text.trim().is_empty() || text.starts_with('*') {
+        return None;
+    }
+    let chars: Vec<char> = text.chars().collect();
+    let word = |(s, e): (usize, usize)| chars[s..e].iter().collect::<String>();
+
+    let mut from = 0;
+    let label = match is_blank(chars[0]) {
+        true => String::new(),
+        false => {
+            let (s, e) = next_word(&chars, 0)?;
+            from = e;
+            word((s, e))
+        }
+    };
+    let (op, op_column, op_end) = match next_word(&chars, from) {
+        Some(w) => (word(w), w.0, w.1),
+        None => (String::new(), chars.len(), chars.len()),
+    };
+    let address = match next_word(&chars, op_end) {
+        Some(w) => (word(w), w.0),
+        None => (String::new(), chars.len()),
+    };
+    Some(Fields {
+        label,
+        op,
+        op_column,
+        address,
+    })
+}
+
+fn op_columns(fields: &Fields) -> std::ops::Range<usize> {
+    fields.op_column..fields.op_column + fields.op.chars().count()
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_text(source: &str) -> (Vec<String>, Vec<Diagnostic>) {
+        let mix_inst = MixInstructions::new();
+        let lines: Vec<SourceLine> = source
+            .lines()
+            .enumerate()
+            .map(|(i, l)| SourceLine::new(l.to_string(), None, i + 1))
+            .collect();
+        let (expanded, diagnostics) = expand(&mix_inst, &lines);
+        let assembled = expanded
+            .iter()
+            .filter(|l| l.assembled)
+            .map(|l| format!("{} {}", l.line, l.text))
+            .collect();
+        (assembled, diagnostics)
+    }
+
+    #[test]
+    fn expand_macros() {
+        let source = "SWAP     MACRO A,B,T=TEMP\n\
+                      %TOP     LDA   A\n\
+                      \x20        LDX   B\n\
+                      \x20        STA   T\n\
+                      \x20        JMP   %TOP\n\
+                      \x20        ENDM\n\
+                      TWICE    MACRO X\n\
+                      \x20        SWAP  X,Y\n\
+                      \x20        SWAP  B=X,A=Y,T=Z\n\
+                      \x20        ENDM\n\
+                      START    SWAP  ONE,TWO\n\
+                      \x20        TWICE 1000\n\
+                      \x20        HLT\n";
+        let (lines, diagnostics) = expand_text(source);
+        assert_eq!(Vec::<Diagnostic>::new(), diagnostics);
+        assert_eq!(
+            vec![
+                "11 START      EQU  *",
+                "11 TOPM1    LDA   ONE",
+                "11          LDX   TWO",
+                "11          STA   TEMP",
+                "11          JMP   TOPM1",
+                "12 TOPM3    LDA   1000",
+                "12          LDX   Y",
+                "12          STA   TEMP",
+                "12          JMP   TOPM3",
+                "12 TOPM4    LDA   Y",
+                "12          LDX   1000",
+                "12          STA   Z",
+                "12          JMP   TOPM4",
+                "13          HLT",
+            ],
+            lines
+        );
+
+        // the name is at the start of the line as an operation without a label
+        let (lines, _) = expand_text("M MACRO\n NOP\n ENDM\nM\nTOPM1 M\n");
+        assert_eq!(vec!["4  NOP", "5 TOPM1      EQU  *", "5  NOP"], lines);
+    }
+
+    #[test]
+    fn card_columns() {
+        // comments aren't changed, the fields stay in their columns
+        let source = "INCR       MACRO R,N=1\n\
+                      %L         INC1 N           ADD N TO I1\n\
+                      \x20          ST1  R           SAVE R\n\
+                      \x20          ENDM\n\
+                      START      INCR X\n\
+                      \x20          INCR COUNTER,N=1000\n\
+                      \x20          INCR VERYLONGNAME\n";
+        let (lines, diagnostics) = expand_text(source);
+        assert_eq!(Vec::<Diagnostic>::new(), diagnostics);
+        assert_eq!(
+            vec![
+                "5 START      EQU  *",
+                "5 LM1        INC1 1           ADD N TO I1",
+                "5            ST1  X           SAVE R",
+                "6 LM2        INC1 1000        ADD N TO I1",
+                "6            ST1  COUNTER     SAVE R",
+                "7 LM3        INC1 1           ADD N TO I1",
+                "7            ST1  VERYLONGNAME SAVE R",
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn macro_errors() {
+        let source = " MACRO\n ENDM\nLDA MACRO\n ENDM\n ENDM\n\
+                      M MACRO A,B=1,A\n ENDM\n\
+                      P MACRO A,B=1\n NOP\n ENDM\n\
+                      \x20P\n P 1,2,3\n P 1,A=2\n\
+                      R MACRO\n R\n ENDM\n R\n\
+                      U MACRO\n";
+        let (_, diagnostics) = expand_text(source);
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.line, &d.message[..]))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, "macro name is missing before MACRO"),
+                (3, "macro name LDA is an operation"),
+                (5, "ENDM without MACRO"),
+                (6, "parameter A of macro M is repeated"),
+                (18, "ENDM is missing for macro U"),
+                (11, "argument A of macro P is missing"),
+                (12, "macro P has 2 parameters"),
+                (13, "argument A of macro P is given twice"),
+                (17, "macro R is nested too deep, the limit is 16 levels"),
+            ],
+            messages
+        );
+        assert_eq!(Some(7..8), diagnostics[6].columns);
+    }
+}
@@ -1,76 +1,232 @@
-use crate::lexer::*;
-use crate::parser::*;
-use crate::parser::*;
-use crate::pseudo_op::*;
-use crate::tags::*;
-
-use std::fs::File;
-use std::io;
-use std::io::prelude::*;
-use std::io::BufReader;
+use crate::cli::{OutputFormat, Verbosity};
+use mixal::diagnostic::Diagnostics;
+use mixal::linker::link;
+use mixal::object::{parse_object, to_object};
+use mixal::{assemble, with_included, Options};
+use mixal::{deck, formatter, image, lsp, xref};
 
 use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
 
-mod lexer;
-mod parser;
-mod pseudo_op;
-mod tags;
+mod cli;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    let program_path = &args[1];
-    if !program_path.ends_with(".mixal") {
-        panic!("The MIXAL source program should end with '.mixal'")
+    // mixal link module.mixo... [-o program.mixo] [--base ADDR]
+    if args.first().map(|a| &a[..]) == Some("link") {
+        link_objects(&args[1..]);
+        return;
+    }
+    // mixal lsp, the language server for editors
+    if args.first().map(|a| &a[..]) == Some("lsp") {
+        if let Err(err) = lsp::run(&mut io::stdin().lock(), &mut io::stdout().lock()) {
+            eprintln!("mixal lsp: {err}");
+            process::exit(1);
+        }
+        return;
+    }
+    // mixal fmt [--check] program.mixal, the source is rewritten in canonical columns
+    if args.first().map(|a| &a[..]) == Some("fmt") {
+        format_source(&args[1..]);
+        return;
     }
 
-    compile(program_path);
-}
+    let options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("mixal: {err}\n\n{}", cli::USAGE);
+            process::exit(1);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
-fn compile(path: &str) {
-    let mix_inst = MixInstructions::new();
-    let sourse = read_programm(path);
+    compile(&options);
+}
 
-    let lexer = Lexer::new();
-    let tokens = lexer.parse_program_lines(&mix_inst, sourse);
+fn compile(options: &cli::Options) {
+    let path = &options.source;
+    let sourse = match fs::read_to_string(path) {
+        Ok(sourse) => sourse,
+        Err(err) => {
+            eprintln!("mixal: can't read {path}: {err}");
+            process::exit(1);
+        }
+    };
+
+    let program = match assemble(&sourse, &assembler_options(options)) {
+        Ok(program) => program,
+        Err(diagnostics) => {
+            eprint!("{diagnostics}");
+            eprintln!("{} error(s)", diagnostics.errors());
+            process::exit(1);
+        }
+    };
+    if !program.warnings.is_empty() && options.verbosity != Verbosity::Quiet {
+        let warnings = Diagnostics {
+            items: program.warnings.clone(),
+            ..with_included(
+                Diagnostics::new(path).with_source(&sourse),
+                &program.included,
+            )
+        };
+        eprint!("{warnings}");
+    }
 
-    let parser = Parser::new();
+    let output = options.output_path();
+    let contents = match options.format {
+        OutputFormat::Text => (program.lines.join("\n") + "\n").into_bytes(),
+        OutputFormat::Object => to_object(&program, true),
+        OutputFormat::Deck => {
+            let name = Path::new(path).file_stem().and_then(|s| s.to_str());
+            match deck::to_deck(&program, name.unwrap_or_default()) {
+                Ok(cards) => (cards.join("\n") + "\n").into_bytes(),
+                Err(message) => {
+                    eprintln!("{path}: error: {message}");
+                    process::exit(1);
+                }
+            }
+        }
+        OutputFormat::Json => image::to_json(&program).into_bytes(),
+    };
+    let mut files = vec![(output, contents)];
 
-    let lines = parser.parse(tokens);
+    if options.listing {
+        let listing = program.listing.join("\n") + "\n";
+        files.push((options.companion_path("lst"), listing.into_bytes()));
+    }
+    if options.symbols {
+        let symbols = xref::to_text(&program.symbols);
+        files.push((options.companion_path("sym"), symbols.into_bytes()));
+    }
+    if options.symbols_json {
+        let symbols = xref::to_json(&program.symbols);
+        files.push((options.companion_path("sym.json"), symbols.into_bytes()));
+    }
 
-    write_programm(path.to_string().replace(".mixal", ".mix"), lines);
+    for (file, contents) in files {
+        if let Err(err) = fs::write(&file, contents) {
+            eprintln!("mixal: can't write {file}: {err}");
+            process::exit(1);
+        }
+        if options.verbosity == Verbosity::Verbose {
+            eprintln!("mixal: wrote {file}");
+        }
+    }
 }
 
-fn write_programm(path: String, lines: Vec<String>) -> io::Result<()> {
-    let mut file = File::options().create(true).append(true).open(path)?;
-
-    for line in lines {
-        let mut line = line;
-        line += &"\n";
-
-        // println!("{line}");
-        file.write_all(&line.as_bytes())?;
+fn assembler_options(options: &cli::Options) -> Options {
+    Options {
+        format: options.source_format,
+        include_paths: options.include_paths.clone(),
+        defines: options.defines.clone(),
+        relocatable: options.module,
+        ..Options::new(&options.source)
     }
-
-    Ok(())
 }
 
-fn read_programm(path: &str) -> Vec<String> {
-    let file = File::open(path.to_string()).expect(&("file not found ".to_owned() + path));
-    let reader = BufReader::new(file);
-    let mut result = Vec::new();
+/// Formats the source in place or to -o, --check only tells if it's formatted
+fn format_source(args: &[String]) {
+    let check = args.iter().any(|a| a == "--check");
+    let args: Vec<String> = args.iter().filter(|a| *a != "--check").cloned().collect();
+    let options = match cli::parse_args(&args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("mixal fmt: {err}\n\n{}", cli::USAGE);
+            process::exit(1);
+        }
+    };
+    let path = &options.source;
+    let sourse = match fs::read_to_string(path) {
+        Ok(sourse) => sourse,
+        Err(err) => {
+            eprintln!("mixal: can't read {path}: {err}");
+            process::exit(1);
+        }
+    };
+
+    let formatted = match formatter::format(&sourse, &assembler_options(&options)) {
+        Ok(formatted) => formatted,
+        Err(diagnostics) => {
+            eprint!("{diagnostics}");
+            eprintln!("{} error(s)", diagnostics.errors());
+            process::exit(1);
+        }
+    };
+    if check {
+        if formatted != sourse {
+            eprintln!("{path}: not formatted");
+            process::exit(1);
+        }
+        return;
+    }
 
-    let lines = reader.lines();
-    for line in lines {
-        let line = line.expect("some err in lines");
+    let output = options.output.as_ref().unwrap_or(path);
+    if let Err(err) = fs::write(output, formatted) {
+        eprintln!("mixal: can't write {output}: {err}");
+        process::exit(1);
+    }
+    if options.verbosity == Verbosity::Verbose {
+        eprintln!("mixal: wrote {output}");
+    }
+}
 
-        if line.len() <= 0 {
-            continue;
+fn link_objects(args: &[String]) {
+    let options = match cli::parse_link_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("mixal link: {err}\n\n{}", cli::USAGE);
+            process::exit(1);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+    let output = &options.output;
+
+    let mut objects = Vec::new();
+    for path in &options.modules {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(err) => {
+                eprintln!("mixal link: can't read {path}: {err}");
+                process::exit(1);
+            }
+        };
+        match parse_object(&bytes) {
+            Ok(object) => objects.push((path.clone(), object)),
+            Err(message) => {
+                eprintln!("{path}: error: {message}");
+                process::exit(1);
+            }
         }
-        result.push(line.to_string());
-
-        // println!("{line}");
     }
 
-    result
+    let program = match link(&objects, options.base) {
+        Ok(program) => program,
+        Err(items) => {
+            let diagnostics = Diagnostics {
+                items,
+                ..Diagnostics::new(output)
+            };
+            eprint!("{diagnostics}");
+            eprintln!("{} error(s)", diagnostics.errors());
+            process::exit(1);
+        }
+    };
+    let contents = match output.ends_with(".mix") {
+        true => (program.lines.join("\n") + "\n").into_bytes(),
+        false => to_object(&program, false),
+    };
+    if let Err(err) = fs::write(output, contents) {
+        eprintln!("mixal link: can't write {output}: {err}");
+        process::exit(1);
+    }
 }
@@ -0,0 +1,332 @@
+use crate::parser::relocation::{Entry, Field, Module};
+use crate::parser::symbol_table::SymbolKind;
+use crate::parser::{Assembly, MEMORY_SIZE};
+use mix_core::object::*;
+use mix_core::word::Word;
+
+// the format of the object file is described in mix_core::object
+
+/// Object file read back for the linker
+#[derive(Debug, Clone, PartialEq)]
+pub struct Object {
+    pub start: u32,
+    pub segments: Vec<(u32, Vec<Word>)>,
+    pub module: Option<Module>,
+}
+
+pub fn to_object(program: &Assembly, with_debug_info: bool) -> Vec<u8> {
+    let mut words = program.words.to_vec();
+    words.sort_by_key(|(addr, _)| *addr);
+
+    // consecutive addresses are packed in one segment
+    let mut segments: Vec<(u32, Vec<u32>)> = Vec::new();
+    for (addr, word) in words {
+        match segments.last_mut() {
+            Some((start, seg_words)) if *start + seg_words.len() as u32 == addr => {
+                seg_words.push(word.get())
+            }
+            Some((start, seg_words)) if *start + seg_words.len() as u32 == addr + 1 => {
+                // the same address is assembled twice, the last word wins
+                *seg_words.last_mut().expect("error") = word.get();
+            }
+            _ => segments.push((addr, vec![word.get()])),
+        }
+    }
+
+    let mut result: Vec<u8> = Vec::new();
+    result.extend_from_slice(MAGIC);
+    put_u16(&mut result, VERSION);
+    put_addr(&mut result, program.start);
+    put_addr(&mut result, segments.len() as u32);
+
+    for (addr, seg_words) in segments {
+        put_addr(&mut result, addr);
+        put_addr(&mut result, seg_words.len() as u32);
+        for w in seg_words {
+            put_u32(&mut result, w);
+        }
+    }
+
+    if with_debug_info {
+        let mut symbols: Vec<u8> = Vec::new();
+        for symbol in &program.symbols {
+            symbols.push(match symbol.kind {
+                SymbolKind::Equ => 0,
+                SymbolKind::Label => 1,
+                SymbolKind::Local => 2,
+                SymbolKind::Literal => 3,
+                SymbolKind::External => 4,
+            });
+            put_u32(&mut symbols, symbol.value as u32);
+            put_name(&mut symbols, &symbol.name);
+        }
+        put_section(&mut result, SECTION_SYMBOLS, symbols);
+
+        let mut lines: Vec<u8> = Vec::new();
+        for (addr, line_num) in &program.line_nums {
+            put_addr(&mut lines, *addr);
+            put_u32(&mut lines, *line_num as u32);
+        }
+        put_section(&mut result, SECTION_LINES, lines);
+    }
+
+    if let Some(module) = &program.module {
+        let mut header: Vec<u8> = Vec::new();
+        put_addr(&mut header, module.size);
+        header.push(module.has_start as u8);
+        put_section(&mut result, SECTION_MODULE, header);
+
+        let mut relocations: Vec<u8> = Vec::new();
+        for (addr, field) in &module.relocations {
+            put_addr(&mut relocations, *addr);
+            relocations.push(field_id(*field));
+        }
+        put_section(&mut result, SECTION_RELOCATIONS, relocations);
+
+        let mut entries: Vec<u8> = Vec::new();
+        for entry in &module.entries {
+            entries.push(entry.relative as u8);
+            put_u32(&mut entries, entry.value as u32);
+            put_name(&mut entries, &entry.name);
+        }
+        put_section(&mut result, SECTION_ENTRIES, entries);
+
+        let mut externals: Vec<u8> = Vec::new();
+        for (addr, field, name) in &module.externals {
+            put_addr(&mut externals, *addr);
+            externals.push(field_id(*field));
+            put_name(&mut externals, name);
+        }
+        put_section(&mut result, SECTION_EXTERNALS, externals);
+    }
+
+    result
+}
+
+/// Reads the words and the linking information, debug sections are skipped
+pub fn parse_object(bytes: &[u8]) -> Result<Object, String> {
+    let mut r = Reader { bytes, pos: 0 };
+
+    if bytes.len() < 4 || &bytes[..4] != MAGIC {
+        return Err("not a MIX object file".to_string());
+    }
+    r.take(4)?;
+    let version = r.u16()?;
+    if version != VERSION {
+        return Err(format!("unsupported object version {version}"));
+    }
+    let start = r.u16()? as u32;
+    if start >= MEMORY_SIZE {
+        return Err(format!("start address {start} is out of range 0-3999"));
+    }
+
+    let mut segments = Vec::new();
+    for _ in 0..r.u16()? {
+        let addr = r.u16()? as u32;
+        let count = r.u16()?;
+        if addr + count as u32 > MEMORY_SIZE {
+            return Err(format!("words at {addr} are out of range 0-3999"));
+        }
+        let mut words = Vec::new();
+        for _ in 0..count {
+            words.push(Word::new(r.u32()?));
+        }
+        segments.push((addr, words));
+    }
+
+    let mut module: Option<Module> = None;
+    while r.pos < bytes.len() {
+        let id = r.u8()?;
+        let len = r.u32()? as usize;
+        let mut section = Reader {
+            bytes: r.take(len)?,
+            pos: 0,
+        };
+        let module = match id {
+            SECTION_MODULE..=SECTION_EXTERNALS => module.get_or_insert_with(Module::new),
+            _ => continue,
+        };
+
+        while section.pos < section.bytes.len() {
+            match id {
+                SECTION_MODULE => {
+                    let size = section.u16()? as u32;
+                    if size > MEMORY_SIZE {
+                        return Err(format!("module of {size} words doesn't fit in memory"));
+                    }
+                    module.size = size;
+                    module.has_start = section.u8()? != 0;
+                }
+                SECTION_RELOCATIONS => {
+                    let addr = section.u16()? as u32;
+                    module.relocations.push((addr, section.field()?));
+                }
+                SECTION_ENTRIES => {
+                    let relative = section.u8()? != 0;
+                    let value = section.u32()? as i32;
+                    let name = section.name()?;
+                    module.entries.push(Entry {
+                        name,
+                        value,
+                        relative,
+                    });
+                }
+                _ => {
+                    let addr = section.u16()? as u32;
+                    let field = section.field()?;
+                    module.externals.push((addr, field, section.name()?));
+                }
+            }
+        }
+    }
+
+    Ok(Object {
+        start,
+        segments,
+        module,
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.pos + n > self.bytes.len() {
+            return Err(format!("object file is truncated at byte {}", self.pos));
+        }
+        let result = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(result)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let b = self.take(2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let b = self.take(4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let len = self.u8()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).to_string())
+    }
+
+    fn field(&mut self) -> Result<Field, String> {
+        match self.u8()? {
+            0 => Ok(Field::Address),
+            1 => Ok(Field::Word),
+            f => Err(format!("unknown relocated field {f}")),
+        }
+    }
+}
+
+fn field_id(field: Field) -> u8 {
+    match field {
+        Field::Address => 0,
+        Field::Word => 1,
+    }
+}
+
+fn put_name(result: &mut Vec<u8>, name: &str) {
+    result.push(name.len() as u8);
+    result.extend_from_slice(name.as_bytes());
+}
+
+fn put_section(result: &mut Vec<u8>, id: u8, payload: Vec<u8>) {
+    result.push(id);
+    put_u32(result, payload.len() as u32);
+    result.extend(payload);
+}
+
+/// Address, size or count of words, they are 0-4000: the assembler and the linker
+/// report the words outside the memory
+fn put_addr(result: &mut Vec<u8>, value: u32) {
+    assert!(value <= MEMORY_SIZE, "{value} is out of the memory");
+    put_u16(result, value as u16);
+}
+
+fn put_u16(result: &mut Vec<u8>, value: u16) {
+    result.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(result: &mut Vec<u8>, value: u32) {
+    result.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::symbol_table::Symbol;
+    use mix_core::word::Word;
+
+    #[test]
+    fn object_segments() {
+        let program = Assembly {
+            start: 3000,
+            lines: Vec::new(),
+            words: vec![
+                (3000, Word::new(1)),
+                (3001, Word::new_from_signed(-2)),
+                (10, Word::new(3)),
+            ],
+            line_nums: vec![(3000, 7)],
+            symbols: vec![Symbol {
+                name: "X".to_string(),
+                kind: SymbolKind::Equ,
+                value: -5,
+                line: 3,
+                uses: vec![7],
+            }],
+            listing: Vec::new(),
+            warnings: Vec::new(),
+            included: Vec::new(),
+            module: None,
+        };
+
+        let object = to_object(&program, false);
+        assert_eq!(
+            vec![
+                b'M', b'I', b'X', b'O', 0, 1, 0x0B, 0xB8, 0, 2, // header
+                0, 10, 0, 1, 0, 0, 0, 3, // segment 10
+                0x0B, 0xB8, 0, 2, 0, 0, 0, 1, 0x80, 0, 0, 2, // segment 3000
+            ],
+            object
+        );
+
+        let object = to_object(&program, true);
+        assert_eq!(
+            vec![
+                SECTION_SYMBOLS, 0, 0, 0, 7, 0, 0xFF, 0xFF, 0xFF, 0xFB, 1, b'X', // symbols
+                SECTION_LINES, 0, 0, 0, 6, 0x0B, 0xB8, 0, 0, 0, 7, // lines
+            ],
+            object[30..].to_vec()
+        );
+    }
+
+    #[test]
+    fn out_of_memory() {
+        let header = [b'M', b'I', b'X', b'O', 0, 1];
+        let object = |rest: &[u8]| parse_object(&[&header[..], rest].concat());
+
+        assert_eq!(
+            Err("start address 4000 is out of range 0-3999".to_string()),
+            object(&[0x0F, 0xA0, 0, 0])
+        );
+        // 2 words at 3999
+        assert_eq!(
+            Err("words at 3999 are out of range 0-3999".to_string()),
+            object(&[0, 0, 0, 1, 0x0F, 0x9F, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2])
+        );
+        assert!(object(&[0, 0, 0, 1, 0x0F, 0x9F, 0, 1, 0, 0, 0, 1]).is_ok());
+    }
+}
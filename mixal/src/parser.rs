@@ -1,14 +1,25 @@
+use crate::diagnostic::Diagnostic;
 use crate::lexer::token::*;
 use crate::lexer::*;
 use crate::parser::addr_parser::*;
+use crate::parser::expr::*;
+use crate::parser::literal_pool::*;
+use crate::parser::relocation::*;
 use crate::parser::symbol_table::*;
 use crate::pseudo_op::*;
 use crate::tags::*;
-use crate::word::*;
+use mix_core::word::Word;
+use mix_core::word::SIGN;
+use mix_core::word_access::WordAccess;
+use mix_core::Bytes;
 
 pub mod addr_parser;
+pub mod expr;
+pub mod literal_pool;
+pub mod relocation;
 pub mod symbol_table;
-pub mod word;
+
+pub(crate) const MEMORY_SIZE: u32 = 4000;
 
 /*
  * program      -> lines
@@ -58,113 +69,262 @@ pub trait Printable {
     fn print(&self) -> String;
 }
 
-pub struct Parser {}
+/// Assembled program: `.mix` text lines and the same memory contents as words
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    pub start: u32,
+    pub lines: Vec<String>,
+    pub words: Vec<(u32, Word)>,
+    pub line_nums: Vec<(u32, usize)>, // address -> source line
+    pub symbols: Vec<Symbol>,
+    pub listing: Vec<String>,
+    pub warnings: Vec<Diagnostic>,
+    pub included: Vec<(String, String)>, // included files, name and source
+    pub module: Option<Module>,          // linking information of a relocatable module
+}
+
+pub struct Parser {
+    relocatable: bool,
+    locations: Vec<String>, // where the lines are in the sources, for messages about another line
+}
 impl Parser {
     pub fn new() -> Parser {
-        Parser {}
+        Parser {
+            relocatable: false,
+            locations: Vec::new(),
+        }
+    }
+
+    /// Parser of a relocatable module, its addresses start at 0 and the linker
+    /// moves them to the base of the module
+    pub fn relocatable() -> Parser {
+        Parser {
+            relocatable: true,
+            ..Parser::new()
+        }
+    }
+
+    /// Locations of the lines in the sources, e.g. `line 6` or `inc/lib.mixal:2`,
+    /// a message that names another line uses them instead of the line number
+    pub fn with_locations(mut self, locations: Vec<String>) -> Parser {
+        self.locations = locations;
+        self
+    }
+
+    fn location(&self, line: usize) -> String {
+        match line.checked_sub(1).and_then(|i| self.locations.get(i)) {
+            Some(location) => location.clone(),
+            None => format!("line {line}"),
+        }
     }
     //1. Cycle 1
     //  - reduce w_value for mixal
     //  - process not printable mixal operations
     //  - for mix operations reduce line address (ORIG)
-    //  - implement literal constant = = -> con1 CON ...
+    //  - literal constant =W= -> symbol of a constant in the literal pool, the pool is placed at END
     //  - remove not printable  mixal operation, in cycle 2 there are only printable operations
     pub fn parse_not_printable<'a>(
+        &self,
         symbols: &mut SymbolTable,
-        lines: Vec<ProgramLine<'a>>
-        ) -> (u32, Vec<ProgramLine<'a>>, Vec<u32>) {
-        let mut lines = lines.to_vec();
-
-        let mut program_start_addr = 0;
+        lines: Vec<ProgramLine<'a>>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> (Option<u32>, Vec<ProgramLine<'a>>, Vec<u32>) {
+        let mut program_start_addr = None;
         let mut mix_lines: Vec<ProgramLine<'a>> = Vec::new();
         let mut addresses: Vec<u32> = Vec::new();
 
-        let mut l_con_inx = 1;
+        let mut pool = LiteralPool::new();
+        let mut failed_literals: Vec<(usize, Vec<Token>, Diagnostic)> = Vec::new();
         let mut addr = 0;
-        let mut line_num = 0;
+        let mut end_line: Option<ProgramLine<'a>> = None;
 
-        let mut i = 0;
-        loop {
-            if i >= lines.len() {
+        for line in &lines {
+            if let Some(end) = &end_line {
+                diagnostics.push(Diagnostic::warning(
+                    line.line_num,
+                    format!(
+                        "the line after END at {} is ignored",
+                        self.location(end.line_num)
+                    ),
+                ));
                 break;
             }
 
-            let line = lines.get(i).expect("error");
-
+            let mixal_op = match line.op.get_tag() {
+                Tag::MIXAL_OP => line.op.get_mixal_op().get_name(),
+                _ => String::new(),
+            };
             if !line.loc.get_symbols().is_empty() {
-                symbols.put_reference(line.loc.get_symbols(), addr);
+                if let Err(message) = check_symbol(&line.loc.get_symbols()) {
+                    diagnostics.push(
+                        Diagnostic::new(line.line_num, message)
+                            .with_columns(line.loc.get_columns()),
+                    );
+                }
+            }
+            // the label of END is defined after the literal pool
+            if !line.loc.get_symbols().is_empty() && mixal_op != "EQU" && mixal_op != "END" {
+                Parser::define_label(symbols, &line.loc, line.line_num, addr, diagnostics);
+            }
+
+            for t in &line.addr {
+                if t.get_tag() == Tag::SYMBOLS && line.line_num != 0 {
+                    symbols.put_use(t.get_symbols(), line.line_num);
+                }
             }
 
             match line.op.get_tag() {
-                Tag::MIX_OP => match line.addr.get(0) {
-                    None => {
-                        mix_lines.push(line.clone());
-                        addresses.push(addr);
-                        addr += 1;
-                        line_num += 1;
+                Tag::MIX_OP => {
+                    let mut mix_line = line.clone();
+                    if line.addr.first().map(|t| t.get_tag()) == Some(Tag::EQUAL) {
+                        mix_line.addr = match split_literal(&line.addr) {
+                            Ok((w_tokens, rest)) => {
+                                let mut add_parser =
+                                    AddrParser::new(symbols, addr, &w_tokens)
+                                        .at_line(line.line_num);
+                                let value = match add_parser.w_value(Vec::new()) {
+                                    Ok(w_value) if add_parser.relocation() != Relocation::Absolute => {
+                                        // the pool keeps values, not their relocation
+                                        diagnostics.push(
+                                            add_parser
+                                                .error_between(
+                                                    "literal constant can't be relocatable, use CON"
+                                                        .to_string(),
+                                                    0,
+                                                )
+                                                .with_line(line.line_num),
+                                        );
+                                        w_value_to_word(w_value)
+                                    }
+                                    Ok(w_value) => w_value_to_word(w_value),
+                                    Err(diagnostic) => {
+                                        failed_literals.push((
+                                            line.line_num,
+                                            w_tokens.to_vec(),
+                                            diagnostic.with_line(line.line_num),
+                                        ));
+                                        Word::new(0)
+                                    }
+                                };
+
+                                let (name, is_new) = pool.name_of(value);
+                                let literal = &line.addr[..line.addr.len() - rest.len()];
+                                if is_new {
+                                    let text: String = literal.iter().map(|t| t.to_text()).collect();
+                                    symbols.put_literal(name.clone(), text, line.line_num);
+                                }
+                                symbols.put_use(name.clone(), line.line_num);
+
+                                let columns = literal[0].get_columns().start
+                                    ..literal[literal.len() - 1].get_columns().end;
+                                let mut tokens = vec![Token::new_symbols(name).with_columns(columns)];
+                                tokens.extend(rest);
+                                tokens
+                            }
+                            Err(diagnostic) => {
+                                diagnostics.push(diagnostic.with_line(line.line_num));
+                                Vec::new()
+                            }
+                        };
                     }
-                    Some(t) => match t.get_tag() {
-                        Tag::EQUAL => {
-                            let mut add_parser =
-                                AddrParser::new(symbols, line_num, addr, &line.addr);
-                            let con_word = w_value_to_word(add_parser.literal_constant());
-                            let con_loc = format!("con{}", l_con_inx);
-                            l_con_inx += 1;
-
-                            let line_con = ProgramLine::new(
-                                line.loc.clone(),
-                                line.op.clone(),
-                                Vec::from([Token::new_symbols(con_loc.clone())]),
-                            );
-                            mix_lines.push(line_con);
-                            addresses.push(addr);
-                            addr += 1;
-                            line_num += 1;
-
-                            let con_value = con_word.get_signed_value();
-                            let con = ProgramLine::new(
-                                Token::new_symbols(con_loc),
-                                OpToken::new_mixal_op(MixalOp::new(
-                                    "CON".to_string(),
-                                    con_value.to_string(),
-                                )),
-                                Vec::from([Token::new_number(con_value)]),
-                            );
-
-                            let indx_before_end = lines.len() - 1;
-                            lines.insert(indx_before_end as usize, con);
-                        }
-                        _ => {
-                            mix_lines.push(line.clone());
-                            addresses.push(addr);
-                            addr += 1;
-                            line_num += 1;
-                        }
-                    },
-                },
+                    mix_lines.push(mix_line);
+                    Parser::place_word(addr, line.line_num, diagnostics);
+                    addresses.push(addr);
+                    addr += 1;
+                }
 
                 Tag::MIXAL_OP => {
-                    let mut add_parser = AddrParser::new(symbols, line_num, addr, &line.addr);
-                    match &line.op.get_mixal_op().get_name()[..] {
+                    let mut add_parser = AddrParser::new(symbols, addr, &line.addr)
+                        .at_line(line.line_num);
+                    let w_values = match &mixal_op[..] {
+                        "EQU" | "ORIG" | "END" => match add_parser.w_value(Vec::new()) {
+                            Ok(_) if matches!(add_parser.relocation(), Relocation::External(_)) => {
+                                diagnostics.push(
+                                    Diagnostic::new(
+                                        line.line_num,
+                                        format!("{mixal_op} can't refer to an external symbol"),
+                                    )
+                                    .with_columns(address_columns(line)),
+                                );
+                                continue;
+                            }
+                            Ok(w_values) => w_values,
+                            Err(diagnostic) => {
+                                diagnostics.push(diagnostic.with_line(line.line_num));
+                                if mixal_op == "END" {
+                                    end_line = Some(line.clone());
+                                }
+                                continue;
+                            }
+                        },
+                        _ => Vec::new(),
+                    };
+                    let relocation = add_parser.relocation();
+                    match &mixal_op[..] {
                         "EQU" => {
-                            let w_values = add_parser.w_value(Vec::new());
-                            symbols.put_equ(line.loc.get_symbols(), w_value_to_word(w_values));
-                        }
-                        "ORIG" => {
-                            let w_values = add_parser.w_value(Vec::new());
-                            addr = w_value_to_word(w_values).get_signed_value() as u32;
+                            let value = w_value_to_word(w_values);
+                            match symbols.put_equ(line.loc.get_symbols(), value, line.line_num) {
+                                Ok(()) if relocation == Relocation::Relative => {
+                                    symbols.set_relative(line.loc.get_symbols(), line.line_num)
+                                }
+                                Ok(()) => {}
+                                Err(message) => diagnostics.push(
+                                    Diagnostic::new(line.line_num, message)
+                                        .with_columns(line.loc.get_columns()),
+                                ),
+                            }
                         }
+                        "ORIG" => match w_value_to_word(w_values).get_signed_value() {
+                            value if value < 0 => diagnostics.push(
+                                Diagnostic::new(
+                                    line.line_num,
+                                    format!("location {value} is out of range 0-3999"),
+                                )
+                                .with_columns(address_columns(line)),
+                            ),
+                            value => addr = value as u32,
+                        },
                         "END" => {
-                            let w_values = add_parser.w_value(Vec::new());
-                            program_start_addr =
-                                w_value_to_word(w_values).get_signed_value() as u32;
+                            if !w_values.is_empty() {
+                                let start = w_value_to_word(w_values).get_signed_value();
+                                match u32::try_from(start) {
+                                    Ok(start) if start < MEMORY_SIZE => {
+                                        program_start_addr = Some(start)
+                                    }
+                                    _ => diagnostics.push(
+                                        Diagnostic::new(
+                                            line.line_num,
+                                            format!("start address {start} is out of range 0-3999"),
+                                        )
+                                        .with_columns(address_columns(line)),
+                                    ),
+                                }
+                            }
+                            // the linker moves only addresses of the module
+                            if symbols.is_relocatable()
+                                && program_start_addr.is_some()
+                                && relocation != Relocation::Relative
+                            {
+                                diagnostics.push(
+                                    Diagnostic::new(
+                                        line.line_num,
+                                        "start address of a module should be its address"
+                                            .to_string(),
+                                    )
+                                    .with_columns(address_columns(line)),
+                                );
+                            }
+                            end_line = Some(line.clone());
+                        }
+                        "ENTRY" | "EXTERN" => {
+                            Parser::declare(symbols, &mixal_op, line, diagnostics);
                         }
                         _ => {
                             // CON, ALF are printable
                             mix_lines.push(line.clone());
+                            Parser::place_word(addr, line.line_num, diagnostics);
                             addresses.push(addr);
                             addr += 1;
-                            line_num += 1;
                         }
                     }
                 }
@@ -172,38 +332,202 @@ impl Parser {
                     panic!("unsupported operation {:#?}", line.op.get_tag());
                 }
             }
+        }
+
+        for (name, value) in pool.constants() {
+            Parser::define_label(symbols, &Token::new_symbols(name.clone()), 0, addr, diagnostics);
+            let con_value = to_text(value);
+            // -0 is kept as minus and 0
+            let mut con_tokens = Vec::new();
+            if value.get() & SIGN != 0 {
+                con_tokens.push(Token::new(Tag::MINUS, "-".to_string()));
+            }
+            con_tokens.push(Token::new_number(value.get_signed_value().abs()));
+            let con = ProgramLine::new(
+                Token::new_symbols(name.clone()),
+                OpToken::new_mixal_op(MixalOp::new("CON".to_string(), con_value)),
+                con_tokens,
+            );
+            mix_lines.push(con);
+            // the pool is placed at END
+            if addr >= MEMORY_SIZE {
+                diagnostics.push(Diagnostic::new(
+                    end_line.as_ref().map_or(0, |end| end.line_num),
+                    format!("literal constant ={}= at {addr} is out of range 0-3999", to_text(value)),
+                ));
+            }
+            addresses.push(addr);
+            addr += 1;
+        }
 
-            i += 1;
+        // Knuth doesn't allow future references in literals
+        for (line, w_tokens, diagnostic) in failed_literals {
+            let mut add_parser = AddrParser::new(symbols, 0, &w_tokens).at_line(line);
+            match add_parser.w_value(Vec::new()) {
+                Ok(_) => diagnostics.push(Diagnostic {
+                    message: "literal constant can't refer to a symbol defined later".to_string(),
+                    ..diagnostic
+                }),
+                Err(_) => diagnostics.push(diagnostic),
+            }
+        }
+
+        match &end_line {
+            Some(end) if !end.loc.get_symbols().is_empty() => {
+                Parser::define_label(symbols, &end.loc, end.line_num, addr, diagnostics)
+            }
+            Some(_) => {}
+            None => diagnostics.push(Diagnostic::warning(
+                0,
+                "END is missing, the program starts at 0000".to_string(),
+            )),
         }
         (program_start_addr, mix_lines, addresses)
     }
 
+    /// ENTRY and EXTERN with a list of symbols
+    fn declare(
+        symbols: &mut SymbolTable,
+        mixal_op: &str,
+        line: &ProgramLine,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let is_list = !line.addr.is_empty()
+            && line.addr.iter().enumerate().all(|(i, t)| match i % 2 {
+                0 => t.get_tag() == Tag::SYMBOLS,
+                _ => t.get_tag() == Tag::COMMA,
+            })
+            && line.addr.len() % 2 == 1;
+        if !is_list {
+            diagnostics.push(
+                Diagnostic::new(
+                    line.line_num,
+                    format!("{mixal_op} needs symbols separated by commas"),
+                )
+                .with_columns(address_columns(line)),
+            );
+            return;
+        }
+        if mixal_op == "EXTERN" && !symbols.is_relocatable() {
+            diagnostics.push(
+                Diagnostic::new(
+                    line.line_num,
+                    "EXTERN is allowed only in relocatable modules".to_string(),
+                )
+                .with_columns(address_columns(line)),
+            );
+            return;
+        }
+
+        for token in line.addr.iter().step_by(2) {
+            if let Err(message) = check_symbol(&token.get_symbols()) {
+                diagnostics.push(
+                    Diagnostic::new(line.line_num, message).with_columns(token.get_columns()),
+                );
+                continue;
+            }
+            if mixal_op == "ENTRY" {
+                symbols.put_entry(token.clone(), line.line_num);
+            } else if let Err(message) = symbols.put_extern(token.get_symbols(), line.line_num) {
+                diagnostics.push(
+                    Diagnostic::new(line.line_num, message).with_columns(token.get_columns()),
+                );
+            }
+        }
+    }
+
+    /// ENTRY symbols with their values, they are checked when all symbols are defined
+    fn entries(symbols: &SymbolTable, diagnostics: &mut Vec<Diagnostic>) -> Vec<Entry> {
+        let mut result: Vec<Entry> = Vec::new();
+        for (token, line) in symbols.entries() {
+            let name = token.get_symbols();
+            let value = match symbols.get(name.clone(), *line) {
+                Ok(value) => value,
+                Err(message) => {
+                    diagnostics.push(
+                        Diagnostic::new(*line, message).with_columns(token.get_columns()),
+                    );
+                    continue;
+                }
+            };
+            let relative = match symbols.relocation_of(&name, *line) {
+                Relocation::Absolute => false,
+                Relocation::Relative => true,
+                Relocation::External(_) => {
+                    diagnostics.push(
+                        Diagnostic::new(*line, format!("external symbol {name} can't be an ENTRY"))
+                            .with_columns(token.get_columns()),
+                    );
+                    continue;
+                }
+            };
+            if !result.iter().any(|e| e.name == name) {
+                result.push(Entry {
+                    name,
+                    value,
+                    relative,
+                });
+            }
+        }
+        result
+    }
+
+    /// A word is assembled only in the memory
+    fn place_word(addr: u32, line_num: usize, diagnostics: &mut Vec<Diagnostic>) {
+        if addr >= MEMORY_SIZE {
+            diagnostics.push(Diagnostic::new(
+                line_num,
+                format!("location {addr} is out of range 0-3999"),
+            ));
+        }
+    }
+
+    fn define_label(
+        symbols: &mut SymbolTable,
+        loc: &Token,
+        line_num: usize,
+        addr: u32,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if let Err(message) = symbols.put_reference(loc.get_symbols(), addr, line_num) {
+            diagnostics.push(Diagnostic::new(line_num, message).with_columns(loc.get_columns()));
+        }
+    }
+
     pub fn parse_printable(
         symbols: &mut SymbolTable,
-        program_start_addr: u32,
+        program_start_addr: Option<u32>,
         lines: Vec<ProgramLine>,
         addresses: Vec<u32>,
-    ) -> Vec<String> {
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Assembly {
         if lines.len() != addresses.len() {
             panic!("lines.len() != addresses.len()");
         }
 
         let mut program: Vec<String> = Vec::new();
+        let mut words: Vec<(u32, Word)> = Vec::new();
+        let mut line_nums: Vec<(u32, usize)> = Vec::new();
+        let mut module = Module::new();
 
         let mut line_num: u32 = 0;
         for line in lines {
             let addr = addresses.get(line_num as usize).expect("error");
             let mut printable_line = String::new();
+            let mut word = Word::new(0);
 
-            let mut add_parser = AddrParser::new(symbols, line_num, *addr, &line.addr);
+            let mut add_parser =
+                AddrParser::new(symbols, *addr, &line.addr).at_line(line.line_num);
 
-            match line.op.get_tag() {
-                Tag::MIX_OP => {
-                    let (a_part, i_part, f_part) = add_parser.aif();
+            let parsed = match line.op.get_tag() {
+                Tag::MIX_OP => add_parser.aif().and_then(|(a_part, i_part, f_part)| {
                     let mut instruction = *line.op.get_mix_op();
+                    instruction.check(a_part, i_part, f_part).map_err(|(part, message)| {
+                        Diagnostic::new(0, message).with_columns(add_parser.part_columns(part))
+                    })?;
 
-                    if a_part != None {
-                        instruction.set_aa(a_part.expect("error set_aa"));
+                    if let Some(a_part) = a_part {
+                        instruction.set_address(a_part);
                     }
                     if i_part != None {
                         instruction.set_i(i_part.expect("error set_i") as u8);
@@ -211,22 +535,27 @@ impl Parser {
                     if f_part != None {
                         instruction.set_f(f_part.expect("error set_f") as u8);
                     }
-                    printable_line = format!("{addr}, {}", instruction.print());
-                }
+                    let word = instruction.to_word();
+                    Ok((text_line(*addr, instruction.print(), word), word))
+                }),
                 Tag::MIXAL_OP => match &line.op.get_mixal_op().get_name()[..] {
-                    "CON" => {
-                        let w_values = add_parser.w_value(Vec::new());
-                        let value_to_print = w_value_to_word(w_values).get_signed_value();
+                    "CON" => add_parser.w_value(Vec::new()).map(|w_values| {
+                        let word = w_value_to_word(w_values);
+                        let value_to_print = word.get_signed_value();
 
-                        printable_line = format!("{addr}, {value_to_print}");
-                    }
-                    "ALF" => {
-                        let bytes = line.op.get_mixal_op().alf_to_num();
-                        printable_line = format!(
-                            "{addr}, 0, {},{},{},{},{}",
-                            bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]
-                        );
-                    }
+                        (text_line(*addr, value_to_print.to_string(), word), word)
+                    }),
+                    "ALF" => line.op.get_mixal_op().alf_to_num().map_err(|message| {
+                        Diagnostic::new(0, message).with_columns(line.addr_column..line.addr_column + 5)
+                    }).map(|bytes| {
+                        (
+                            format!(
+                                "{addr}, 0, {},{},{},{},{}",
+                                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]
+                            ),
+                            Word::new(bytes.iter().fold(0, |acc, b| (acc << 6) | *b as u32)),
+                        )
+                    }),
                     mixal_op => {
                         panic!("unexpected mixal op operation {:#?}", mixal_op);
                     }
@@ -234,34 +563,128 @@ impl Parser {
                 _ => {
                     panic!("unsupported operation {:#?}", line.op.get_tag());
                 }
+            };
+            let field = match line.op.get_tag() {
+                Tag::MIX_OP => Field::Address,
+                _ => Field::Word,
+            };
+            match add_parser.relocation() {
+                _ if parsed.is_err() => {}
+                Relocation::Absolute => {}
+                Relocation::Relative => module.relocations.push((*addr, field)),
+                Relocation::External(name) => module.externals.push((*addr, field, name)),
+            }
+            match parsed {
+                Ok((line, assembled)) => {
+                    printable_line = line;
+                    word = assembled;
+                }
+                Err(diagnostic) => diagnostics.push(diagnostic.with_line(line.line_num)),
             }
             program.push(printable_line);
+            words.push((*addr, word));
+            if line.line_num != 0 {
+                line_nums.push((*addr, line.line_num));
+            }
 
             line_num += 1;
         }
-        program.push(program_start_addr.to_string());
-        program
+        let start = program_start_addr.unwrap_or(0);
+        program.push(start.to_string());
+
+        let module = match symbols.is_relocatable() {
+            true => Some(Module {
+                size: words.iter().map(|(addr, _)| addr + 1).max().unwrap_or(0),
+                has_start: program_start_addr.is_some(),
+                entries: Parser::entries(symbols, diagnostics),
+                ..module
+            }),
+            false => None,
+        };
+        Assembly {
+            start,
+            lines: program,
+            words,
+            line_nums,
+            symbols: symbols.symbols(),
+            listing: Vec::new(),
+            warnings: Vec::new(),
+            included: Vec::new(),
+            module,
+        }
     }
 
-    pub fn parse(&self, lines: Vec<ProgramLine>) -> Vec<String> {
-        let mut symbols = SymbolTable::new();
-        let (start, lines, addrs) = Parser::parse_not_printable(&mut symbols, lines);
-        let lines = Parser::parse_printable(&mut symbols, start, lines, addrs);
-        lines
+    /// Assembles the lines, problems of all lines are reported ordered by line.
+    /// Only warnings are kept in the assembly
+    pub fn parse(&self, lines: Vec<ProgramLine>) -> Result<Assembly, Vec<Diagnostic>> {
+        let mut symbols = match self.relocatable {
+            true => SymbolTable::relocatable(),
+            false => SymbolTable::new(),
+        };
+        let mut diagnostics = Vec::new();
+
+        let (start, lines, addrs) =
+            self.parse_not_printable(&mut symbols, lines, &mut diagnostics);
+        let mut program =
+            Parser::parse_printable(&mut symbols, start, lines, addrs, &mut diagnostics);
+
+        diagnostics.sort_by_key(|d| d.line);
+        if diagnostics.iter().any(|d| d.is_error()) {
+            return Err(diagnostics);
+        }
+        program.warnings = diagnostics;
+        Ok(program)
     }
 }
-fn w_value_to_word(w_value: Vec<(Option<i32>, Option<i32>)>) -> Word {
+/// Line of the `.mix` text format. A negative word written as 0, e.g. CON -0 or ENTA -0,
+/// would be loaded as positive, it's written with the sign and the bytes instead
+pub(crate) fn text_line(addr: u32, fields: String, word: Word) -> String {
+    match word.get() & SIGN != 0 && fields.split(',').next() == Some("0") {
+        true => {
+            let bytes: Vec<String> = (1..=5).map(|i| word.get_byte(i).to_string()).collect();
+            format!("{addr}, -1, {}", bytes.join(", "))
+        }
+        false => format!("{addr}, {fields}"),
+    }
+}
+
+/// Columns of the address field, from the first token to the last one
+fn address_columns(line: &ProgramLine) -> std::ops::Range<usize> {
+    match (line.addr.first(), line.addr.last()) {
+        (Some(first), Some(last)) => first.get_columns().start..last.get_columns().end,
+        _ => line.addr_column..line.addr_column + 1,
+    }
+}
+
+fn w_value_to_word(w_value: Vec<(Option<Word>, Option<i32>)>) -> Word {
     let mut result = Word::new(0);
     for (e, f) in w_value {
         match f {
             None => {
-                result = Word::new_from_signed(e.expect("error"));
+                result = e.expect("error");
             }
             Some(spec) => {
-                result.put_by_access(e.expect("error"), WordAccess::new_by_spec(spec as u8));
+                put_by_access(&mut result, e.expect("error"), WordAccess::new_by_spec(spec as u8));
             }
         }
     }
 
     result
 }
+
+/// Stores the value into the field of the word like STA: the rightmost bytes
+/// of the value go to the field, the sign goes only if the field includes 0
+fn put_by_access(word: &mut Word, value: Word, access: WordAccess) {
+    for i in 0..access.right - access.left + 1 {
+        let b_from = 5 - i;
+        let b_to = access.right - i;
+        if b_to == 0 {
+            continue;
+        }
+        word.set_byte(b_to, value.get_byte(b_from));
+    }
+
+    if access.left == 0 {
+        word.set_sign(value.get_sign());
+    }
+}
@@ -1,25 +1,64 @@
+use crate::diagnostic::Diagnostic;
 use crate::lexer::token::*;
 use crate::parser::*;
 
+use std::ops::Range;
+
 pub struct AddrParser<'a> {
     symbols: &'a SymbolTable,
-    line_num: u32,
-    line_addr: u32,
+    line_addr: u32, // value of *
+    source_line: usize, // for dB and dF
 
     tokens: &'a Vec<Token>,
     current: usize,
+
+    // relocatable terms of the last expression and of its last atom
+    terms: Terms,
+    atom_terms: Terms,
+    relocation: Relocation,
+
+    parts: [Range<usize>; 3], // tokens of A, I and F after `aif`
 }
 impl<'a> AddrParser<'a> {
-    pub fn new(symbols: &'a SymbolTable, line_num: u32, line_addr: u32, tokens: &'a Vec<Token>) -> AddrParser<'a> {
+    pub fn new(symbols: &'a SymbolTable, line_addr: u32, tokens: &'a Vec<Token>) -> AddrParser<'a> {
         AddrParser {
             symbols: symbols,
-            line_num: line_num,
             line_addr: line_addr,
+            source_line: 0,
             tokens: tokens,
             current: 0,
+            terms: Terms::absolute(),
+            atom_terms: Terms::absolute(),
+            relocation: Relocation::Absolute,
+            parts: [0..0, 0..0, 0..0],
         }
     }
 
+    /// Columns of a part of the address after `aif`, the whole address if the part is empty
+    pub fn part_columns(&self, part: Part) -> Range<usize> {
+        let tokens = &self.parts[part as usize];
+        let tokens = match tokens.is_empty() {
+            true => 0..self.tokens.len(),
+            false => tokens.clone(),
+        };
+        match (self.tokens.get(tokens.start), tokens.end.checked_sub(1)) {
+            (Some(first), Some(last)) => {
+                first.get_columns().start..self.tokens[last].get_columns().end
+            }
+            _ => 0..0,
+        }
+    }
+
+    /// Relocation of the A-part after `aif`, of the whole W-value after `w_value`
+    pub fn relocation(&self) -> Relocation {
+        self.relocation.clone()
+    }
+
+    pub fn at_line(mut self, source_line: usize) -> AddrParser<'a> {
+        self.source_line = source_line;
+        self
+    }
+
     fn current(&mut self) -> Option<Token> {
         let result = match self.tokens.get(self.current) {
             None => None,
@@ -31,9 +70,9 @@ impl<'a> AddrParser<'a> {
         self.current += 1;
     }
 
-    pub fn literal_constant(&mut self) -> Vec<(Option<i32>, Option<i32>)> {
+    pub fn literal_constant(&mut self) -> Result<Vec<(Option<Word>, Option<i32>)>, Diagnostic> {
         match self.current() {
-            None => return Vec::new(),
+            None => return Ok(Vec::new()),
             Some(t) => {
                 if t.get_tag() == Tag::EQUAL {
                     self.step();
@@ -45,8 +84,12 @@ impl<'a> AddrParser<'a> {
 
     pub fn w_value(
         &mut self,
-        mut acc: Vec<(Option<i32>, Option<i32>)>,
-    ) -> Vec<(Option<i32>, Option<i32>)> {
+        mut acc: Vec<(Option<Word>, Option<i32>)>,
+    ) -> Result<Vec<(Option<Word>, Option<i32>)>, Diagnostic> {
+        // an expression before the comma
+        if let Some(t) = self.current().filter(|t| t.get_tag() == Tag::COMMA) {
+            return Err(error_at("expression is missing".to_string(), &t));
+        }
         loop {
             match self.current() {
                 None => break,
@@ -57,197 +100,336 @@ impl<'a> AddrParser<'a> {
             };
         }
 
-        let e = self.exprs(None);
+        let start = self.current;
+        let e = self.exprs(None)?;
         let mut f_part = None;
         if e != None {
-            f_part = self.f_part();
+            let relocation = self.terms.relocation().map_err(|m| self.error_between(m, start))?;
+            let f_start = self.current;
+            f_part = self.f_part()?;
+            if let Some(f) = f_part {
+                check_field(f).map_err(|m| self.error_between(m, f_start))?;
+            }
+
+            // the linker relocates only whole words
+            let is_relocatable = relocation != Relocation::Absolute;
+            if (is_relocatable || self.relocation != Relocation::Absolute)
+                && (!acc.is_empty() || f_part.is_some_and(|f| f != 5))
+            {
+                return Err(self.error_between(
+                    "relocatable value should be the whole W-value".to_string(),
+                    start,
+                ));
+            }
+            if is_relocatable {
+                self.relocation = relocation;
+            }
         }
 
         if (e == None && f_part == None) {
-            return acc;
+            return Ok(acc);
         }
         acc.push((e, f_part));
 
         return match self.current() {
-            None => acc,
+            None => Ok(acc),
             Some(t) => match t.get_tag() {
-                Tag::EQUAL => return acc,
+                Tag::EQUAL => return Ok(acc),
                 Tag::COMMA | Tag::CLOSE_BR => {
                     self.step();
+                    if t.get_tag() == Tag::COMMA && self.current().is_none() {
+                        return Err(self.error_at_end("expression is missing".to_string()));
+                    }
                     self.w_value(acc)
                 }
-                tag => panic!("w_value syntax error"),
+                tag => Err(error_at(format!("unexpected '{}' in W-value", t.to_text()), &t)),
             },
         };
     }
 
-    pub fn aif(&mut self) -> (Option<i32>, Option<i32>, Option<i32>) {
-        let a_part = self.exprs(None);
+    pub fn aif(&mut self) -> Result<(Option<Word>, Option<i32>, Option<i32>), Diagnostic> {
+        let a_part = self.exprs(None)?;
+        self.parts[Part::Address as usize] = 0..self.current;
+        self.relocation = self.terms.relocation().map_err(|m| self.error_between(m, 0))?;
         // println!("a_part {:#?}", a_part);
 
         let i_part = match self.current() {
-            None => return (a_part, None, None),
+            None => return Ok((a_part, None, None)),
             Some(t) => match t.get_tag() {
                 Tag::COMMA => {
                     self.step();
-                    self.exprs(None)
+                    let start = self.current;
+                    let i_part = match self.exprs(None)? {
+                        Some(w) => w.get_signed_value(),
+                        None => {
+                            return Err(match self.current() {
+                                Some(t) => error_at("expression is missing".to_string(), &t),
+                                None => self.error_at_end("expression is missing".to_string()),
+                            })
+                        }
+                    };
+                    self.check_absolute("index", start)?;
+                    self.parts[Part::Index as usize] = start..self.current;
+                    Some(i_part)
                 }
                 _ => None,
             },
         };
 
-        let f_part = self.f_part();
+        let f_start = self.current;
+        let f_part = self.f_part()?;
+        self.parts[Part::Field as usize] = f_start..self.current;
         // println!("i_part {:#?}", i_part);
 
         // println!("f_part {:#?}", f_part);
-        (a_part, i_part, f_part)
+        if let Some(t) = self.current() {
+            return Err(error_at(format!("unexpected '{}' in address", t.to_text()), &t));
+        }
+        Ok((a_part, i_part, f_part))
     }
 
-    fn f_part(&mut self) -> Option<i32> {
+    fn f_part(&mut self) -> Result<Option<i32>, Diagnostic> {
         return match self.current() {
-            None => None,
+            None => Ok(None),
             Some(t) => match t.get_tag() {
                 Tag::OPEN_BR => {
                     self.step();
-                    self.exprs(None)
+                    let start = self.current;
+                    let f = self.exprs(None)?.map(|w| w.get_signed_value());
+                    self.check_absolute("field", start)?;
+                    match self.current() {
+                        Some(t) if t.get_tag() == Tag::CLOSE_BR => self.step(),
+                        Some(t) => return Err(error_at(format!("')' is expected before '{}'", t.to_text()), &t)),
+                        None => return Err(self.error_at_end("')' is missing".to_string())),
+                    }
+                    Ok(f)
                 }
-                _ => None,
+                _ => Ok(None),
             },
         };
     }
 
-    fn exprs(&mut self, acc: Option<i32>) -> Option<i32> {
+    fn exprs(&mut self, acc: Option<Word>) -> Result<Option<Word>, Diagnostic> {
         let left = match acc {
-            None => self.expr().reduce(),
-            Some(x) => acc,
+            None => self.expr()?.reduce().map_err(|m| self.error_at_end(m))?,
+            Some(_) => acc,
         };
         // println!("exprs left {:#?}", left);
         // println!("exprs current {:#?}", self.current());
 
-        let op = match self.current() {
-            None => return left,
+        let op_token = match self.current() {
+            None => return Ok(left),
             Some(t) => match t.get_tag() {
-                Tag::COMMA => return left,
-                Tag::EQUAL => return left, //TODO: this should be parsed as symbols
-                Tag::OPEN_BR => return left,
-                Tag::CLOSE_BR => return left,
-                Tag::NUMBER => return left,
-                Tag::SYMBOLS => return left,
-                _ => t.get_tag(),
+                Tag::COMMA => return Ok(left),
+                Tag::EQUAL => return Ok(left), //TODO: this should be parsed as symbols
+                Tag::OPEN_BR => return Ok(left),
+                Tag::CLOSE_BR => return Ok(left),
+                Tag::NUMBER => return Ok(left),
+                Tag::SYMBOLS => return Ok(left),
+                _ => t,
             },
         };
+        let op = op_token.get_tag();
         self.step();
         // println!("exprs op {:#?}", op);
 
-        let right: Box<dyn Expr> = match self.current() {
-            None => panic!("syntax error"),
-            Some(t) => Box::new(self.unary(t.clone())),
-        };
+        let right: Box<dyn Expr> = Box::new(self.operand()?);
         self.step();
 
-        let result = BinaryOp::new(op, Box::new(Holder::new(left)), right).reduce();
+        let result = BinaryOp::new(op, Box::new(Holder::new(left)), right)
+            .reduce()
+            .map_err(|m| error_at(m, &op_token))?;
+        self.combine_terms(&op_token)?;
         self.exprs(result)
     }
 
-    fn expr(&mut self) -> Box<dyn Expr> {
+    fn expr(&mut self) -> Result<Box<dyn Expr>, Diagnostic> {
+        self.terms = Terms::absolute();
         let left: Box<dyn Expr> = match self.current() {
-            None => return Box::new(EmptyExpr::new()),
-            Some(t) => Box::new(self.unary(t.clone())),
+            None => return Ok(Box::new(EmptyExpr::new())),
+            Some(t) => match t.get_tag() {
+                // empty A-part before I-part or F-part
+                Tag::COMMA | Tag::OPEN_BR => return Ok(Box::new(EmptyExpr::new())),
+                _ => Box::new(self.unary(t.clone())?),
+            },
         };
+        self.terms = self.atom_terms.clone();
         self.step();
         // println!("expr left {:#?}", left.to_string());
         // println!("expr current {:#?}", self.current());
 
-        let op = match self.current() {
-            None => return left,
+        let op_token = match self.current() {
+            None => return Ok(left),
             Some(t) => match t.get_tag() {
-                Tag::COMMA => return left,
-                Tag::EQUAL => return left, //TODO: this should be parsed as symbols
-                Tag::OPEN_BR => return left,
-                Tag::CLOSE_BR => return left,
-                _ => t.get_tag(),
+                Tag::COMMA => return Ok(left),
+                Tag::EQUAL => return Ok(left), //TODO: this should be parsed as symbols
+                Tag::OPEN_BR => return Ok(left),
+                Tag::CLOSE_BR => return Ok(left),
+                _ => t,
             },
         };
+        let op = op_token.get_tag();
         self.step();
         // println!("expr op {:#?}", op);
 
-        let right: Box<dyn Expr> = match self.current() {
-            None => panic!("syntax error"),
-            Some(t) => Box::new(self.unary(t.clone())),
-        };
+        let right: Box<dyn Expr> = Box::new(self.operand()?);
         self.step();
 
-        Box::new(BinaryOp::new(op, left, right))
+        // reduced here to point at the operation if it fails
+        let result = BinaryOp::new(op, left, right)
+            .reduce()
+            .map_err(|m| error_at(m, &op_token))?;
+        self.combine_terms(&op_token)?;
+        Ok(Box::new(Holder::new(result)))
     }
 
-    fn unary(&mut self, token: Token) -> UnaryOp {
+    /// Terms of the expression with the last atom after `op_token`
+    fn combine_terms(&mut self, op_token: &Token) -> Result<(), Diagnostic> {
+        let right = self.atom_terms.clone();
+        self.terms = self
+            .terms
+            .clone()
+            .combine(op_token.get_tag(), &op_token.to_text(), right)
+            .map_err(|m| error_at(m, op_token))?;
+        Ok(())
+    }
+
+    fn check_absolute(&self, part: &str, start: usize) -> Result<(), Diagnostic> {
+        if self.terms.is_absolute() {
+            return Ok(());
+        }
+        Err(self.error_between(format!("{part} can't be relocatable"), start))
+    }
+
+    fn operand(&mut self) -> Result<UnaryOp, Diagnostic> {
+        match self.current() {
+            None => Err(self.error_at_end("operand is missing at the end of expression".to_string())),
+            Some(t) => self.unary(t.clone()),
+        }
+    }
+
+    fn unary(&mut self, token: Token) -> Result<UnaryOp, Diagnostic> {
         return match token.get_tag() {
             Tag::MINUS => {
                 self.step();
-                let next_t = self.current().expect("syntax error");
-                UnaryOp::new(Tag::MINUS, Box::new(self.atom_expr(next_t)))
+                let next_t = self.operand_after(&token)?;
+                let atom = self.atom_expr(next_t)?;
+                self.atom_terms = self.atom_terms.clone().negate();
+                Ok(UnaryOp::new(Tag::MINUS, atom))
             }
             Tag::PLUS => {
                 self.step();
-                let next_t = self.current().expect("syntax error");
-                UnaryOp::new(Tag::PLUS, Box::new(self.atom_expr(next_t)))
+                let next_t = self.operand_after(&token)?;
+                Ok(UnaryOp::new(Tag::PLUS, self.atom_expr(next_t)?))
             }
-            _ => UnaryOp::new(Tag::PLUS, Box::new(self.atom_expr(token))),
+            _ => Ok(UnaryOp::new(Tag::PLUS, self.atom_expr(token)?)),
         };
     }
 
-    fn atom_expr(&self, token: Token) -> Number {
+    fn operand_after(&mut self, token: &Token) -> Result<Token, Diagnostic> {
+        self.current()
+            .ok_or_else(|| error_at(format!("operand is missing after '{}'", token.to_text()), token))
+    }
+
+    fn atom_expr(&mut self, token: Token) -> Result<Box<dyn Expr>, Diagnostic> {
+        self.atom_terms = Terms::absolute();
         return match token.get_tag() {
-            Tag::NUMBER => Number::new(token.clone()),
-            Tag::SYMBOLS => Number::new(Token::new_number(self.symbols.get(token.get_symbols(), self.line_addr))),
-            Tag::MULTIPLY => Number::new(Token::new_number(self.line_num as i32)),
-            tag => {
-                panic!("atom_expr syntax error {:#?}", tag);
+            Tag::NUMBER => Ok(Box::new(Number::new(token.clone()))),
+            Tag::SYMBOLS => {
+                let name = token.get_symbols();
+                let value = self
+                    .symbols
+                    .get_word(name.clone(), self.source_line)
+                    .map_err(|m| error_at(m, &token))?;
+                self.atom_terms = Terms::of(self.symbols.relocation_of(&name, self.source_line));
+                Ok(Box::new(Holder::new(Some(value))))
+            }
+            Tag::MULTIPLY => {
+                if self.symbols.is_relocatable() {
+                    self.atom_terms = Terms::of(Relocation::Relative);
+                }
+                Ok(Box::new(Holder::new(Some(Word::new(self.line_addr)))))
             }
+            _ => Err(error_at(format!("unexpected '{}' in expression", token.to_text()), &token)),
         };
     }
+
+    /// Points from the token `start` to the last parsed one
+    pub fn error_between(&self, message: String, start: usize) -> Diagnostic {
+        let columns = self.tokens[start].get_columns().start
+            ..self.tokens[self.current - 1].get_columns().end;
+        Diagnostic::new(0, message).with_columns(columns)
+    }
+
+    /// Points after the last token, for something missing at the end
+    fn error_at_end(&self, message: String) -> Diagnostic {
+        let end = self.tokens.last().map_or(0, |t| t.get_columns().end);
+        Diagnostic::new(0, message).with_columns(end..end + 1)
+    }
+}
+
+fn error_at(message: String, token: &Token) -> Diagnostic {
+    Diagnostic::new(0, message).with_columns(token.get_columns())
+}
+
+/// Field of a W-value is (L:R) with 0 <= L <= R <= 5
+pub fn check_field(f: i32) -> Result<(), String> {
+    if f < 0 {
+        return Err(format!("field {f} is invalid, it should be (L:R) with 0 <= L <= R <= 5"));
+    }
+    let (left, right) = (f / 8, f % 8);
+    if left > right || right > 5 {
+        return Err(format!(
+            "field ({left}:{right}) is invalid, it should be (L:R) with 0 <= L <= R <= 5"
+        ));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn w(value: i32) -> Option<Word> {
+        Some(Word::new_from_signed(value))
+    }
+
     #[test]
     fn binary_op_simple() {
         let table = SymbolTable::new();
 
         let tokens = vec![];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
         assert_eq!(None, result);
 
         let tokens = vec![Token::new_number(5)];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(5), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(5), result);
 
         let tokens = vec![Token::new(Tag::PLUS, "+".to_string()), Token::new_number(5)];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(5), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(5), result);
 
         let tokens = vec![
             Token::new(Tag::MINUS, "-".to_string()),
             Token::new_number(5),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(-5), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(-5), result);
 
         let tokens = vec![Token::new(Tag::MULTIPLY, "*".to_string())];
-        let mut parser = AddrParser::new(&table, 1, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(1), result);
+        let mut parser = AddrParser::new(&table, 1, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(1), result);
     }
 
     #[test]
@@ -259,57 +441,57 @@ mod tests {
             Token::new(Tag::PLUS, "+".to_string()),
             Token::new_number(6),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(11), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(11), result);
 
         let tokens = vec![
             Token::new_number(5),
             Token::new(Tag::MINUS, "-".to_string()),
             Token::new_number(6),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(-1), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(-1), result);
 
         let tokens = vec![
             Token::new_number(1),
             Token::new(Tag::F_OP, ":".to_string()),
             Token::new_number(5),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(13), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(13), result);
 
         let tokens = vec![
             Token::new(Tag::MULTIPLY, "*".to_string()),
             Token::new(Tag::MULTIPLY, "*".to_string()),
             Token::new(Tag::MULTIPLY, "*".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 2, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(4), result);
+        let mut parser = AddrParser::new(&table, 2, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(4), result);
     }
 
     #[test]
     fn binary_op_symbols() {
         let mut table = SymbolTable::new();
-        table.put_equ("x1".to_string(), Word::new(2));
-        table.put_equ("x2".to_string(), Word::new(4));
+        table.put_equ("x1".to_string(), Word::new(2), 1).expect("new symbol");
+        table.put_equ("x2".to_string(), Word::new(4), 2).expect("new symbol");
 
         let tokens = vec![
             Token::new_symbols("x1".to_string()),
             Token::new(Tag::PLUS, "+".to_string()),
             Token::new_symbols("x2".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let e = parser.expr();
-        let result = e.reduce();
-        assert_eq!(Some(6), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let e = parser.expr().expect("valid expr");
+        let result = e.reduce().expect("valid expr");
+        assert_eq!(w(6), result);
     }
 
     #[test]
@@ -321,9 +503,9 @@ mod tests {
             Token::new(Tag::PLUS, "+".to_string()),
             Token::new_number(6),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.exprs(None);
-        assert_eq!(Some(11), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.exprs(None).expect("valid expr");
+        assert_eq!(w(11), result);
 
         let tokens = vec![
             Token::new_number(1),
@@ -334,9 +516,9 @@ mod tests {
             Token::new(Tag::PLUS, "+".to_string()),
             Token::new_number(9),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.exprs(None);
-        assert_eq!(Some(9), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.exprs(None).expect("valid expr");
+        assert_eq!(w(9), result);
 
         let tokens = vec![
             Token::new(Tag::MINUS, "-".to_string()),
@@ -348,27 +530,27 @@ mod tests {
             Token::new(Tag::DEVIDE, "/".to_string()),
             Token::new_number(6),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.exprs(None);
-        assert_eq!(Some(13), result);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.exprs(None).expect("valid expr");
+        assert_eq!(w(13), result);
 
         let tokens = vec![
             Token::new(Tag::MULTIPLY, "*".to_string()),
             Token::new(Tag::MULTIPLY, "*".to_string()),
             Token::new(Tag::MULTIPLY, "*".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 2, 0, &tokens);
-        let result = parser.exprs(None);
-        assert_eq!(Some(4), result);
+        let mut parser = AddrParser::new(&table, 2, &tokens);
+        let result = parser.exprs(None).expect("valid expr");
+        assert_eq!(w(4), result);
 
         let tokens = vec![
             Token::new(Tag::MULTIPLY, "*".to_string()),
             Token::new(Tag::MINUS, "-".to_string()),
             Token::new_number(3),
         ];
-        let mut parser = AddrParser::new(&table, 2, 0, &tokens);
-        let result = parser.exprs(None);
-        assert_eq!(Some(-1), result);
+        let mut parser = AddrParser::new(&table, 2, &tokens);
+        let result = parser.exprs(None).expect("valid expr");
+        assert_eq!(w(-1), result);
 
         let tokens = vec![
             Token::new(Tag::MULTIPLY, "*".to_string()),
@@ -376,9 +558,9 @@ mod tests {
             Token::new(Tag::MINUS, "-".to_string()),
             Token::new_number(3),
         ];
-        let mut parser = AddrParser::new(&table, 2, 0, &tokens);
-        let result = parser.exprs(None);
-        assert_eq!(Some(5), result);
+        let mut parser = AddrParser::new(&table, 2, &tokens);
+        let result = parser.exprs(None).expect("valid expr");
+        assert_eq!(w(5), result);
     }
 
     #[test]
@@ -393,9 +575,9 @@ mod tests {
             Token::new_number(0),
             Token::new(Tag::CLOSE_BR, ")".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let (a, i, f) = parser.aif();
-        assert_eq!(Some(5), a);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let (a, i, f) = parser.aif().expect("valid address");
+        assert_eq!(w(5), a);
         assert_eq!(Some(2), i);
         assert_eq!(Some(0), f);
 
@@ -409,16 +591,16 @@ mod tests {
             Token::new_number(5),
             Token::new(Tag::CLOSE_BR, ")".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let (a, i, f) = parser.aif();
-        assert_eq!(Some(5), a);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let (a, i, f) = parser.aif().expect("valid address");
+        assert_eq!(w(5), a);
         assert_eq!(Some(2), i);
         assert_eq!(Some(13), f);
 
         let tokens = vec![Token::new_number(5)];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let (a, i, f) = parser.aif();
-        assert_eq!(Some(5), a);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let (a, i, f) = parser.aif().expect("valid address");
+        assert_eq!(w(5), a);
         assert_eq!(None, i);
         assert_eq!(None, f);
 
@@ -427,9 +609,9 @@ mod tests {
             Token::new(Tag::COMMA, ",".to_string()),
             Token::new_number(2),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let (a, i, f) = parser.aif();
-        assert_eq!(Some(5), a);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let (a, i, f) = parser.aif().expect("valid address");
+        assert_eq!(w(5), a);
         assert_eq!(Some(2), i);
         assert_eq!(None, f);
 
@@ -443,9 +625,9 @@ mod tests {
             Token::new_number(0),
             Token::new(Tag::CLOSE_BR, ")".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let (a, i, f) = parser.aif();
-        assert_eq!(Some(10), a);
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let (a, i, f) = parser.aif().expect("valid address");
+        assert_eq!(w(10), a);
         assert_eq!(Some(2), i);
         assert_eq!(Some(0), f);
     }
@@ -455,13 +637,13 @@ mod tests {
         let table = SymbolTable::new();
 
         let tokens = vec![Token::new_number(1)];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.w_value(Vec::new());
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.w_value(Vec::new()).expect("valid W-value");
 
         assert_eq!(1, result.len());
         let (e1, f1) = result.get(0).expect("error");
 
-        assert_eq!(Some(1), *e1);
+        assert_eq!(w(1), *e1);
         assert_eq!(None, *f1);
 
         let tokens = vec![
@@ -475,17 +657,17 @@ mod tests {
             Token::new_number(2),
             Token::new(Tag::CLOSE_BR, ")".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.w_value(Vec::new());
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.w_value(Vec::new()).expect("valid W-value");
 
         assert_eq!(2, result.len());
 
         let (e1, f1) = result.get(0).expect("error");
-        assert_eq!(Some(1), *e1);
+        assert_eq!(w(1), *e1);
         assert_eq!(None, *f1);
 
         let (e2, f2) = result.get(1).expect("error");
-        assert_eq!(Some(-1000), *e2);
+        assert_eq!(w(-1000), *e2);
         assert_eq!(Some(2), *f2);
 
         let tokens = vec![
@@ -499,17 +681,17 @@ mod tests {
             Token::new(Tag::COMMA, ",".to_string()),
             Token::new_number(1),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.w_value(Vec::new());
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.w_value(Vec::new()).expect("valid W-value");
 
         assert_eq!(2, result.len());
 
         let (e1, f1) = result.get(0).expect("error");
-        assert_eq!(Some(-1000), *e1);
+        assert_eq!(w(-1000), *e1);
         assert_eq!(Some(2), *f1);
 
         let (e2, f2) = result.get(1).expect("error");
-        assert_eq!(Some(1), *e2);
+        assert_eq!(w(1), *e2);
         assert_eq!(None, *f2);
     }
 
@@ -522,16 +704,16 @@ mod tests {
             Token::new_number(1),
             Token::new(Tag::EQUAL, "=".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.literal_constant();
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.literal_constant().expect("valid literal");
 
         assert_eq!(1, result.len());
         let (e1, f1) = result.get(0).expect("error");
 
-        assert_eq!(Some(1), *e1);
+        assert_eq!(w(1), *e1);
         assert_eq!(None, *f1);
 
-        table.put_equ("L".to_string(), Word::new(2));
+        table.put_equ("L".to_string(), Word::new(2), 1).expect("new symbol");
         let tokens = vec![
             Token::new(Tag::EQUAL, "=".to_string()),
             Token::new_number(1),
@@ -539,13 +721,13 @@ mod tests {
             Token::new_symbols("L".to_string()),
             Token::new(Tag::EQUAL, "=".to_string()),
         ];
-        let mut parser = AddrParser::new(&table, 0, 0, &tokens);
-        let result = parser.literal_constant();
+        let mut parser = AddrParser::new(&table, 0, &tokens);
+        let result = parser.literal_constant().expect("valid literal");
 
         assert_eq!(1, result.len());
         let (e1, f1) = result.get(0).expect("error");
 
-        assert_eq!(Some(-1), *e1);
+        assert_eq!(w(-1), *e1);
         assert_eq!(None, *f1);
     }
 }
@@ -0,0 +1,206 @@
+use crate::lexer::token::*;
+use crate::tags::*;
+use mix_core::word::Word;
+use mix_core::word::{ABS, SIGN};
+
+/*
+ * Expressions are evaluated strictly from left to right on signed 5-byte words,
+ * the same way MIX does it in its registers (Knuth 1.3.2):
+ *
+ *   a+b, a-b  the sign of a zero result is the sign of a
+ *   a*b, a/b  the sign is the product of the signs, even for a zero result
+ *   a//b      (a * 64^5) / b
+ *   a:b       8a + b
+ *
+ * A result that doesn't fit in 5 bytes is an overflow, it's reported as an error.
+ */
+
+pub trait Expr {
+    fn reduce(&self) -> Result<Option<Word>, String>;
+    fn to_string(&self) -> String;
+}
+
+pub struct EmptyExpr {}
+impl EmptyExpr {
+    pub fn new() -> EmptyExpr {
+        EmptyExpr {}
+    }
+}
+impl Expr for EmptyExpr {
+    fn reduce(&self) -> Result<Option<Word>, String> {
+        Ok(None)
+    }
+    fn to_string(&self) -> String {
+        "empty_expr".to_string()
+    }
+}
+
+pub struct Holder {
+    val: Option<Word>,
+}
+impl Holder {
+    pub fn new(val: Option<Word>) -> Holder {
+        Holder { val }
+    }
+}
+impl Expr for Holder {
+    fn reduce(&self) -> Result<Option<Word>, String> {
+        Ok(self.val)
+    }
+    fn to_string(&self) -> String {
+        format!("holder {:?}", self.val.map(|w| to_text(&w)))
+    }
+}
+
+pub struct Number {
+    token: Token,
+}
+impl Number {
+    pub fn new(token: Token) -> Number {
+        Number { token }
+    }
+}
+impl Expr for Number {
+    fn reduce(&self) -> Result<Option<Word>, String> {
+        Ok(Some(Word::new_from_signed(self.token.get_number())))
+    }
+    fn to_string(&self) -> String {
+        format!("number {:#?}", self.token.get_number())
+    }
+}
+
+pub struct BinaryOp {
+    tag: Tag,
+
+    left: Box<dyn Expr>,
+    right: Box<dyn Expr>,
+}
+impl BinaryOp {
+    pub fn new(tag: Tag, left: Box<dyn Expr>, right: Box<dyn Expr>) -> BinaryOp {
+        BinaryOp {
+            tag: tag,
+            left: left,
+            right: right,
+        }
+    }
+}
+impl Expr for BinaryOp {
+    fn reduce(&self) -> Result<Option<Word>, String> {
+        let (l, r) = match (self.left.reduce()?, self.right.reduce()?) {
+            (Some(l), Some(r)) => (l, r),
+            _ => return Err("operand is missing in expression".to_string()),
+        };
+
+        let result = match self.tag {
+            Tag::PLUS => add(l, r),
+            Tag::MINUS => add(l, negate(r)),
+            Tag::MULTIPLY => multiply(l, r),
+            Tag::DEVIDE => divide(magnitude(l), l, r),
+            Tag::MOD => divide(magnitude(l) << 30, l, r),
+            Tag::F_OP => multiply(l, Word::new(8)).and_then(|l8| add(l8, r)),
+
+            tag => return Err(format!("unsupported operation {:?} in expression", tag)),
+        };
+        result.map(Some).ok_or_else(|| match self.tag {
+            Tag::DEVIDE | Tag::MOD if magnitude(r) == 0 => "division by zero".to_string(),
+            tag => format!(
+                "overflow, {} {} {} doesn't fit in 5 bytes",
+                to_text(&l),
+                op_text(tag),
+                to_text(&r)
+            ),
+        })
+    }
+    fn to_string(&self) -> String {
+        format!("binary_op {} {:#?} {}", self.left.to_string(), self.tag, self.right.to_string())
+    }
+}
+
+pub struct UnaryOp {
+    tag: Tag,
+
+    right: Box<dyn Expr>,
+}
+impl UnaryOp {
+    pub fn new(tag: Tag, right: Box<dyn Expr>) -> UnaryOp {
+        UnaryOp {
+            tag: tag,
+            right: right,
+        }
+    }
+}
+impl Expr for UnaryOp {
+    fn reduce(&self) -> Result<Option<Word>, String> {
+        let right = self.right.reduce()?;
+        match self.tag {
+            Tag::PLUS => Ok(right),
+            // -0 is minus zero
+            Tag::MINUS => Ok(right.map(negate)),
+
+            _ => panic!("unsupported unary operation {:#?}", self.tag),
+        }
+    }
+    fn to_string(&self) -> String {
+        format!("unary_op {:#?} {}",  self.tag, self.right.to_string())
+    }
+}
+
+/// Value of a word for messages, minus zero is -0
+pub fn to_text(word: &Word) -> String {
+    if is_negative(word) {
+        format!("-{}", magnitude(*word))
+    } else {
+        magnitude(*word).to_string()
+    }
+}
+
+fn op_text(tag: Tag) -> &'static str {
+    match tag {
+        Tag::PLUS => "+",
+        Tag::MINUS => "-",
+        Tag::MULTIPLY => "*",
+        Tag::DEVIDE => "/",
+        Tag::MOD => "//",
+        Tag::F_OP => ":",
+        _ => "?",
+    }
+}
+
+fn is_negative(word: &Word) -> bool {
+    word.get() & SIGN != 0
+}
+
+fn magnitude(word: Word) -> u64 {
+    (word.get() & ABS) as u64
+}
+
+fn negate(word: Word) -> Word {
+    Word::new(word.get() ^ SIGN)
+}
+
+fn with_sign(negative: bool, value: u64) -> Option<Word> {
+    if value > ABS as u64 {
+        return None;
+    }
+    let sign = if negative { SIGN } else { 0 };
+    Some(Word::new(value as u32 | sign))
+}
+
+fn add(l: Word, r: Word) -> Option<Word> {
+    let signed = |w: Word| if is_negative(&w) { -(magnitude(w) as i64) } else { magnitude(w) as i64 };
+    let sum = signed(l) + signed(r);
+    let negative = if sum == 0 { is_negative(&l) } else { sum < 0 };
+    with_sign(negative, sum.unsigned_abs())
+}
+
+fn multiply(l: Word, r: Word) -> Option<Word> {
+    with_sign(is_negative(&l) != is_negative(&r), magnitude(l) * magnitude(r))
+}
+
+/// `dividend` is the magnitude of rAX before DIV, the signs come from `l` and `r`
+fn divide(dividend: u64, l: Word, r: Word) -> Option<Word> {
+    if magnitude(r) == 0 {
+        return None;
+    }
+    with_sign(is_negative(&l) != is_negative(&r), dividend / magnitude(r))
+}
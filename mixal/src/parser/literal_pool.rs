@@ -0,0 +1,122 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer::token::*;
+use crate::tags::Tag;
+use mix_core::word::Word;
+
+/*
+ * literal_constant -> =W_value=, the W-value is less than 10 characters
+ *
+ * The constants are placed after the program when END is met,
+ * a constant is shared by all literals with the same value.
+ * The names start with '=', they can't collide with the symbols of a program.
+ */
+
+pub const MAX_LITERAL_LENGTH: usize = 9;
+
+pub struct LiteralPool {
+    constants: Vec<(String, Word)>,
+}
+impl LiteralPool {
+    pub fn new() -> LiteralPool {
+        LiteralPool {
+            constants: Vec::new(),
+        }
+    }
+
+    /// Name of the constant with the value, true if the constant is new
+    pub fn name_of(&mut self, value: Word) -> (String, bool) {
+        if let Some((name, _)) = self.constants.iter().find(|(_, w)| *w == value) {
+            return (name.clone(), false);
+        }
+        let name = format!("=CON{}", self.constants.len() + 1);
+        self.constants.push((name.clone(), value));
+        (name, true)
+    }
+
+    pub fn constants(&self) -> &[(String, Word)] {
+        &self.constants
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+}
+
+/// Splits an address that starts with a literal into the W-value between '=' and the rest,
+/// e.g. =1-L=,1(0:2) -> 1-L and ,1(0:2)
+pub fn split_literal(tokens: &[Token]) -> Result<(Vec<Token>, Vec<Token>), Diagnostic> {
+    let open = match tokens.first() {
+        Some(t) if t.get_tag() == Tag::EQUAL => t,
+        _ => return Ok((Vec::new(), tokens.to_vec())),
+    };
+
+    let close = match tokens.iter().skip(1).position(|t| t.get_tag() == Tag::EQUAL) {
+        Some(i) => i + 1,
+        None => {
+            let end = tokens.last().map_or(0, |t| t.get_columns().end);
+            return Err(
+                Diagnostic::new(0, "'=' is missing at the end of the literal constant".to_string())
+                    .with_columns(open.get_columns().start..end),
+            );
+        }
+    };
+
+    let w_value = tokens[1..close].to_vec();
+    if w_value.is_empty() {
+        return Err(Diagnostic::new(0, "literal constant is empty".to_string())
+            .with_columns(open.get_columns().start..tokens[close].get_columns().end));
+    }
+
+    let columns = w_value[0].get_columns().start..w_value[w_value.len() - 1].get_columns().end;
+    if columns.len() > MAX_LITERAL_LENGTH {
+        return Err(Diagnostic::new(
+            0,
+            format!("literal constant is longer than {MAX_LITERAL_LENGTH} characters"),
+        )
+        .with_columns(columns));
+    }
+
+    Ok((w_value, tokens[close + 1..].to_vec()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(tag: Tag, text: &str, column: usize) -> Token {
+        Token::new(tag, text.to_string()).with_columns(column..column + text.len())
+    }
+
+    #[test]
+    fn pool_and_split() {
+        let mut pool = LiteralPool::new();
+        assert_eq!(("=CON1".to_string(), true), pool.name_of(Word::new(1)));
+        assert_eq!(("=CON2".to_string(), true), pool.name_of(Word::new_from_signed(-1)));
+        assert_eq!(("=CON1".to_string(), false), pool.name_of(Word::new(1)));
+        assert_eq!(2, pool.constants().len());
+
+        // =1=,2
+        let tokens = vec![
+            token(Tag::EQUAL, "=", 4),
+            Token::new_number(1).with_columns(5..6),
+            token(Tag::EQUAL, "=", 6),
+            token(Tag::COMMA, ",", 7),
+            Token::new_number(2).with_columns(8..9),
+        ];
+        let (w_value, rest) = split_literal(&tokens).expect("valid literal");
+        assert_eq!(1, w_value.len());
+        assert_eq!(2, rest.len());
+
+        let err = split_literal(&tokens[..2]).expect_err("no closing '='");
+        assert_eq!(Some(4..6), err.columns);
+
+        // =1234567890=
+        let tokens = vec![
+            token(Tag::EQUAL, "=", 4),
+            Token::new_number(1234567890).with_columns(5..15),
+            token(Tag::EQUAL, "=", 15),
+        ];
+        let err = split_literal(&tokens).expect_err("too long");
+        assert_eq!("literal constant is longer than 9 characters", err.message);
+    }
+}
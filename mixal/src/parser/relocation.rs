@@ -0,0 +1,195 @@
+use crate::tags::Tag;
+
+/*
+ * Relocatable module: the addresses are offsets from the base of the module,
+ * the linker decides the base and adds it where an address of the module is used.
+ *
+ * value  -> absolute | address of the module + constant | external symbol + constant
+ *
+ * Only + and - can be applied to addresses of the module and external symbols,
+ * the addresses of the module can be subtracted from each other (A-B is absolute).
+ */
+
+/// What the linker adds to a value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Relocation {
+    Absolute,
+    /// the base of the module
+    Relative,
+    /// the value of an ENTRY of another module
+    External(String),
+}
+
+/// Part of the word that is relocated
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Field {
+    /// the address of an instruction, sign and bytes 1-2
+    Address,
+    /// the whole word of CON
+    Word,
+}
+
+/// Symbol exported by ENTRY
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub value: i32,
+    pub relative: bool,
+}
+
+/// Linking information of a relocatable module
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    /// the module takes `size` words from its base
+    pub size: u32,
+    /// END has the start address of the program
+    pub has_start: bool,
+    pub relocations: Vec<(u32, Field)>,
+    /// unresolved references to the symbols of other modules
+    pub externals: Vec<(u32, Field, String)>,
+    pub entries: Vec<Entry>,
+}
+
+impl Module {
+    pub fn new() -> Module {
+        Module {
+            size: 0,
+            has_start: false,
+            relocations: Vec::new(),
+            externals: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+/// Relocatable terms of a value while its expression is evaluated: how many
+/// times the base of the module and each external symbol are added
+#[derive(Debug, Clone, PartialEq)]
+pub struct Terms {
+    base: i32,
+    externals: Vec<(String, i32)>,
+}
+
+impl Terms {
+    pub fn absolute() -> Terms {
+        Terms {
+            base: 0,
+            externals: Vec::new(),
+        }
+    }
+
+    pub fn of(relocation: Relocation) -> Terms {
+        match relocation {
+            Relocation::Absolute => Terms::absolute(),
+            Relocation::Relative => Terms {
+                base: 1,
+                externals: Vec::new(),
+            },
+            Relocation::External(name) => Terms {
+                base: 0,
+                externals: vec![(name, 1)],
+            },
+        }
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.base == 0 && self.externals.iter().all(|(_, n)| *n == 0)
+    }
+
+    pub fn negate(mut self) -> Terms {
+        self.base = -self.base;
+        for (_, n) in self.externals.iter_mut() {
+            *n = -*n;
+        }
+        self
+    }
+
+    /// Terms of `self op right`, `op_text` is the operation for the message
+    pub fn combine(mut self, op: Tag, op_text: &str, right: Terms) -> Result<Terms, String> {
+        let right = match op {
+            Tag::PLUS => right,
+            Tag::MINUS => right.negate(),
+            _ if self.is_absolute() && right.is_absolute() => return Ok(Terms::absolute()),
+            _ => {
+                return Err(format!(
+                    "'{op_text}' can't be applied to a relocatable value, only + and - can"
+                ))
+            }
+        };
+
+        self.base += right.base;
+        for (name, n) in right.externals {
+            match self.externals.iter_mut().find(|(e, _)| *e == name) {
+                Some((_, count)) => *count += n,
+                None => self.externals.push((name, n)),
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn relocation(&self) -> Result<Relocation, String> {
+        let externals: Vec<&(String, i32)> =
+            self.externals.iter().filter(|(_, n)| *n != 0).collect();
+        match (self.base, &externals[..]) {
+            (0, []) => Ok(Relocation::Absolute),
+            (1, []) => Ok(Relocation::Relative),
+            (_, []) => Err(
+                "value can't be relocated, it should be an address of the module plus a constant"
+                    .to_string(),
+            ),
+            (0, [(name, 1)]) => Ok(Relocation::External(name.clone())),
+            (_, [(name, _), ..]) => Err(format!(
+                "external symbol {name} can only be added to a constant"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn external(name: &str) -> Terms {
+        Terms::of(Relocation::External(name.to_string()))
+    }
+
+    #[test]
+    fn terms() {
+        let relative = || Terms::of(Relocation::Relative);
+        let absolute = Terms::absolute;
+
+        let sum = relative().combine(Tag::PLUS, "+", absolute()).expect("A+1");
+        assert_eq!(Ok(Relocation::Relative), sum.relocation());
+        let difference = relative()
+            .combine(Tag::MINUS, "-", relative())
+            .expect("A-B");
+        assert_eq!(Ok(Relocation::Absolute), difference.relocation());
+        let twice = relative().combine(Tag::PLUS, "+", relative()).expect("A+B");
+        assert!(twice.relocation().is_err());
+
+        let x = external("X")
+            .combine(Tag::MINUS, "-", absolute())
+            .expect("X-1");
+        assert_eq!(Ok(Relocation::External("X".to_string())), x.relocation());
+        let x = external("X")
+            .combine(Tag::PLUS, "+", relative())
+            .expect("X+A");
+        assert_eq!(
+            Err("external symbol X can only be added to a constant".to_string()),
+            x.relocation()
+        );
+        let x = external("X")
+            .combine(Tag::MINUS, "-", external("X"))
+            .expect("X-X");
+        assert_eq!(Ok(Relocation::Absolute), x.relocation());
+
+        assert_eq!(
+            Err("'*' can't be applied to a relocatable value, only + and - can".to_string()),
+            relative().combine(Tag::MULTIPLY, "*", absolute())
+        );
+        assert_eq!(
+            Ok(Terms::absolute()),
+            absolute().combine(Tag::DEVIDE, "/", absolute())
+        );
+    }
+}
@@ -1,10 +1,14 @@
-use crate::word::*;
+use crate::lexer::token::Token;
+use crate::parser::relocation::Relocation;
+use mix_core::word::Word;
 
 use std::collections::HashMap;
+use std::collections::HashSet;
 
+/// Local symbols as in Knuth: dB is the last dH before the line, dF is the first
+/// dH after it, the order of the source lines is used, not the addresses
 pub struct LocalSymbolTabel {
-    local_symbols: HashMap<u8, Vec<u32>>,
-    // local_symbols: HashMap<u8, Vec<(u32, u32)>>, //TODO: need to jump over line num not addresses
+    local_symbols: HashMap<u8, Vec<(i32, usize)>>, // value, line of dH
 }
 impl LocalSymbolTabel {
     pub fn new() -> LocalSymbolTabel {
@@ -13,72 +17,134 @@ impl LocalSymbolTabel {
         }
     }
 
-    pub fn get(&self, name: String, current_addrs: u32) -> u32 {
-        let digit = self.get_digit(name.clone());
-        let mut addresses = self
-            .local_symbols
-            .get(&digit)
-            .expect("local symbol not found")
-            .to_vec();
+    pub fn get(&self, name: String, current_line: usize) -> Result<i32, String> {
+        self.find(&name, current_line).map(|(value, _)| value)
+    }
 
-        let is_up = self.is_up_direction(name.clone());
-        if is_up {
-            addresses.reverse();
+    /// Value and the line of the dH referenced by dB or dF
+    pub fn find(&self, name: &String, current_line: usize) -> Result<(i32, usize), String> {
+        if !self.is_reference(name) {
+            let digit = self.get_digit(name);
+            return Err(format!(
+                "local symbol {name} can't be referenced, use {digit}B or {digit}F"
+            ));
         }
 
-        for addr in addresses {
-            if is_up {
-                if addr < current_addrs {
-                    return addr;
-                }
-            } else {
-                if addr > current_addrs {
-                    return addr;
-                }
+        let definitions = match self.local_symbols.get(&self.get_digit(name)) {
+            None => return Err(format!("local symbol {name} is not defined")),
+            Some(definitions) => definitions,
+        };
+
+        let found = if self.is_up_direction(name) {
+            definitions.iter().rev().find(|(_, line)| *line < current_line)
+        } else {
+            definitions.iter().find(|(_, line)| *line > current_line)
+        };
+        match found {
+            Some(definition) => Ok(*definition),
+            None if self.is_up_direction(name) => {
+                Err(format!("local symbol {name} is not defined before this line"))
             }
+            None => Err(format!("local symbol {name} is not defined after this line")),
         }
-        panic!("local symbol not found {name}:{current_addrs}");
     }
-    fn put(&mut self, name: String, address: u32) {
-        let digit = self.get_digit(name);
-        let mut local_symbols_addrs = match self.local_symbols.get(&digit) {
-            None => Vec::new(),
-            Some(v) => v.to_vec(),
-        };
-        local_symbols_addrs.push(address);
-        self.local_symbols.insert(digit, local_symbols_addrs);
+
+    fn put(&mut self, name: String, value: i32, line: usize) -> Result<(), String> {
+        if self.is_reference(&name) {
+            let digit = self.get_digit(&name);
+            return Err(format!("local symbol {name} can't be defined, use {digit}H"));
+        }
+        self.local_symbols
+            .entry(self.get_digit(&name))
+            .or_default()
+            .push((value, line));
+        Ok(())
     }
 
-    pub fn is_local_symbol(&self, name: &String) -> bool {
-        if name.len() == 2 {
-            let mut chars = name.chars();
-            let digit = chars.next().expect("error");
-            let direction = chars.next().expect("error");
+    /// dH, value and line of all definitions
+    pub fn definitions(&self) -> Vec<(String, i32, usize)> {
+        let mut result = Vec::new();
+        for (digit, definitions) in &self.local_symbols {
+            for (value, line) in definitions {
+                result.push((format!("{digit}H"), *value, *line));
+            }
+        }
+        result
+    }
 
-            return digit.is_numeric()
-                && (direction == 'H' || direction == 'B' || direction == 'F');
+    pub fn is_local_symbol(&self, name: &String) -> bool {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(digit), Some(direction), None) => {
+                digit.is_ascii_digit() && (direction == 'H' || direction == 'B' || direction == 'F')
+            }
+            _ => false,
         }
+    }
 
-        return false;
+    fn is_reference(&self, name: &String) -> bool {
+        !name.ends_with('H')
     }
 
-    fn get_digit(&self, name: String) -> u8 {
+    fn get_digit(&self, name: &String) -> u8 {
         let mut chars = name.chars();
         let digit = chars.next().expect("error");
         digit.to_digit(10).expect("error") as u8
     }
 
-    fn is_up_direction(&self, name: String) -> bool {
-        let mut chars = name.chars();
-        chars.next();
-        chars.next().expect("error") == 'B'
+    fn is_up_direction(&self, name: &String) -> bool {
+        name.ends_with('B')
+    }
+}
+
+/// A symbol has 1-10 letters A-Z and digits, one of them at least is a letter
+pub fn check_symbol(name: &str) -> Result<(), String> {
+    if name.chars().count() > 10 {
+        return Err(format!("symbol {name} is longer than 10 characters"));
     }
+    if !name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+        return Err(format!("symbol {name} can have only letters A-Z and digits"));
+    }
+    if !name.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(format!("symbol {name} needs a letter"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SymbolKind {
+    Equ,
+    Label,
+    Local,    // dH
+    Literal,  // =W-value=, the address of the constant in the pool
+    External, // EXTERN, defined in another module
+}
+
+/// Symbol with the line that defines it and the lines that use it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub value: i32,
+    pub line: usize,
+    pub uses: Vec<usize>,
 }
 
 pub struct SymbolTable {
     equ_values: HashMap<String, Word>,
     references: HashMap<String, u32>,
     local_symbols: LocalSymbolTabel,
+
+    lines: HashMap<String, usize>,        // name -> defining line
+    literals: HashMap<String, String>,    // label of the constant -> =W-value=
+    uses: Vec<(String, usize)>,           // name, line
+
+    // relocatable module, labels are offsets from its base
+    relocatable: bool,
+    externals: HashSet<String>,
+    relative_equs: HashSet<String>,
+    local_equs: HashMap<usize, bool>, // line of dH EQU -> relative
+    entries: Vec<(Token, usize)>,     // ENTRY symbol, line
 }
 impl SymbolTable {
     pub fn new() -> SymbolTable {
@@ -86,32 +152,206 @@ impl SymbolTable {
             equ_values: HashMap::new(),
             references: HashMap::new(),
             local_symbols: LocalSymbolTabel::new(),
+            lines: HashMap::new(),
+            literals: HashMap::new(),
+            uses: Vec::new(),
+            relocatable: false,
+            externals: HashSet::new(),
+            relative_equs: HashSet::new(),
+            local_equs: HashMap::new(),
+            entries: Vec::new(),
         }
     }
-    pub fn get(&self, name: String, current_addrs: u32) -> i32 {
+
+    /// Symbols of a relocatable module
+    pub fn relocatable() -> SymbolTable {
+        SymbolTable {
+            relocatable: true,
+            ..SymbolTable::new()
+        }
+    }
+
+    pub fn is_relocatable(&self) -> bool {
+        self.relocatable
+    }
+    /// `current_line` is the source line of the reference, it's used for dB and dF
+    pub fn get(&self, name: String, current_line: usize) -> Result<i32, String> {
+        self.get_word(name, current_line).map(|w| w.get_signed_value())
+    }
+
+    /// Value with its sign, EQU keeps minus zero
+    pub fn get_word(&self, name: String, current_line: usize) -> Result<Word, String> {
         if self.local_symbols.is_local_symbol(&name) {
-            return self.local_symbols.get(name, current_addrs) as i32;
+            return self.local_symbols.get(name, current_line).map(Word::new_from_signed);
         }
 
+        if self.externals.contains(&name) {
+            return Ok(Word::new(0));
+        }
         return match self.equ_values.get(&name) {
-            Some(v) => v.get_signed_value(),
+            Some(v) => Ok(*v),
             None => match self.references.get(&name) {
-                Some(v) => *v as i32,
-                None => panic!("symbol {} not found", name),
+                Some(v) => Ok(Word::new(*v)),
+                None => Err(format!("symbol {} is not defined", name)),
             },
         };
     }
 
-    pub fn put_equ(&mut self, name: String, value: Word) {
+    /// What the linker adds to the value of the symbol, everything is absolute
+    /// outside of relocatable modules
+    pub fn relocation_of(&self, name: &String, current_line: usize) -> Relocation {
+        if !self.relocatable {
+            return Relocation::Absolute;
+        }
+        let relative = if self.local_symbols.is_local_symbol(name) {
+            match self.local_symbols.find(name, current_line) {
+                Ok((_, line)) => self.local_equs.get(&line).copied().unwrap_or(true),
+                Err(_) => false,
+            }
+        } else if self.externals.contains(name) {
+            return Relocation::External(name.clone());
+        } else {
+            self.references.contains_key(name) || self.relative_equs.contains(name)
+        };
+        match relative {
+            true => Relocation::Relative,
+            false => Relocation::Absolute,
+        }
+    }
+
+    /// All symbols sorted by name and line, uses are resolved by the current definitions
+    pub fn symbols(&self) -> Vec<Symbol> {
+        let mut result: Vec<Symbol> = Vec::new();
+        let line_of = |name: &String| self.lines.get(name).copied().unwrap_or(0);
+
+        for (name, value) in &self.equ_values {
+            result.push(Symbol {
+                name: name.clone(),
+                kind: SymbolKind::Equ,
+                value: value.get_signed_value(),
+                line: line_of(name),
+                uses: self.uses_of(|n, _| n == name),
+            });
+        }
+        for (name, addr) in &self.references {
+            let (name_to_show, kind) = match self.literals.get(name) {
+                Some(text) => (text.clone(), SymbolKind::Literal),
+                None => (name.clone(), SymbolKind::Label),
+            };
+            result.push(Symbol {
+                name: name_to_show,
+                kind,
+                value: *addr as i32,
+                line: line_of(name),
+                uses: self.uses_of(|n, _| n == name),
+            });
+        }
+        for name in &self.externals {
+            result.push(Symbol {
+                name: name.clone(),
+                kind: SymbolKind::External,
+                value: 0,
+                line: line_of(name),
+                uses: self.uses_of(|n, _| n == name),
+            });
+        }
+        for (name, value, line) in self.local_symbols.definitions() {
+            let digit = &name[..1];
+            result.push(Symbol {
+                uses: self.uses_of(|n, at| {
+                    n.starts_with(digit)
+                        && self.local_symbols.is_local_symbol(n)
+                        && self.local_symbols.find(n, at).map(|(_, l)| l) == Ok(line)
+                }),
+                name,
+                kind: SymbolKind::Local,
+                value,
+                line,
+            });
+        }
+        result.sort_by(|a, b| a.name.cmp(&b.name).then(a.line.cmp(&b.line)));
+        result
+    }
+
+    fn uses_of(&self, is_use: impl Fn(&String, usize) -> bool) -> Vec<usize> {
+        let mut result: Vec<usize> = Vec::new();
+        for (name, line) in &self.uses {
+            if is_use(name, *line) && result.last() != Some(line) {
+                result.push(*line);
+            }
+        }
+        result
+    }
+
+    pub fn put_equ(&mut self, name: String, value: Word, line: usize) -> Result<(), String> {
+        if self.local_symbols.is_local_symbol(&name) {
+            self.local_equs.insert(line, false);
+            return self.local_symbols.put(name, value.get_signed_value(), line);
+        }
+        self.check_not_defined(&name)?;
+        self.lines.insert(name.clone(), line);
         self.equ_values.insert(name, value);
+        Ok(())
+    }
+
+    /// EQU of `name` at `line` is an address of the module
+    pub fn set_relative(&mut self, name: String, line: usize) {
+        if self.local_symbols.is_local_symbol(&name) {
+            self.local_equs.insert(line, true);
+        } else {
+            self.relative_equs.insert(name);
+        }
+    }
+
+    /// EXTERN symbol, its value is 0 until the linker resolves it
+    pub fn put_extern(&mut self, name: String, line: usize) -> Result<(), String> {
+        if self.local_symbols.is_local_symbol(&name) {
+            return Err(format!("local symbol {name} can't be external"));
+        }
+        self.check_not_defined(&name)?;
+        self.lines.insert(name.clone(), line);
+        self.externals.insert(name);
+        Ok(())
+    }
+
+    /// ENTRY symbol, it's resolved when the module is assembled
+    pub fn put_entry(&mut self, name: Token, line: usize) {
+        self.entries.push((name, line));
+    }
+
+    pub fn entries(&self) -> &[(Token, usize)] {
+        &self.entries
     }
 
-    pub fn put_reference(&mut self, name: String, address: u32) {
+    pub fn put_reference(&mut self, name: String, address: u32, line: usize) -> Result<(), String> {
         if self.local_symbols.is_local_symbol(&name) {
-            self.local_symbols.put(name, address);
-            return;
+            return self.local_symbols.put(name, address as i32, line);
         }
+        self.check_not_defined(&name)?;
+        self.lines.entry(name.clone()).or_insert(line);
         self.references.insert(name, address);
+        Ok(())
+    }
+
+    /// `name` is the label of the generated constant, it's defined later by `put_reference`
+    pub fn put_literal(&mut self, name: String, text: String, line: usize) {
+        self.lines.insert(name.clone(), line);
+        self.literals.insert(name, text);
+    }
+
+    /// Use of a symbol for the cross-reference
+    pub fn put_use(&mut self, name: String, line: usize) {
+        self.uses.push((name, line));
+    }
+
+    fn check_not_defined(&self, name: &String) -> Result<(), String> {
+        if self.equ_values.contains_key(name)
+            || self.references.contains_key(name)
+            || self.externals.contains(name)
+        {
+            return Err(format!("symbol {name} is already defined"));
+        }
+        Ok(())
     }
 }
 
@@ -122,22 +362,51 @@ mod tests {
     #[test]
     fn local_symbol_table() {
         let mut table = LocalSymbolTabel::new();
-        table.put("2H".to_string(), 2);
-        table.put("2H".to_string(), 4);
-        table.put("2H".to_string(), 10);
+        table.put("2H".to_string(), 2, 1).expect("dH");
+        table.put("2H".to_string(), 4, 5).expect("dH");
+        // ORIG moved the location counter back
+        table.put("2H".to_string(), 1, 9).expect("dH");
+
+        table.put("3H".to_string(), 20, 2).expect("dH");
+        table.put("3H".to_string(), 30, 3).expect("dH");
+
+        assert_eq!(Ok(4), table.get("2B".to_string(), 7));
+        assert_eq!(Ok(2), table.get("2B".to_string(), 3));
+        assert_eq!(Ok(1), table.get("2B".to_string(), 200));
+        assert_eq!(Ok(1), table.get("2F".to_string(), 6));
 
-        table.put("3H".to_string(), 20);
-        table.put("3H".to_string(), 30);
-        table.put("3H".to_string(), 100);
+        // the line of the definition itself
+        assert_eq!(Ok(2), table.get("2B".to_string(), 5));
+        assert_eq!(Ok(1), table.get("2F".to_string(), 5));
 
-        // assert_eq!(4, table.get("2B".to_string(), 0));
+        assert_eq!(Ok(20), table.get("3F".to_string(), 0));
+        assert_eq!(Ok(30), table.get("3F".to_string(), 2));
 
-        assert_eq!(4, table.get("2B".to_string(), 7));
-        assert_eq!(2, table.get("2B".to_string(), 3));
-        assert_eq!(10, table.get("2B".to_string(), 200));
+        assert_eq!(
+            Err("local symbol 3F is not defined after this line".to_string()),
+            table.get("3F".to_string(), 3)
+        );
+        assert_eq!(
+            Err("local symbol 2B is not defined before this line".to_string()),
+            table.get("2B".to_string(), 1)
+        );
+        assert_eq!(
+            Err("local symbol 4F is not defined".to_string()),
+            table.get("4F".to_string(), 1)
+        );
+        assert_eq!(
+            Err("local symbol 2H can't be referenced, use 2B or 2F".to_string()),
+            table.get("2H".to_string(), 4)
+        );
+        assert_eq!(
+            Err("local symbol 2F can't be defined, use 2H".to_string()),
+            table.put("2F".to_string(), 1, 10)
+        );
 
-        assert_eq!(20, table.get("3F".to_string(), 0));
-        assert_eq!(30, table.get("3F".to_string(), 22));
-        assert_eq!(2, table.get("2F".to_string(), 0));
+        // characters, not bytes
+        assert!(table.is_local_symbol(&"2H".to_string()));
+        assert!(!table.is_local_symbol(&"é".to_string()));
+        assert!(!table.is_local_symbol(&"٣H".to_string()));
+        assert!(!table.is_local_symbol(&"2HH".to_string()));
     }
 }
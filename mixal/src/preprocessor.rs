@@ -0,0 +1,514 @@
+use crate::diagnostic::Diagnostic;
+use crate::lexer::{is_operation, Lexer};
+use crate::macros::{fields, Fields};
+use crate::parser::addr_parser::AddrParser;
+use crate::parser::symbol_table::SymbolTable;
+use crate::tags::MixInstructions;
+use crate::Options;
+use mix_core::word::Word;
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/*
+ * Directives handled before macros and the assembly:
+ *
+ *          INCLUDE "print.mixal"   the lines of the file, it's searched next to the
+ *                                  including file and then in the include paths
+ *          IF      expr            the lines up to ELSE or ENDIF if expr isn't zero
+ *          IFDEF   SYMBOL          the same if SYMBOL is defined before the line
+ *          IFNDEF  SYMBOL
+ *          ELSE
+ *          ENDIF
+ *
+ * Expressions and IFDEF see the defines of the options, EQU and labels of the lines
+ * before. IF and its ENDIF are in the same file.
+ */
+
+pub const DIRECTIVES: [&str; 6] = ["INCLUDE", "IF", "IFDEF", "IFNDEF", "ELSE", "ENDIF"];
+
+/// Line of the program, `file` is None for the main source. `line` is 1-based in
+/// its file, `main_line` is the line in the main source, INCLUDE for included lines.
+/// Lines expanded from macros have the place of the invocation
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceLine {
+    pub text: String,
+    pub file: Option<String>,
+    pub line: usize,
+    pub main_line: usize,
+    pub macro_name: Option<String>, // the line is expanded from the macro
+    pub assembled: bool,            // false for directives, skipped lines, definitions, invocations
+}
+
+impl SourceLine {
+    pub fn new(text: String, file: Option<String>, line: usize) -> SourceLine {
+        SourceLine {
+            text,
+            file,
+            line,
+            main_line: line,
+            macro_name: None,
+            assembled: true,
+        }
+    }
+
+    pub fn diagnostic(&self, message: String) -> Diagnostic {
+        Diagnostic::new(self.line, message).in_file(self.file.clone())
+    }
+}
+
+/// Branch of IF in progress
+struct Condition {
+    line: SourceLine,
+    outer_active: bool,
+    active: bool,
+    taken: bool, // a branch is taken
+    has_else: bool,
+}
+
+struct Preprocessor<'a> {
+    mix_inst: &'a MixInstructions<'a>,
+    include_paths: Vec<PathBuf>,
+    symbols: SymbolTable, // defines and EQU for expressions
+    labels: HashSet<String>,
+    stack: Vec<(PathBuf, String)>, // files being included and their names, for cycles
+    included: Vec<(String, String)>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Lines of the program with the included files, the included files with
+/// their names and sources, and the problems
+pub fn preprocess(
+    mix_inst: &MixInstructions,
+    source_lines: &[String],
+    options: &Options,
+) -> (Vec<SourceLine>, Vec<(String, String)>, Vec<Diagnostic>) {
+    let mut preprocessor = Preprocessor {
+        mix_inst,
+        include_paths: options.include_paths.iter().map(PathBuf::from).collect(),
+        symbols: SymbolTable::new(),
+        labels: HashSet::new(),
+        stack: Vec::new(),
+        included: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    for (name, value) in &options.defines {
+        let value = Word::new_from_signed(*value);
+        if let Err(message) = preprocessor.symbols.put_equ(name.clone(), value, 0) {
+            preprocessor.diagnostics.push(Diagnostic::new(0, message));
+        }
+    }
+
+    let main = Path::new(&options.name);
+    if let Ok(path) = fs::canonicalize(main) {
+        let name = main.file_name().unwrap_or_default().to_string_lossy();
+        preprocessor.stack.push((path, name.to_string()));
+    }
+    let dir = main.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    let mut result = Vec::new();
+    preprocessor.process(source_lines, None, &dir, None, &mut result);
+    (result, preprocessor.included, preprocessor.diagnostics)
+}
+
+impl Preprocessor<'_> {
+    /// `main_line` is the INCLUDE of the main source for included files
+    fn process(
+        &mut self,
+        lines: &[String],
+        file: Option<String>,
+        dir: &Path,
+        main_line: Option<usize>,
+        result: &mut Vec<SourceLine>,
+    ) {
+        let mut conditions: Vec<Condition> = Vec::new();
+
+        for (i, text) in lines.iter().enumerate() {
+            let mut line = SourceLine::new(text.clone(), file.clone(), i + 1);
+            line.main_line = main_line.unwrap_or(i + 1);
+            let active = conditions.last().is_none_or(|c| c.active);
+
+            let fields = match fields(text).map(directive_fields) {
+                Some(fields) if DIRECTIVES.contains(&&fields.op[..]) => fields,
+                other => {
+                    if let (Some(fields), true) = (other, active) {
+                        self.define(&fields, &line);
+                    }
+                    line.assembled = active;
+                    result.push(line);
+                    continue;
+                }
+            };
+            line.assembled = false;
+            result.push(line.clone());
+
+            match &fields.op[..] {
+                "INCLUDE" if active => self.include(&fields, &line, dir, result),
+                "INCLUDE" => {}
+                "IF" | "IFDEF" | "IFNDEF" => {
+                    let value = active && self.condition(&fields, &line);
+                    conditions.push(Condition {
+                        line,
+                        outer_active: active,
+                        active: value,
+                        taken: value,
+                        has_else: false,
+                    });
+                }
+                "ELSE" => match conditions.last_mut() {
+                    None => self.diagnostics.push(
+                        line.diagnostic("ELSE without IF".to_string())
+                            .with_columns(op_columns(&fields)),
+                    ),
+                    Some(c) if c.has_else => {
+                        let message = format!("ELSE is repeated for IF at line {}", c.line.line);
+                        self.diagnostics
+                            .push(line.diagnostic(message).with_columns(op_columns(&fields)));
+                    }
+                    Some(c) => {
+                        c.has_else = true;
+                        c.active = c.outer_active && !c.taken;
+                        c.taken = true;
+                    }
+                },
+                _ => {
+                    // ENDIF
+                    if conditions.pop().is_none() {
+                        self.diagnostics.push(
+                            line.diagnostic("ENDIF without IF".to_string())
+                                .with_columns(op_columns(&fields)),
+                        );
+                    }
+                }
+            }
+        }
+
+        for c in conditions {
+            self.diagnostics.push(
+                c.line
+                    .diagnostic("ENDIF is missing for this IF".to_string()),
+            );
+        }
+    }
+
+    /// Remembers EQU values and labels for the next conditions
+    fn define(&mut self, fields: &Fields, line: &SourceLine) {
+        let label = &fields.label;
+        if label.is_empty()
+            || is_operation(self.mix_inst, label)
+            || label.len() == 2 && label.starts_with(|c: char| c.is_ascii_digit())
+        {
+            return;
+        }
+
+        if fields.op == "EQU" {
+            let (address, column) = &fields.address;
+            let value = Lexer::new()
+                .parse_address(address, *column)
+                .ok()
+                .and_then(|tokens| {
+                    AddrParser::new(&self.symbols, 0, &tokens)
+                        .at_line(line.line)
+                        .aif()
+                        .ok()
+                });
+            if let Some((Some(value), _, _)) = value {
+                // redefinitions are reported by the assembler
+                let _ = self.symbols.put_equ(label.clone(), value, line.line);
+                return;
+            }
+        }
+        self.labels.insert(label.clone());
+    }
+
+    fn condition(&mut self, fields: &Fields, line: &SourceLine) -> bool {
+        let (address, column) = &fields.address;
+        if address.is_empty() {
+            let end = fields.op_column + fields.op.chars().count();
+            self.diagnostics.push(
+                line.diagnostic(format!("{} needs an operand", fields.op))
+                    .with_columns(end..end + 1),
+            );
+            return false;
+        }
+
+        if fields.op != "IF" {
+            let defined = self.labels.contains(address)
+                || self.symbols.get_word(address.clone(), line.line).is_ok();
+            return defined == (fields.op == "IFDEF");
+        }
+
+        let value = Lexer::new()
+            .parse_address(address, *column)
+            .and_then(|tokens| {
+                AddrParser::new(&self.symbols, 0, &tokens)
+                    .at_line(line.line)
+                    .aif()
+                    .map_err(|d| d.with_line(line.line))
+            });
+        match value {
+            Ok((a_part, _, _)) => a_part.is_some_and(|w| w.get_signed_value() != 0),
+            Err(diagnostic) => {
+                self.diagnostics
+                    .push(diagnostic.with_line(line.line).in_file(line.file.clone()));
+                false
+            }
+        }
+    }
+
+    fn include(
+        &mut self,
+        fields: &Fields,
+        line: &SourceLine,
+        dir: &Path,
+        result: &mut Vec<SourceLine>,
+    ) {
+        let (name, columns) = match quoted(&line.text, fields) {
+            Some(name) => name,
+            None => {
+                let (address, column) = &fields.address;
+                let columns = *column..column + address.chars().count().max(1);
+                self.diagnostics.push(
+                    line.diagnostic("INCLUDE needs a file name in quotes".to_string())
+                        .with_columns(columns),
+                );
+                return;
+            }
+        };
+
+        let candidates = std::iter::once(dir.to_path_buf()).chain(self.include_paths.clone());
+        let path = match candidates.map(|d| d.join(&name)).find(|p| p.is_file()) {
+            Some(path) => path,
+            None => {
+                self.diagnostics.push(
+                    line.diagnostic(format!("file \"{name}\" is not found"))
+                        .with_columns(columns),
+                );
+                return;
+            }
+        };
+
+        let canonical = fs::canonicalize(&path).unwrap_or(path.clone());
+        if let Some(i) = self.stack.iter().position(|(p, _)| *p == canonical) {
+            let message = match i == self.stack.len() - 1 {
+                true => format!("file \"{name}\" includes itself"),
+                false => {
+                    let chain: Vec<&str> = self.stack[i..].iter().map(|(_, n)| &n[..]).collect();
+                    format!(
+                        "file \"{name}\" is included in a cycle: {} → {name}",
+                        chain.join(" → ")
+                    )
+                }
+            };
+            self.diagnostics
+                .push(line.diagnostic(message).with_columns(columns));
+            return;
+        }
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) => {
+                self.diagnostics.push(
+                    line.diagnostic(format!("can't read \"{name}\": {e}"))
+                        .with_columns(columns),
+                );
+                return;
+            }
+        };
+
+        let file_name = path.to_string_lossy().to_string();
+        let lines: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+        if !self.included.iter().any(|(n, _)| *n == file_name) {
+            self.included.push((file_name.clone(), source));
+        }
+
+        self.stack.push((canonical, name));
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        self.process(&lines, Some(file_name), &dir, Some(line.main_line), result);
+        self.stack.pop();
+    }
+}
+
+/// ENDIF at the start of a line is the operation, not a label
+fn directive_fields(fields: Fields) -> Fields {
+    if DIRECTIVES.contains(&&fields.label[..]) && !DIRECTIVES.contains(&&fields.op[..]) {
+        return Fields {
+            label: String::new(),
+            op: fields.label,
+            op_column: 0,
+            address: (fields.op, fields.op_column),
+        };
+    }
+    fields
+}
+
+/// "name" after the operation with its columns
+fn quoted(text: &str, fields: &Fields) -> Option<(String, std::ops::Range<usize>)> {
+    let start = fields.address.1;
+    let rest: String = text.chars().skip(start).collect();
+    let name: String = rest
+        .strip_prefix('"')?
+        .chars()
+        .take_while(|c| *c != '"')
+        .collect();
+    if name.is_empty() || rest.chars().nth(name.chars().count() + 1) != Some('"') {
+        return None;
+    }
+    Some((name.clone(), start..start + name.chars().count() + 2))
+}
+
+fn op_columns(fields: &Fields) -> std::ops::Range<usize> {
+    fields.op_column..fields.op_column + fields.op.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocess_text(source: &str, options: &Options) -> (Vec<String>, Vec<Diagnostic>) {
+        let mix_inst = MixInstructions::new();
+        let lines: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+        let (lines, _, diagnostics) = preprocess(&mix_inst, &lines, options);
+        let assembled = lines
+            .iter()
+            .filter(|l| l.assembled)
+            .map(|l| l.text.trim().to_string())
+            .collect();
+        (assembled, diagnostics)
+    }
+
+    #[test]
+    fn conditions() {
+        let source = "N EQU 2\n\
+                      \x20IF N-2\n LDA 1\n ELSE\n LDA 2\n\
+                      \x20IFDEF DEBUG\n LDA 3\n ENDIF\n ENDIF\n\
+                      \x20IFNDEF N\n LDA 4\n ELSE\n LDA 5\n ENDIF\n\
+                      START IFDEF START\n LDA 6\n ENDIF\n";
+        let options = Options {
+            defines: vec![("DEBUG".to_string(), 1)],
+            ..Options::new("p.mixal")
+        };
+        let (lines, diagnostics) = preprocess_text(source, &options);
+        assert_eq!(Vec::<Diagnostic>::new(), diagnostics);
+        assert_eq!(vec!["N EQU 2", "LDA 2", "LDA 3", "LDA 5"], lines);
+
+        let (_, diagnostics) = preprocess_text(
+            " ELSE\n ENDIF\n IF\n ELSE\n IF X\n ELSE\n ELSE\n",
+            &Options::new("p"),
+        );
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|d| (d.line, &d.message[..]))
+            .collect();
+        assert_eq!(
+            vec![
+                (1, "ELSE without IF"),
+                (2, "ENDIF without IF"),
+                (3, "IF needs an operand"),
+                (5, "symbol X is not defined"),
+                (7, "ELSE is repeated for IF at line 5"),
+                (3, "ENDIF is missing for this IF"),
+                (5, "ENDIF is missing for this IF"),
+            ],
+            messages
+        );
+    }
+
+    #[test]
+    fn include() {
+        let dir = std::env::temp_dir().join(format!("mixal_include_{}", std::process::id()));
+        let lib = dir.join("lib");
+        fs::create_dir_all(&lib).expect("temp dir");
+        fs::write(
+            lib.join("print.mixal"),
+            " OUT 0(18)\n INCLUDE \"units.mixal\"\n",
+        )
+        .expect("file");
+        fs::write(lib.join("units.mixal"), " IOC 0(18)\n").expect("file");
+        fs::write(dir.join("loop.mixal"), " INCLUDE \"loop.mixal\"\n").expect("file");
+
+        let main = dir.join("main.mixal");
+        let options = Options {
+            include_paths: vec![lib.to_string_lossy().to_string()],
+            ..Options::new(&main.to_string_lossy())
+        };
+        let source = " INCLUDE \"print.mixal\"\n HLT\n INCLUDE \"loop.mixal\"\n INCLUDE \"none\"\n INCLUDE none\n";
+
+        let mix_inst = MixInstructions::new();
+        let lines: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+        let (lines, included, diagnostics) = preprocess(&mix_inst, &lines, &options);
+
+        let print = lib.join("print.mixal").to_string_lossy().to_string();
+        let units = lib.join("units.mixal").to_string_lossy().to_string();
+        let assembled: Vec<_> = lines
+            .iter()
+            .filter(|l| l.assembled)
+            .map(|l| (l.text.trim(), l.file.clone(), l.line, l.main_line))
+            .collect();
+        assert_eq!(
+            vec![
+                ("OUT 0(18)", Some(print.clone()), 1, 1),
+                ("IOC 0(18)", Some(units.clone()), 1, 1),
+                ("HLT", None, 2, 2),
+            ],
+            assembled
+        );
+        let loop_file = dir.join("loop.mixal").to_string_lossy().to_string();
+        assert_eq!(
+            vec![print, units, loop_file.clone()],
+            included.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                Diagnostic::new(1, "file \"loop.mixal\" includes itself".to_string())
+                    .in_file(Some(loop_file))
+                    .with_columns(9..21),
+                Diagnostic::new(4, "file \"none\" is not found".to_string()).with_columns(9..15),
+                Diagnostic::new(5, "INCLUDE needs a file name in quotes".to_string())
+                    .with_columns(9..13),
+            ],
+            diagnostics
+        );
+        fs::remove_dir_all(&dir).expect("temp dir");
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = std::env::temp_dir().join(format!("mixal_cycle_{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("temp dir");
+        fs::write(dir.join("a.mixal"), " INCLUDE \"b.mixal\"\n").expect("file");
+        fs::write(dir.join("b.mixal"), " NOP\n INCLUDE \"a.mixal\"\n").expect("file");
+        fs::write(dir.join("c.mixal"), " INCLUDE \"main.mixal\"\n").expect("file");
+
+        let main = dir.join("main.mixal");
+        fs::write(&main, "").expect("file");
+        let options = Options::new(&main.to_string_lossy());
+        let source = " INCLUDE \"a.mixal\"\n INCLUDE \"c.mixal\"\n";
+
+        let mix_inst = MixInstructions::new();
+        let lines: Vec<String> = source.lines().map(|l| l.to_string()).collect();
+        let (_, _, diagnostics) = preprocess(&mix_inst, &lines, &options);
+
+        let file = |name: &str| Some(dir.join(name).to_string_lossy().to_string());
+        assert_eq!(
+            vec![
+                Diagnostic::new(
+                    2,
+                    "file \"a.mixal\" is included in a cycle: a.mixal → b.mixal → a.mixal"
+                        .to_string()
+                )
+                .in_file(file("b.mixal"))
+                .with_columns(9..18),
+                Diagnostic::new(
+                    1,
+                    "file \"main.mixal\" is included in a cycle: main.mixal → c.mixal → main.mixal"
+                        .to_string()
+                )
+                .in_file(file("c.mixal"))
+                .with_columns(9..21),
+            ],
+            diagnostics
+        );
+        fs::remove_dir_all(&dir).expect("temp dir");
+    }
+}
@@ -1,43 +1,17 @@
 use crate::lexer::split_whitespace_once;
 use crate::parser::Printable;
-use std::collections::HashMap;
-
-const SYMBOLS: [char; 56] = [
-    ' ', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{0394}', 'J', 'K', 'L', 'M', 'N', 'O',
-    'P', 'Q', 'R', '\u{2211}', '\u{03A0}', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '0', '1', '2',
-    '3', '4', '5', '6', '7', '8', '9', '.', ',', '(', ')', '+', '-', '*', '/', '=', '$', '<', '>',
-    '@', ';', ':', '\'',
-];
-
-struct AlfSymbols {
-    symbol_ids: HashMap<char, u8>,
-}
-impl AlfSymbols {
-    pub fn new() -> AlfSymbols {
-        let mut symbols = HashMap::new();
-        let mut i = 0;
-        for c in SYMBOLS {
-            symbols.insert(c, i);
-            i += 1;
-        }
-
-        AlfSymbols {
-            symbol_ids: symbols,
-        }
-    }
-
-    pub fn symbol_id(&self, c: char) -> u8 {
-        *self.symbol_ids.get(&c).expect("symbol not found")
-    }
-}
+use mix_core::chars::char_code;
 
 pub fn new_if_presudo_op(op: &str, line: &str) -> Option<MixalOp> {
     return match op {
         "EQU" => Some(MixalOp::new(op.to_string(), line.to_string())),
         "ORIG" => Some(MixalOp::new(op.to_string(), line.to_string())),
         "CON" => Some(MixalOp::new(op.to_string(), line.to_string())),
-        "ALF" => Some(MixalOp::new(op.to_string(), line.to_string())),
+        // trailing spaces of ALF are often trimmed by editors
+        "ALF" => Some(MixalOp::new(op.to_string(), format!("{line:<5}"))),
         "END" => Some(MixalOp::new(op.to_string(), line.to_string())),
+        "ENTRY" => Some(MixalOp::new(op.to_string(), line.to_string())),
+        "EXTERN" => Some(MixalOp::new(op.to_string(), line.to_string())),
         _ => None,
     };
 }
@@ -51,7 +25,7 @@ impl MixalOp {
     }
     pub fn parse_address(&self) -> String {
         return match &self.name[..] {
-            "ALF" => self.value[0..5].to_string(),
+            "ALF" => self.value.chars().take(5).collect(),
             _ => {
                 let (op, _) = split_whitespace_once(&self.value[..]);
                 op.to_string()
@@ -62,19 +36,15 @@ impl MixalOp {
         self.name.clone()
     }
 
-    pub fn alf_to_num(&self) -> [u8; 5] {
+    pub fn alf_to_num(&self) -> Result<[u8; 5], String> {
         if self.name != "ALF" {
             panic!("can't convert not ALF");
         }
-        let mut chars = self.value.chars();
-        let alf = AlfSymbols::new();
-        [
-          alf.symbol_id(chars.next().expect("error")),
-          alf.symbol_id(chars.next().expect("error")),
-          alf.symbol_id(chars.next().expect("error")),
-          alf.symbol_id(chars.next().expect("error")),
-          alf.symbol_id(chars.next().expect("error")),
-        ]
+        let mut result = [0; 5];
+        for (b, c) in result.iter_mut().zip(self.value.chars()) {
+            *b = char_code(c).ok_or(format!("'{c}' is not a MIX character"))?;
+        }
+        Ok(result)
     }
 }
 impl Clone for MixalOp {
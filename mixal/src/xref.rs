@@ -0,0 +1,117 @@
+use crate::parser::symbol_table::Symbol;
+use crate::parser::symbol_table::SymbolKind;
+
+/*
+ * Symbol table with the cross-reference, one symbol per line
+ *
+ * text -> name kind value defining_line uses...
+ * json -> [{"name": ..., "kind": ..., "value": ..., "line": ..., "uses": [...]}, ...]
+ */
+
+pub fn kind_name(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::Equ => "equ",
+        SymbolKind::Label => "label",
+        SymbolKind::Local => "local",
+        SymbolKind::Literal => "literal",
+        SymbolKind::External => "extern",
+    }
+}
+
+pub fn to_text(symbols: &[Symbol]) -> String {
+    let width = symbols.iter().map(|s| s.name.len()).max().unwrap_or(0).max(4);
+
+    let mut result = format!(
+        "{:width$} {:7} {:>11} {:>5}  USES\n",
+        "NAME", "KIND", "VALUE", "LINE"
+    );
+    for symbol in symbols {
+        let uses: Vec<String> = symbol.uses.iter().map(|l| l.to_string()).collect();
+        let line = format!(
+            "{:width$} {:7} {:>11} {:>5}  {}",
+            symbol.name,
+            kind_name(symbol.kind),
+            symbol.value,
+            symbol.line,
+            uses.join(" ")
+        );
+        result += line.trim_end();
+        result += "\n";
+    }
+    result
+}
+
+pub fn to_json(symbols: &[Symbol]) -> String {
+    let items: Vec<String> = symbols
+        .iter()
+        .map(|symbol| {
+            let uses: Vec<String> = symbol.uses.iter().map(|l| l.to_string()).collect();
+            format!(
+                "  {{\"name\": \"{}\", \"kind\": \"{}\", \"value\": {}, \"line\": {}, \"uses\": [{}]}}",
+                json_escape(&symbol.name),
+                kind_name(symbol.kind),
+                symbol.value,
+                symbol.line,
+                uses.join(", ")
+            )
+        })
+        .collect();
+
+    if items.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", items.join(",\n"))
+}
+
+fn json_escape(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            c if (c as u32) < 0x20 => result += &format!("\\u{:04x}", c as u32),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble, Options};
+
+    #[test]
+    fn cross_reference() {
+        let source = "\
+X EQU 1000
+ ORIG 3000
+START LDA X
+2H INC1 1
+ JMP 2B
+ LDA =X+1=
+ JMP START
+ END START
+";
+        let assembly = assemble(source, &Options::new("p.mixal")).expect("valid program");
+
+        assert_eq!(
+            "NAME  KIND          VALUE  LINE  USES\n\
+             2H    local          3001     4  5\n\
+             =X+1= literal        3005     6  6\n\
+             START label          3000     3  7 8\n\
+             X     equ            1000     1  3 6\n",
+            to_text(&assembly.symbols)
+        );
+        assert_eq!(
+            "[\n\
+             \x20 {\"name\": \"2H\", \"kind\": \"local\", \"value\": 3001, \"line\": 4, \"uses\": [5]},\n\
+             \x20 {\"name\": \"=X+1=\", \"kind\": \"literal\", \"value\": 3005, \"line\": 6, \"uses\": [6]},\n\
+             \x20 {\"name\": \"START\", \"kind\": \"label\", \"value\": 3000, \"line\": 3, \"uses\": [7, 8]},\n\
+             \x20 {\"name\": \"X\", \"kind\": \"equ\", \"value\": 1000, \"line\": 1, \"uses\": [3, 6]}\n\
+             ]\n",
+            to_json(&assembly.symbols)
+        );
+        assert_eq!("[]\n", to_json(&[]));
+    }
+}